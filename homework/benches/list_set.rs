@@ -0,0 +1,327 @@
+//! Compares the lock-coupling, optimistic, lazy and lock-free list sets under read-heavy
+//! workloads.
+//!
+//! Lock-coupling makes every reader take every lock on its way, so readers serialize behind each
+//! other at the head. Optimistic readers traverse without locks and only lock their position,
+//! so they should scale with the number of threads. Lazy readers take no lock at all, so they
+//! should not slow down behind writers either.
+//!
+//! The `workloads` group compares lock-coupling with a single lock over a `BTreeSet`, under
+//! contains-heavy, insert-heavy and mixed workloads on small and large key ranges.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Barrier;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crossbeam_utils::thread::scope;
+use rand::{thread_rng, Rng};
+
+use cs431_homework::{HarrisListSet, LazyListSet, LockSet, OptimisticListSet, OrderedListSet};
+
+/// Operations per thread.
+const OPS: usize = 1_000;
+
+/// Keys are drawn from `0..KEYS`, and every other one is inserted before measuring.
+const KEYS: usize = 256;
+
+const THREADS: &[usize] = &[1, 4, 16];
+
+trait BenchSet: Default + Sync {
+    fn contains(&self, key: &usize) -> bool;
+    fn insert(&self, key: usize) -> bool;
+    fn remove(&self, key: &usize) -> bool;
+}
+
+impl BenchSet for OrderedListSet<usize> {
+    fn contains(&self, key: &usize) -> bool {
+        OrderedListSet::contains(self, key)
+    }
+
+    fn insert(&self, key: usize) -> bool {
+        OrderedListSet::insert(self, key).is_ok()
+    }
+
+    fn remove(&self, key: &usize) -> bool {
+        OrderedListSet::remove(self, key).is_some()
+    }
+}
+
+impl BenchSet for OptimisticListSet<usize> {
+    fn contains(&self, key: &usize) -> bool {
+        OptimisticListSet::contains(self, key)
+    }
+
+    fn insert(&self, key: usize) -> bool {
+        OptimisticListSet::insert(self, key).is_ok()
+    }
+
+    fn remove(&self, key: &usize) -> bool {
+        OptimisticListSet::remove(self, key).is_some()
+    }
+}
+
+impl BenchSet for LazyListSet<usize> {
+    fn contains(&self, key: &usize) -> bool {
+        LazyListSet::contains(self, key)
+    }
+
+    fn insert(&self, key: usize) -> bool {
+        LazyListSet::insert(self, key).is_ok()
+    }
+
+    fn remove(&self, key: &usize) -> bool {
+        LazyListSet::remove(self, key).is_some()
+    }
+}
+
+impl BenchSet for LockSet<usize> {
+    fn contains(&self, key: &usize) -> bool {
+        LockSet::contains(self, key)
+    }
+
+    fn insert(&self, key: usize) -> bool {
+        LockSet::insert(self, key).is_ok()
+    }
+
+    fn remove(&self, key: &usize) -> bool {
+        LockSet::remove(self, key).is_some()
+    }
+}
+
+impl BenchSet for HarrisListSet<usize> {
+    fn contains(&self, key: &usize) -> bool {
+        HarrisListSet::contains(self, key)
+    }
+
+    fn insert(&self, key: usize) -> bool {
+        HarrisListSet::insert(self, key).is_ok()
+    }
+
+    fn remove(&self, key: &usize) -> bool {
+        HarrisListSet::remove(self, key).is_some()
+    }
+}
+
+/// Inserts every other key of `0..keys`, in decreasing order so that every list inserts at its
+/// head and large ranges are filled in linear time.
+fn prefill<S: BenchSet>(keys: usize) -> S {
+    let set = S::default();
+    (0..keys).step_by(2).rev().for_each(|k| {
+        let _ = set.insert(k);
+    });
+    set
+}
+
+/// The fractions of lookups and inserts among the operations. The rest are removes.
+#[derive(Clone, Copy)]
+struct Mix {
+    contains: f64,
+    insert: f64,
+}
+
+/// Runs `iters` rounds of `ops` operations on each of `threads` threads, with keys drawn from
+/// `0..keys` and operations in the proportions of `mix`.
+fn run<S: BenchSet>(threads: usize, keys: usize, ops: usize, mix: Mix, iters: u64) -> Duration {
+    let mut total = Duration::ZERO;
+    for _ in 0..iters {
+        let set = prefill::<S>(keys);
+        let barrier = Barrier::new(threads + 1);
+        total += scope(|scope| {
+            for _ in 0..threads {
+                let _ = scope.spawn(|_| {
+                    let mut rng = thread_rng();
+                    let _ = barrier.wait();
+                    for _ in 0..ops {
+                        let key = rng.gen_range(0..keys);
+                        let op = rng.gen::<f64>();
+                        if op < mix.contains {
+                            let _ = set.contains(&key);
+                        } else if op < mix.contains + mix.insert {
+                            let _ = set.insert(key);
+                        } else {
+                            let _ = set.remove(&key);
+                        }
+                    }
+                });
+            }
+            let _ = barrier.wait();
+            Instant::now()
+        })
+        .unwrap()
+        .elapsed();
+    }
+    total
+}
+
+fn read_heavy(c: &mut Criterion) {
+    for &(name, writes) in &[("read_only", 0.0), ("read_mostly", 0.1)] {
+        let mix = Mix {
+            contains: 1.0 - writes,
+            insert: writes / 2.0,
+        };
+        let mut group = c.benchmark_group(name);
+        for &threads in THREADS {
+            let _ = group.throughput(Throughput::Elements((threads * OPS) as u64));
+            let _ = group.bench_with_input(
+                BenchmarkId::new("lock_coupling", threads),
+                &threads,
+                |b, &t| b.iter_custom(|iters| run::<OrderedListSet<_>>(t, KEYS, OPS, mix, iters)),
+            );
+            let _ = group.bench_with_input(
+                BenchmarkId::new("optimistic", threads),
+                &threads,
+                |b, &t| {
+                    b.iter_custom(|iters| run::<OptimisticListSet<_>>(t, KEYS, OPS, mix, iters))
+                },
+            );
+            let _ = group.bench_with_input(BenchmarkId::new("lazy", threads), &threads, |b, &t| {
+                b.iter_custom(|iters| run::<LazyListSet<_>>(t, KEYS, OPS, mix, iters))
+            });
+            let _ =
+                group.bench_with_input(BenchmarkId::new("harris", threads), &threads, |b, &t| {
+                    b.iter_custom(|iters| run::<HarrisListSet<_>>(t, KEYS, OPS, mix, iters))
+                });
+        }
+        group.finish();
+    }
+}
+
+/// Writers inserting and removing keys until the readers finish.
+const WRITERS: usize = 2;
+
+/// Runs `iters` rounds of `OPS` lookups on each of `threads` threads, while `WRITERS` threads
+/// keep modifying the set. Only the slowest reader is timed.
+fn run_with_writers<S: BenchSet>(threads: usize, iters: u64) -> Duration {
+    let mut total = Duration::ZERO;
+    for _ in 0..iters {
+        let set = prefill::<S>(KEYS);
+        let done = AtomicBool::new(false);
+        let barrier = Barrier::new(threads + WRITERS + 1);
+        total += scope(|scope| {
+            for _ in 0..WRITERS {
+                let _ = scope.spawn(|_| {
+                    let mut rng = thread_rng();
+                    let _ = barrier.wait();
+                    while !done.load(Ordering::Relaxed) {
+                        let key = rng.gen_range(0..KEYS);
+                        if !set.insert(key) {
+                            let _ = set.remove(&key);
+                        }
+                    }
+                });
+            }
+            let readers = (0..threads)
+                .map(|_| {
+                    scope.spawn(|_| {
+                        let mut rng = thread_rng();
+                        let _ = barrier.wait();
+                        let start = Instant::now();
+                        for _ in 0..OPS {
+                            let _ = set.contains(&rng.gen_range(0..KEYS));
+                        }
+                        start.elapsed()
+                    })
+                })
+                .collect::<Vec<_>>();
+            let _ = barrier.wait();
+            // Timed by the readers themselves, since they may finish before this thread resumes.
+            let elapsed = readers.into_iter().map(|h| h.join().unwrap()).max();
+            done.store(true, Ordering::Relaxed);
+            elapsed.unwrap_or_default()
+        })
+        .unwrap();
+    }
+    total
+}
+
+fn contains_with_writers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("contains_with_writers");
+    for &threads in THREADS {
+        let _ = group.throughput(Throughput::Elements((threads * OPS) as u64));
+        let _ = group.bench_with_input(
+            BenchmarkId::new("lock_coupling", threads),
+            &threads,
+            |b, &t| b.iter_custom(|iters| run_with_writers::<OrderedListSet<_>>(t, iters)),
+        );
+        let _ = group.bench_with_input(
+            BenchmarkId::new("optimistic", threads),
+            &threads,
+            |b, &t| b.iter_custom(|iters| run_with_writers::<OptimisticListSet<_>>(t, iters)),
+        );
+        let _ = group.bench_with_input(BenchmarkId::new("lazy", threads), &threads, |b, &t| {
+            b.iter_custom(|iters| run_with_writers::<LazyListSet<_>>(t, iters))
+        });
+        let _ = group.bench_with_input(BenchmarkId::new("harris", threads), &threads, |b, &t| {
+            b.iter_custom(|iters| run_with_writers::<HarrisListSet<_>>(t, iters))
+        });
+    }
+    group.finish();
+}
+
+/// Operations per thread in `workloads`, fewer than `OPS` since a traversal of the large range
+/// is long.
+const WORKLOAD_OPS: usize = 100;
+
+fn workloads(c: &mut Criterion) {
+    let mixes = [
+        (
+            "contains_heavy",
+            Mix {
+                contains: 0.9,
+                insert: 0.05,
+            },
+        ),
+        (
+            "insert_heavy",
+            Mix {
+                contains: 0.1,
+                insert: 0.8,
+            },
+        ),
+        (
+            "mixed",
+            Mix {
+                contains: 0.5,
+                insert: 0.25,
+            },
+        ),
+    ];
+    for &(name, mix) in &mixes {
+        for &keys in &[1_000, 100_000] {
+            let mut group = c.benchmark_group(format!("{}_{}", name, keys));
+            for &threads in THREADS {
+                let _ = group.throughput(Throughput::Elements((threads * WORKLOAD_OPS) as u64));
+                let _ = group.bench_with_input(
+                    BenchmarkId::new("lock_coupling", threads),
+                    &threads,
+                    |b, &t| {
+                        b.iter_custom(|iters| {
+                            run::<OrderedListSet<_>>(t, keys, WORKLOAD_OPS, mix, iters)
+                        })
+                    },
+                );
+                let _ = group.bench_with_input(
+                    BenchmarkId::new("btree_lock", threads),
+                    &threads,
+                    |b, &t| {
+                        b.iter_custom(|iters| run::<LockSet<_>>(t, keys, WORKLOAD_OPS, mix, iters))
+                    },
+                );
+                let _ = group.bench_with_input(
+                    BenchmarkId::new("optimistic", threads),
+                    &threads,
+                    |b, &t| {
+                        b.iter_custom(|iters| {
+                            run::<OptimisticListSet<_>>(t, keys, WORKLOAD_OPS, mix, iters)
+                        })
+                    },
+                );
+            }
+            group.finish();
+        }
+    }
+}
+
+criterion_group!(benches, read_heavy, contains_with_writers, workloads);
+criterion_main!(benches);
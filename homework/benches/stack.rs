@@ -0,0 +1,257 @@
+//! Compares elimination-backoff stacks against Treiber's stack and lock-based baselines.
+//!
+//! Each thread performs the same number of balanced push/pop pairs, so results are comparable
+//! across thread counts.
+
+use std::sync::{Barrier, Mutex};
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crossbeam_utils::thread::scope;
+
+use cs431_homework::{ElimStack, SpinLockStack, Stack, StripedStack, TreiberStack};
+
+/// Push/pop pairs per thread.
+const PAIRS: usize = 1_000;
+
+/// Values pushed before measuring, so that pops rarely find the stack empty.
+const WARM_UP: usize = 1_024;
+
+const THREADS: &[usize] = &[1, 4, 16, 64];
+
+trait BenchStack: Default + Sync {
+    fn push(&self, t: usize);
+    fn pop(&self) -> Option<usize>;
+    fn clear(&self);
+}
+
+impl BenchStack for TreiberStack<usize> {
+    fn push(&self, t: usize) {
+        Stack::push(self, t)
+    }
+
+    fn pop(&self) -> Option<usize> {
+        Stack::pop(self)
+    }
+
+    fn clear(&self) {
+        drop(self.drain_all());
+    }
+}
+
+impl BenchStack for ElimStack<usize> {
+    fn push(&self, t: usize) {
+        Stack::push(self, t)
+    }
+
+    fn pop(&self) -> Option<usize> {
+        Stack::pop(self)
+    }
+
+    fn clear(&self) {
+        drop(self.drain_all());
+    }
+}
+
+impl BenchStack for SpinLockStack<usize> {
+    fn push(&self, t: usize) {
+        Stack::push(self, t)
+    }
+
+    fn pop(&self) -> Option<usize> {
+        Stack::pop(self)
+    }
+
+    fn clear(&self) {
+        drop(self.drain_all());
+    }
+}
+
+impl BenchStack for StripedStack<usize, ElimStack<usize>> {
+    fn push(&self, t: usize) {
+        Stack::push(self, t)
+    }
+
+    fn pop(&self) -> Option<usize> {
+        Stack::pop(self)
+    }
+
+    fn clear(&self) {
+        drop(self.drain_all());
+    }
+}
+
+#[derive(Default)]
+struct MutexStack(Mutex<Vec<usize>>);
+
+impl BenchStack for MutexStack {
+    fn push(&self, t: usize) {
+        self.0.lock().unwrap().push(t);
+    }
+
+    fn pop(&self) -> Option<usize> {
+        self.0.lock().unwrap().pop()
+    }
+
+    fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
+
+/// Runs `iters` rounds of `PAIRS` push/pop pairs on each of `threads` threads.
+fn run<S: BenchStack>(stack: &S, threads: usize, iters: u64) -> Duration {
+    let mut total = Duration::ZERO;
+    for _ in 0..iters {
+        stack.clear();
+        (0..WARM_UP).for_each(|i| stack.push(i));
+
+        let barrier = Barrier::new(threads + 1);
+        total += scope(|scope| {
+            for _ in 0..threads {
+                let _ = scope.spawn(|_| {
+                    let _ = barrier.wait();
+                    for i in 0..PAIRS {
+                        stack.push(i);
+                        let _ = stack.pop();
+                    }
+                });
+            }
+            let _ = barrier.wait();
+            Instant::now()
+        })
+        .unwrap()
+        .elapsed();
+    }
+    total
+}
+
+fn push_pop(c: &mut Criterion) {
+    let mut group = c.benchmark_group("push_pop");
+    for &threads in THREADS {
+        let _ = group.throughput(Throughput::Elements((threads * PAIRS) as u64));
+
+        let stack = TreiberStack::default();
+        let _ = group.bench_with_input(BenchmarkId::new("treiber", threads), &threads, |b, &t| {
+            b.iter_custom(|iters| run(&stack, t, iters))
+        });
+
+        let stack = TreiberStack::with_node_cache(WARM_UP);
+        let _ = group.bench_with_input(
+            BenchmarkId::new("treiber_cached", threads),
+            &threads,
+            |b, &t| b.iter_custom(|iters| run(&stack, t, iters)),
+        );
+
+        let stack = ElimStack::default();
+        let _ = group.bench_with_input(BenchmarkId::new("elim", threads), &threads, |b, &t| {
+            b.iter_custom(|iters| run(&stack, t, iters))
+        });
+
+        let stack = StripedStack::<_, ElimStack<_>>::default();
+        let _ = group.bench_with_input(BenchmarkId::new("striped", threads), &threads, |b, &t| {
+            b.iter_custom(|iters| run(&stack, t, iters))
+        });
+
+        let stack = SpinLockStack::default();
+        let _ = group.bench_with_input(BenchmarkId::new("spinlock", threads), &threads, |b, &t| {
+            b.iter_custom(|iters| run(&stack, t, iters))
+        });
+
+        let stack = MutexStack::default();
+        let _ = group.bench_with_input(BenchmarkId::new("mutex", threads), &threads, |b, &t| {
+            b.iter_custom(|iters| run(&stack, t, iters))
+        });
+    }
+    group.finish();
+}
+
+/// Two stacks placed next to each other in one struct.
+#[derive(Default)]
+struct Adjacent<S> {
+    first: S,
+    second: S,
+}
+
+/// Same as [`run`], but half of the threads use `stacks.first` and the others `stacks.second`.
+fn run_adjacent<S: BenchStack>(stacks: &Adjacent<S>, threads: usize, iters: u64) -> Duration {
+    let mut total = Duration::ZERO;
+    for _ in 0..iters {
+        for stack in &[&stacks.first, &stacks.second] {
+            stack.clear();
+            (0..WARM_UP).for_each(|i| stack.push(i));
+        }
+
+        let barrier = Barrier::new(threads + 1);
+        total += scope(|scope| {
+            for t in 0..threads {
+                let stack = if t % 2 == 0 {
+                    &stacks.first
+                } else {
+                    &stacks.second
+                };
+                let barrier = &barrier;
+                let _ = scope.spawn(move |_| {
+                    let _ = barrier.wait();
+                    for i in 0..PAIRS {
+                        stack.push(i);
+                        let _ = stack.pop();
+                    }
+                });
+            }
+            let _ = barrier.wait();
+            Instant::now()
+        })
+        .unwrap()
+        .elapsed();
+    }
+    total
+}
+
+/// Independent stacks sharing a struct. The heads of Treiber's and elimination stacks are
+/// cache-padded, so they should scale like a single stack with half the threads, while the
+/// unpadded mutexes false-share.
+fn adjacent(c: &mut Criterion) {
+    let mut group = c.benchmark_group("adjacent");
+    for &threads in THREADS.iter().filter(|&&t| t > 1) {
+        let _ = group.throughput(Throughput::Elements((threads * PAIRS) as u64));
+
+        let stacks = Adjacent::<TreiberStack<_>>::default();
+        let _ = group.bench_with_input(BenchmarkId::new("treiber", threads), &threads, |b, &t| {
+            b.iter_custom(|iters| run_adjacent(&stacks, t, iters))
+        });
+
+        let stacks = Adjacent::<ElimStack<_>>::default();
+        let _ = group.bench_with_input(BenchmarkId::new("elim", threads), &threads, |b, &t| {
+            b.iter_custom(|iters| run_adjacent(&stacks, t, iters))
+        });
+
+        let stacks = Adjacent::<MutexStack>::default();
+        let _ = group.bench_with_input(BenchmarkId::new("mutex", threads), &threads, |b, &t| {
+            b.iter_custom(|iters| run_adjacent(&stacks, t, iters))
+        });
+    }
+    group.finish();
+}
+
+/// Two threads, whose CAS failures are too rare for elimination partners to show up. Elimination
+/// is skipped after repeated timeouts, so the elimination stack should stay close to Treiber's.
+fn low_contention(c: &mut Criterion) {
+    const THREADS: usize = 2;
+
+    let mut group = c.benchmark_group("low_contention");
+    let _ = group.throughput(Throughput::Elements((THREADS * PAIRS) as u64));
+
+    let stack = TreiberStack::default();
+    let _ = group.bench_function("treiber", |b| {
+        b.iter_custom(|iters| run(&stack, THREADS, iters))
+    });
+
+    let stack = ElimStack::default();
+    let _ = group.bench_function("elim", |b| {
+        b.iter_custom(|iters| run(&stack, THREADS, iters))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, push_pop, adjacent, low_contention);
+criterion_main!(benches);
@@ -0,0 +1,95 @@
+//! Compares strong and weak CAS in the retry loops of Treiber's stack.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use crossbeam_utils::thread::scope;
+
+use cs431_homework::{Stack, TreiberStack};
+
+const OPS: usize = 10_000;
+
+fn push(c: &mut Criterion) {
+    let mut group = c.benchmark_group("push");
+    for &threads in &[1, 4] {
+        let _ = group.bench_with_input(
+            BenchmarkId::new("strong", threads),
+            &threads,
+            |b, &threads| {
+                b.iter(|| {
+                    let stack = TreiberStack::default();
+                    scope(|scope| {
+                        for _ in 0..threads {
+                            let _ = scope.spawn(|_| (0..OPS).for_each(|i| stack.push(i)));
+                        }
+                    })
+                    .unwrap();
+                })
+            },
+        );
+        let _ = group.bench_with_input(
+            BenchmarkId::new("weak", threads),
+            &threads,
+            |b, &threads| {
+                b.iter(|| {
+                    let stack = TreiberStack::default();
+                    scope(|scope| {
+                        for _ in 0..threads {
+                            let _ = scope.spawn(|_| (0..OPS).for_each(|i| stack.push_weak(i)));
+                        }
+                    })
+                    .unwrap();
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn push_pop(c: &mut Criterion) {
+    let mut group = c.benchmark_group("push_pop");
+    for &threads in &[1, 4] {
+        let _ = group.bench_with_input(
+            BenchmarkId::new("strong", threads),
+            &threads,
+            |b, &threads| {
+                let stack = TreiberStack::default();
+                b.iter(|| {
+                    scope(|scope| {
+                        for _ in 0..threads {
+                            let _ = scope.spawn(|_| {
+                                for i in 0..OPS {
+                                    stack.push(i);
+                                    let _ = stack.pop();
+                                }
+                            });
+                        }
+                    })
+                    .unwrap();
+                })
+            },
+        );
+        let _ = group.bench_with_input(
+            BenchmarkId::new("weak", threads),
+            &threads,
+            |b, &threads| {
+                let stack = TreiberStack::default();
+                b.iter(|| {
+                    scope(|scope| {
+                        for _ in 0..threads {
+                            let _ = scope.spawn(|_| {
+                                for i in 0..OPS {
+                                    stack.push_weak(i);
+                                    let _ = stack.pop_weak();
+                                }
+                            });
+                        }
+                    })
+                    .unwrap();
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, push, push_pop);
+criterion_main!(benches);
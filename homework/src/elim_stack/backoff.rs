@@ -0,0 +1,63 @@
+use std::thread;
+
+/// Strategy for waiting between the attempts of a retry loop.
+///
+/// A fresh value is created for each operation, so the state may be kept in `self`.
+pub trait Backoff: Default {
+    /// Waits after a failed attempt.
+    fn snooze(&mut self);
+
+    /// Resets the state after a successful attempt.
+    fn reset(&mut self);
+}
+
+/// Spins once between attempts.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SpinBackoff;
+
+impl Backoff for SpinBackoff {
+    #[inline]
+    fn snooze(&mut self) {
+        core::hint::spin_loop();
+    }
+
+    #[inline]
+    fn reset(&mut self) {}
+}
+
+/// Spins twice as long as the last time between attempts, up to a limit.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ExponentialBackoff {
+    step: u32,
+}
+
+impl ExponentialBackoff {
+    const SPIN_LIMIT: u32 = 10;
+}
+
+impl Backoff for ExponentialBackoff {
+    fn snooze(&mut self) {
+        for _ in 0..1u32 << self.step {
+            core::hint::spin_loop();
+        }
+        if self.step < Self::SPIN_LIMIT {
+            self.step += 1;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.step = 0;
+    }
+}
+
+/// Yields to the OS scheduler between attempts, for systems with more threads than cores.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct YieldBackoff;
+
+impl Backoff for YieldBackoff {
+    fn snooze(&mut self) {
+        thread::yield_now();
+    }
+
+    fn reset(&mut self) {}
+}
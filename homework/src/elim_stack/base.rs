@@ -1,31 +1,72 @@
+use core::any;
+use core::fmt;
 use core::marker::PhantomData;
 use core::mem::ManuallyDrop;
-use core::ops::Deref;
-use crossbeam_epoch::{pin, Atomic, Guard, Owned};
+use core::ptr;
+use core::sync::atomic::{fence, AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use crossbeam_epoch::{pin, Guard, Owned};
+use crossbeam_utils::CachePadded;
 use rand::{thread_rng, Rng};
+
+use super::backoff::{Backoff, SpinBackoff};
+use super::exchanger::Exchanger;
+use super::treiber_stack::Node;
+use std::sync::{Condvar, Mutex};
 use std::time;
 
-pub const ELIM_SIZE: usize = 16;
+/// Default size of the elimination array.
+pub const DEFAULT_ELIM_SIZE: usize = 16;
+/// Number of times `flush_reclamation` pins and flushes, each of which may advance the epoch.
+const FLUSH_ROUNDS: usize = 4;
+
 pub const ELIM_DELAY: time::Duration = time::Duration::from_millis(10);
 
 #[inline]
-pub fn get_random_elim_index() -> usize {
-    thread_rng().gen::<usize>() % ELIM_SIZE
+pub fn get_random_elim_index(elim_size: usize) -> usize {
+    thread_rng().gen::<usize>() % elim_size
 }
 
-/// Concurrent stack types.
-pub trait Stack<T>: Default {
-    /// Push request type.
-    type PushReq: From<T> + Deref<Target = ManuallyDrop<T>>;
+/// Source of randomness for picking elimination slots.
+#[derive(Debug)]
+pub(crate) enum ElimRng {
+    /// The thread-local generator.
+    Thread,
+    /// A generator shared by all threads, which draws the same sequence for the same seed.
+    Seeded(AtomicU64),
+}
 
+impl ElimRng {
+    pub(crate) fn seeded(seed: u64) -> Self {
+        Self::Seeded(AtomicU64::new(seed))
+    }
+
+    /// Picks an index below `elim_size`.
+    pub(crate) fn gen_index(&self, elim_size: usize) -> usize {
+        match self {
+            Self::Thread => get_random_elim_index(elim_size),
+            Self::Seeded(state) => {
+                // SplitMix64, with the state advanced atomically.
+                const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+                let mut z = state
+                    .fetch_add(GAMMA, Ordering::Relaxed)
+                    .wrapping_add(GAMMA);
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                ((z ^ (z >> 31)) % elim_size as u64) as usize
+            }
+        }
+    }
+}
+
+/// Concurrent stack types, whose retry loops wait with `B`.
+///
+/// The trait is object-safe, so that implementations can be picked at runtime behind
+/// `Box<dyn Stack<T>>`.
+pub trait Stack<T, B: Backoff = SpinBackoff> {
     /// Tries to push a value to the stack.
     ///
     /// Returns `Ok(())` if the push request is served; `Err(req)` is CAS failed.
-    fn try_push(
-        &self,
-        req: Owned<Self::PushReq>,
-        guard: &Guard,
-    ) -> Result<(), Owned<Self::PushReq>>;
+    fn try_push(&self, req: Owned<Node<T>>, guard: &Guard) -> Result<(), Owned<Node<T>>>;
 
     /// Tries to pop a value from the stack.
     ///
@@ -34,46 +75,720 @@ pub trait Stack<T>: Default {
     fn try_pop(&self, guard: &Guard) -> Result<Option<T>, ()>;
 
     /// Returns `true` if the stack is empty.
+    ///
+    /// The answer is instantaneous: concurrent operations may change it right after it returns.
     fn is_empty(&self, guard: &Guard) -> bool;
 
     /// Pushes a value to the stack.
     fn push(&self, t: T) {
-        let mut req = Owned::new(Self::PushReq::from(t));
-        let guard = pin();
+        self.push_with(t, &pin());
+    }
+
+    /// Pushes a value to the stack under an existing guard.
+    ///
+    /// Useful for batches of operations, as the epoch is not pinned again for each of them.
+    fn push_with(&self, t: T, guard: &Guard) {
+        let mut req = Owned::new(Node::from(t));
+        let mut backoff = B::default();
         loop {
-            match self.try_push(req, &guard) {
+            match self.try_push(req, guard) {
                 Ok(_) => break,
                 Err(r) => req = r,
             }
+            backoff.snooze();
+        }
+    }
+
+    /// Pushes every value of `iter` to the stack.
+    ///
+    /// The values are pushed in order, so popping returns them in reverse.
+    fn push_iter<I: IntoIterator<Item = T>>(&self, iter: I)
+    where
+        Self: Sized,
+    {
+        for t in iter {
+            self.push(t);
+        }
+    }
+
+    /// Tries to push a value to the stack at most `max_attempts` times.
+    ///
+    /// Returns `Err(t)` with the original value if every attempt failed.
+    fn try_push_bounded(&self, t: T, max_attempts: usize) -> Result<(), T> {
+        let mut req = Owned::new(Node::from(t));
+        let guard = pin();
+        let mut backoff = B::default();
+        for _ in 0..max_attempts {
+            match self.try_push(req, &guard) {
+                Ok(_) => return Ok(()),
+                Err(r) => req = r,
+            }
+            backoff.snooze();
         }
+        Err(unsafe { ManuallyDrop::into_inner(ptr::read(&**req)) })
     }
 
     /// Pops a value from the stack.
     ///
     /// Returns `Some(v)` if `v` is popped; `None` if the stack is empty.
     fn pop(&self) -> Option<T> {
-        let guard = pin();
+        self.pop_with(&pin())
+    }
+
+    /// Pops a value from the stack under an existing guard.
+    ///
+    /// Returns `Some(v)` if `v` is popped; `None` if the stack is empty.
+    fn pop_with(&self, guard: &Guard) -> Option<T> {
+        let mut backoff = B::default();
         loop {
-            if let Ok(result) = self.try_pop(&guard) {
+            if let Ok(result) = self.try_pop(guard) {
                 return result;
             }
+            backoff.snooze();
+        }
+    }
+
+    /// Pops at most `n` values from the stack.
+    ///
+    /// Returns the popped values in LIFO order. Fewer than `n` values are returned if the stack
+    /// runs out of values.
+    fn pop_n(&self, n: usize) -> Vec<T> {
+        let mut result = Vec::new();
+        let _ = self.pop_many_into(&mut result, n);
+        result
+    }
+
+    /// Pops at most `n` values from the stack and appends them to `buf`.
+    ///
+    /// The values are appended in LIFO order after the existing contents of `buf`, reusing its
+    /// capacity. Returns the number of values appended.
+    fn pop_many_into(&self, buf: &mut Vec<T>, n: usize) -> usize {
+        let guard = pin();
+        let mut backoff = B::default();
+        let mut count = 0;
+        while count < n {
+            match self.try_pop(&guard) {
+                Ok(Some(t)) => {
+                    buf.push(t);
+                    count += 1;
+                    backoff.reset();
+                }
+                Ok(None) => break,
+                Err(()) => backoff.snooze(),
+            }
+        }
+        count
+    }
+
+    /// Pops values and passes each of them to `f` under a single pin, until `f` returns `false`
+    /// or the stack is empty.
+    ///
+    /// Returns the number of values passed to `f`, including the one for which it returned
+    /// `false`. Each value is owned by `f` once passed, so it is dropped if `f` panics.
+    fn pop_each<F: FnMut(T) -> bool>(&self, mut f: F) -> usize
+    where
+        Self: Sized,
+    {
+        let guard = pin();
+        let mut backoff = B::default();
+        let mut count = 0;
+        loop {
+            match self.try_pop(&guard) {
+                Ok(Some(t)) => {
+                    count += 1;
+                    if !f(t) {
+                        return count;
+                    }
+                    backoff.reset();
+                }
+                Ok(None) => return count,
+                Err(()) => backoff.snooze(),
+            }
         }
     }
+
+    /// Asks the epoch collector to free the nodes retired by this thread.
+    ///
+    /// Nodes removed from the stack are freed only once no thread can access them, which may be
+    /// much later. This flushes them to the collector and tries to advance the epoch, but threads
+    /// pinned elsewhere can still hold them back.
+    fn flush_reclamation(&self) {
+        for _ in 0..FLUSH_ROUNDS {
+            pin().flush();
+        }
+    }
+
+    /// Pops every value from the stack.
+    ///
+    /// Returns the popped values in LIFO order, i.e. the top of the stack comes first.
+    fn drain_all(&self) -> Vec<T> {
+        let mut result = Vec::new();
+        let guard = pin();
+        let mut backoff = B::default();
+        loop {
+            match self.try_pop(&guard) {
+                Ok(Some(t)) => {
+                    result.push(t);
+                    backoff.reset();
+                }
+                Ok(None) => return result,
+                Err(()) => backoff.snooze(),
+            }
+        }
+    }
+
+    /// Returns the number of values, which is exact since no operation can be in progress.
+    ///
+    /// The default pops every value and pushes it back, so implementations that can count their
+    /// values in place override it.
+    fn len_mut(&mut self) -> usize {
+        let values = self.drain_all();
+        let len = values.len();
+        let guard = pin();
+        for t in values.into_iter().rev() {
+            self.push_with(t, &guard);
+        }
+        len
+    }
 }
 
-#[derive(Debug)]
-pub struct ElimStack<T, S: Stack<T>> {
+impl<T, B: Backoff, S: Stack<T, B> + ?Sized> Stack<T, B> for Box<S> {
+    fn try_push(&self, req: Owned<Node<T>>, guard: &Guard) -> Result<(), Owned<Node<T>>> {
+        (**self).try_push(req, guard)
+    }
+
+    fn try_pop(&self, guard: &Guard) -> Result<Option<T>, ()> {
+        (**self).try_pop(guard)
+    }
+
+    fn is_empty(&self, guard: &Guard) -> bool {
+        (**self).is_empty(guard)
+    }
+
+    fn push(&self, t: T) {
+        (**self).push(t)
+    }
+
+    fn push_with(&self, t: T, guard: &Guard) {
+        (**self).push_with(t, guard)
+    }
+
+    fn try_push_bounded(&self, t: T, max_attempts: usize) -> Result<(), T> {
+        (**self).try_push_bounded(t, max_attempts)
+    }
+
+    fn pop(&self) -> Option<T> {
+        (**self).pop()
+    }
+
+    fn pop_with(&self, guard: &Guard) -> Option<T> {
+        (**self).pop_with(guard)
+    }
+
+    fn pop_n(&self, n: usize) -> Vec<T> {
+        (**self).pop_n(n)
+    }
+
+    fn pop_many_into(&self, buf: &mut Vec<T>, n: usize) -> usize {
+        (**self).pop_many_into(buf, n)
+    }
+
+    fn flush_reclamation(&self) {
+        (**self).flush_reclamation()
+    }
+
+    fn drain_all(&self) -> Vec<T> {
+        (**self).drain_all()
+    }
+
+    fn len_mut(&mut self) -> usize {
+        (**self).len_mut()
+    }
+}
+
+/// Statistics of an elimination-backoff stack.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ElimStats {
+    /// Number of push/pop pairs completed via the elimination array.
+    pub exchanges: usize,
+    /// Number of push requests that timed out in the elimination array.
+    pub failed_exchanges: usize,
+    /// Number of push requests that skipped the elimination array after repeated timeouts.
+    pub skipped_exchanges: usize,
+    /// Number of operations completed directly on the inner stack.
+    pub direct_cas: usize,
+}
+
+/// Error of a single attempt of an operation, as the state of the stack changed under it.
+///
+/// The operation may succeed if tried again later.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Contended;
+
+/// Path through which an operation of an elimination-backoff stack completed.
+#[cfg(feature = "trace")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionPath {
+    /// Completed directly on the inner stack.
+    DirectCas,
+    /// Completed by an exchange in the elimination array.
+    Eliminated {
+        /// Number of failed attempts before the exchange.
+        retries: u32,
+    },
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ElimCounters {
+    pub(crate) exchanges: AtomicUsize,
+    pub(crate) failed_exchanges: AtomicUsize,
+    pub(crate) skipped_exchanges: AtomicUsize,
+    pub(crate) direct_cas: AtomicUsize,
+}
+
+/// Threads blocked until a value is pushed.
+#[derive(Debug, Default)]
+pub(crate) struct Waiters {
+    count: AtomicUsize,
+    lock: Mutex<()>,
+    cond: Condvar,
+    /// Number of times a thread went to sleep.
+    #[cfg(test)]
+    pub(crate) parks: AtomicUsize,
+}
+
+impl Waiters {
+    /// Wakes up a blocked thread, if any. Must be called after the push is published.
+    pub(crate) fn notify_one(&self) {
+        if self.has_waiters() {
+            drop(self.lock.lock().unwrap());
+            self.cond.notify_one();
+        }
+    }
+
+    /// Wakes up every blocked thread. Must be called after the pushes are published.
+    pub(crate) fn notify_all(&self) {
+        if self.has_waiters() {
+            drop(self.lock.lock().unwrap());
+            self.cond.notify_all();
+        }
+    }
+
+    fn has_waiters(&self) -> bool {
+        // Pairs with the fence in `wait`: either we see the waiter, or it sees our push.
+        fence(Ordering::SeqCst);
+        self.count.load(Ordering::Relaxed) > 0
+    }
+
+    /// Calls `f` until it returns `Some`, blocking in between until notified.
+    ///
+    /// Returns `None` if `deadline` passes first.
+    pub(crate) fn wait<R>(
+        &self,
+        mut f: impl FnMut() -> Option<R>,
+        deadline: Option<time::Instant>,
+    ) -> Option<R> {
+        if let Some(r) = f() {
+            return Some(r);
+        }
+
+        let mut lock = self.lock.lock().unwrap();
+        let _ = self.count.fetch_add(1, Ordering::Relaxed);
+        fence(Ordering::SeqCst);
+        let result = loop {
+            // Retried after registering, so that a push that did not see us is visible here.
+            if let Some(r) = f() {
+                break Some(r);
+            }
+            let timeout = match deadline {
+                None => None,
+                Some(deadline) => match deadline.checked_duration_since(time::Instant::now()) {
+                    Some(timeout) if timeout > time::Duration::ZERO => Some(timeout),
+                    _ => break None,
+                },
+            };
+            #[cfg(test)]
+            let _ = self.parks.fetch_add(1, Ordering::Relaxed);
+            lock = match timeout {
+                None => self.cond.wait(lock).unwrap(),
+                Some(timeout) => self.cond.wait_timeout(lock, timeout).unwrap().0,
+            };
+        };
+        let _ = self.count.fetch_sub(1, Ordering::Relaxed);
+        result
+    }
+}
+
+/// Elimination slot, where a pop offers `None` and a push offers its request.
+pub(crate) type Slot<T> = CachePadded<Exchanger<Option<Owned<Node<T>>>>>;
+
+pub struct ElimStack<T, S: Stack<T>, B, const ELIM_SIZE: usize> {
     pub(crate) inner: S,
-    pub(crate) slots: [Atomic<S::PushReq>; ELIM_SIZE],
-    _marker: PhantomData<T>,
+    /// Padded so that operations in adjacent slots do not false-share.
+    pub(crate) slots: [Slot<T>; ELIM_SIZE],
+    /// Number of slots at the front of `slots` that operations pick from.
+    pub(crate) active_elim_range: AtomicUsize,
+    pub(crate) rng: ElimRng,
+    pub(crate) len: AtomicUsize,
+    pub(crate) elim_wait: time::Duration,
+    /// Whether failed operations on `inner` try the elimination array.
+    pub(crate) elimination_enabled: AtomicBool,
+    /// Whether `pop_timeout` waits in the elimination array for a push.
+    pub(crate) pop_reservations: AtomicBool,
+    /// Number of consecutive timeouts of push requests in the elimination array, or of skipped
+    /// attempts once it reaches `SKIP_AFTER_FAILURES`. Reset by any exchange.
+    pub(crate) elim_failures: AtomicU32,
+    pub(crate) counters: ElimCounters,
+    pub(crate) waiters: Waiters,
+    /// Set when an exchange completes, so that tests can tell that elimination happened.
+    #[cfg(test)]
+    pub(crate) eliminated: AtomicBool,
+    _marker: PhantomData<(T, B)>,
 }
 
-impl<T, S: Stack<T>> Default for ElimStack<T, S> {
-    fn default() -> Self {
+impl<T, S: Stack<T>, B: Backoff, const ELIM_SIZE: usize> ElimStack<T, S, B, ELIM_SIZE> {
+    const ELIM_SIZE_NONZERO: () = assert!(ELIM_SIZE > 0, "elimination array must not be empty");
+
+    /// Creates a new stack on top of `inner`, which holds `len` values.
+    pub(crate) fn from_inner(inner: S, len: usize) -> Self {
+        // Fails to compile if `ELIM_SIZE` is zero.
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::ELIM_SIZE_NONZERO;
         Self {
-            inner: Default::default(),
-            slots: Default::default(),
+            inner,
+            slots: [(); ELIM_SIZE].map(|_| CachePadded::new(Exchanger::default())),
+            active_elim_range: AtomicUsize::new(1),
+            rng: ElimRng::Thread,
+            len: AtomicUsize::new(len),
+            elim_wait: ELIM_DELAY,
+            elimination_enabled: AtomicBool::new(true),
+            pop_reservations: AtomicBool::new(false),
+            elim_failures: AtomicU32::new(0),
+            counters: ElimCounters::default(),
+            waiters: Waiters::default(),
+            #[cfg(test)]
+            eliminated: AtomicBool::new(false),
             _marker: PhantomData,
         }
     }
+
+    /// Creates a new stack on top of `inner`, keeping its values.
+    ///
+    /// The values are counted for [`ElimStack::len`] with [`Stack::len_mut`].
+    pub fn new_with(mut inner: S) -> Self {
+        let len = inner.len_mut();
+        Self::from_inner(inner, len)
+    }
+
+    /// Returns the inner stack, discarding the elimination array.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Creates a new stack whose push requests wait `elim_wait` in the elimination array.
+    ///
+    /// With a zero wait, a push request is withdrawn right after it is offered.
+    ///
+    /// The wait halves with each consecutive timeout of a push request, and after a few timeouts
+    /// push requests skip the elimination array for a while. Any exchange restores the full wait.
+    pub fn with_elim_wait(elim_wait: time::Duration) -> Self
+    where
+        S: Default,
+    {
+        Self {
+            elim_wait,
+            ..Default::default()
+        }
+    }
+
+    /// Creates a new stack that picks elimination slots with a generator seeded with `seed`.
+    ///
+    /// The slots are drawn from a single sequence shared by all threads, so that the same
+    /// schedule of operations picks the same slots.
+    pub fn with_seed(seed: u64) -> Self
+    where
+        S: Default,
+    {
+        Self {
+            rng: ElimRng::seeded(seed),
+            ..Default::default()
+        }
+    }
+
+    /// Sets how long push requests wait in the elimination array.
+    pub fn set_elim_wait(&mut self, elim_wait: time::Duration) {
+        self.elim_wait = elim_wait;
+    }
+
+    /// Enables or disables the elimination array, which is enabled by default.
+    ///
+    /// While it is disabled, push and pop retry on the inner stack with backoff only, which avoids
+    /// the latency of elimination when there is little contention.
+    pub fn set_elimination_enabled(&self, enabled: bool) {
+        self.elimination_enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns whether the elimination array is enabled.
+    pub fn elimination_enabled(&self) -> bool {
+        self.elimination_enabled.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables pop reservations, which are disabled by default.
+    ///
+    /// While they are enabled, [`ElimStack::pop_timeout`] waits in the elimination array for a
+    /// push instead of only retrying on the inner stack, and every push first looks for such a
+    /// reservation in the active slots, handing its value over without touching the inner stack.
+    pub fn set_pop_reservations(&self, enabled: bool) {
+        self.pop_reservations.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns whether pop reservations are enabled.
+    pub fn pop_reservations(&self) -> bool {
+        self.pop_reservations.load(Ordering::Relaxed)
+    }
+
+    /// Returns the statistics of the elimination array.
+    pub fn stats(&self) -> ElimStats {
+        ElimStats {
+            exchanges: self.counters.exchanges.load(Ordering::Relaxed),
+            failed_exchanges: self.counters.failed_exchanges.load(Ordering::Relaxed),
+            skipped_exchanges: self.counters.skipped_exchanges.load(Ordering::Relaxed),
+            direct_cas: self.counters.direct_cas.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Resets the statistics of the elimination array.
+    pub fn reset_stats(&self) {
+        self.counters.exchanges.store(0, Ordering::Relaxed);
+        self.counters.failed_exchanges.store(0, Ordering::Relaxed);
+        self.counters.skipped_exchanges.store(0, Ordering::Relaxed);
+        self.counters.direct_cas.store(0, Ordering::Relaxed);
+    }
+}
+
+impl<T, S: Stack<T> + Default, B: Backoff, const ELIM_SIZE: usize> Default
+    for ElimStack<T, S, B, ELIM_SIZE>
+{
+    fn default() -> Self {
+        Self::from_inner(S::default(), 0)
+    }
+}
+
+impl<T, S: Stack<T> + fmt::Debug, B: Backoff, const ELIM_SIZE: usize> fmt::Debug
+    for ElimStack<T, S, B, ELIM_SIZE>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ElimStack")
+            .field("inner_type", &any::type_name::<S>())
+            .field("inner", &self.inner)
+            .field("len", &self.len.load(Ordering::Relaxed))
+            .field("stats", &self.stats())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crossbeam_utils::thread::scope;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// Operation of a conformance run.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    enum Op {
+        Push(u32),
+        PushIter(Vec<u32>),
+        Pop,
+        PopN(usize),
+        IsEmpty,
+        DrainAll,
+    }
+
+    /// Result of an [`Op`].
+    #[derive(Debug, PartialEq, Eq)]
+    enum Output {
+        Pushed,
+        Popped(Option<u32>),
+        PoppedN(Vec<u32>),
+        IsEmpty(bool),
+    }
+
+    impl Op {
+        /// Generates a random operation, favoring pushes so that the stack grows deep enough.
+        fn random<R: Rng>(rng: &mut R) -> Self {
+            match rng.gen_range(0..16) {
+                0..=5 => Op::Push(rng.gen()),
+                6 => Op::PushIter((0..rng.gen_range(0..8)).map(|_| rng.gen()).collect()),
+                7..=11 => Op::Pop,
+                12 => Op::PopN(rng.gen_range(0..8)),
+                13 | 14 => Op::IsEmpty,
+                _ => Op::DrainAll,
+            }
+        }
+
+        fn apply<S: Stack<u32>>(&self, stack: &S) -> Output {
+            match self {
+                Op::Push(t) => {
+                    stack.push(*t);
+                    Output::Pushed
+                }
+                Op::PushIter(ts) => {
+                    stack.push_iter(ts.iter().copied());
+                    Output::Pushed
+                }
+                Op::Pop => Output::Popped(stack.pop()),
+                Op::PopN(n) => Output::PoppedN(stack.pop_n(*n)),
+                Op::IsEmpty => Output::IsEmpty(stack.is_empty(&pin())),
+                Op::DrainAll => Output::PoppedN(stack.drain_all()),
+            }
+        }
+
+        /// Same as [`Op::apply`], but on a vector whose last element is the top.
+        fn apply_oracle(&self, oracle: &mut Vec<u32>) -> Output {
+            match self {
+                Op::Push(t) => {
+                    oracle.push(*t);
+                    Output::Pushed
+                }
+                Op::PushIter(ts) => {
+                    oracle.extend(ts);
+                    Output::Pushed
+                }
+                Op::Pop => Output::Popped(oracle.pop()),
+                Op::PopN(n) => {
+                    let rest = oracle.len().saturating_sub(*n);
+                    Output::PoppedN(oracle.drain(rest..).rev().collect())
+                }
+                Op::IsEmpty => Output::IsEmpty(oracle.is_empty()),
+                Op::DrainAll => Output::PoppedN(oracle.drain(..).rev().collect()),
+            }
+        }
+    }
+
+    /// Runs random sequences of operations on a new `S` and on a `Vec` on a single thread, and
+    /// checks that every operation returns the same result.
+    ///
+    /// A mismatch reports the seed of the sequence and the operations up to the mismatch.
+    pub(crate) fn conformance<S: Stack<u32> + Default>() {
+        const CASES: u64 = 64;
+        const OPS: usize = 256;
+
+        for seed in 0..CASES {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let ops = (0..OPS).map(|_| Op::random(&mut rng)).collect::<Vec<_>>();
+
+            let stack = S::default();
+            let mut oracle = Vec::new();
+            for (i, op) in ops.iter().enumerate() {
+                assert_eq!(
+                    op.apply(&stack),
+                    op.apply_oracle(&mut oracle),
+                    "seed {}, operations {:?}",
+                    seed,
+                    &ops[..=i]
+                );
+            }
+            assert_eq!(
+                stack.drain_all(),
+                oracle.into_iter().rev().collect::<Vec<_>>()
+            );
+        }
+    }
+
+    /// Runs `ops` operations on each of `threads` threads and checks that no value is lost or
+    /// duplicated.
+    ///
+    /// Each thread pushes values tagged with its id and a sequence number, and pops after about
+    /// half of its pushes. The popped values and those left in the stack must be exactly the
+    /// pushed values.
+    pub(crate) fn stress<B: Backoff, S: Stack<u64, B> + Sync + ?Sized>(
+        stack: &S,
+        threads: usize,
+        ops: usize,
+    ) {
+        let mut values = scope(|scope| {
+            let handles = (0..threads as u64)
+                .map(|t| {
+                    scope.spawn(move |_| {
+                        let mut rng = thread_rng();
+                        let mut popped = Vec::new();
+                        for seq in 0..ops as u64 {
+                            stack.push(t << 32 | seq);
+                            if rng.gen::<bool>() {
+                                popped.extend(stack.pop());
+                            }
+                        }
+                        popped
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .unwrap();
+        values.extend(stack.drain_all());
+
+        values.sort_unstable();
+        let pushed = (0..threads as u64)
+            .flat_map(|t| (0..ops as u64).map(move |seq| t << 32 | seq))
+            .collect::<Vec<_>>();
+        assert_eq!(values, pushed);
+    }
+
+    /// Value whose words are written only before it is pushed.
+    pub(crate) type Message = Box<[u64; 8]>;
+
+    /// Half of `threads` push freshly allocated [`Message`]s which the other half pop and read,
+    /// checking that every word written before a push is visible to the thread popping it.
+    ///
+    /// Only the orderings of the stack publish the words, so a too-weak ordering shows up as a
+    /// torn message. Strongly ordered hardware such as x86 hides most such bugs; the test is
+    /// meant for weakly ordered machines.
+    pub(crate) fn message_passing<B: Backoff, S: Stack<Message, B> + Sync + ?Sized>(
+        stack: &S,
+        threads: usize,
+        ops: usize,
+    ) {
+        let producers = threads / 2;
+        let consumers = threads - producers;
+        let popped = scope(|scope| {
+            for t in 0..producers as u64 {
+                let _ = scope.spawn(move |_| {
+                    for seq in 0..ops as u64 {
+                        stack.push(Box::new([t << 32 | seq; 8]));
+                    }
+                });
+            }
+            let handles = (0..consumers)
+                .map(|_| {
+                    scope.spawn(|_| {
+                        let mut popped = 0;
+                        for _ in 0..ops * producers / consumers {
+                            popped += stack.pop().map_or(0, check);
+                        }
+                        popped
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .sum::<usize>()
+        })
+        .unwrap();
+        let rest = stack.drain_all().into_iter().map(check).sum::<usize>();
+        assert_eq!(popped + rest, producers * ops);
+
+        fn check(message: Message) -> usize {
+            assert!(
+                message.iter().all(|word| *word == message[0]),
+                "torn message {:?}",
+                message
+            );
+            1
+        }
+    }
 }
@@ -0,0 +1,166 @@
+use core::fmt;
+use core::mem;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crossbeam_epoch::pin;
+
+use super::base::Stack;
+use super::treiber_stack::TreiberStack;
+
+/// Treiber's stack that holds at most a fixed number of values.
+pub struct BoundedTreiberStack<T> {
+    inner: TreiberStack<T>,
+    capacity: usize,
+    /// Number of values in the stack plus the pushes in progress. Never exceeds `capacity`.
+    reserved: AtomicUsize,
+}
+
+/// A reserved slot, released on drop unless the push was completed.
+struct Reservation<'s>(&'s AtomicUsize);
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        let _ = self.0.fetch_sub(1, Ordering::Release);
+    }
+}
+
+impl<T> fmt::Debug for BoundedTreiberStack<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoundedTreiberStack")
+            .field("capacity", &self.capacity)
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> BoundedTreiberStack<T> {
+    /// Creates a new stack that holds at most `capacity` values.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: TreiberStack::default(),
+            capacity,
+            reserved: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the maximum number of values in the stack.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of values in the stack, including those being pushed.
+    pub fn len(&self) -> usize {
+        self.reserved.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if the stack is empty.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty(&pin())
+    }
+
+    /// Pushes a value to the stack.
+    ///
+    /// Returns `Err(t)` with the original value if the stack is full.
+    pub fn try_push(&self, t: T) -> Result<(), T> {
+        let mut reserved = self.reserved.load(Ordering::Relaxed);
+        loop {
+            if reserved >= self.capacity {
+                return Err(t);
+            }
+            match self.reserved.compare_exchange_weak(
+                reserved,
+                reserved + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(current) => reserved = current,
+            }
+        }
+
+        // Released if the push panics, e.g. on allocation failure.
+        let reservation = Reservation(&self.reserved);
+        self.inner.push(t);
+        mem::forget(reservation);
+        Ok(())
+    }
+
+    /// Pops a value from the stack, releasing its slot.
+    ///
+    /// Returns `Some(v)` if `v` is popped; `None` if the stack is empty.
+    pub fn pop(&self) -> Option<T> {
+        let result = self.inner.pop();
+        if result.is_some() {
+            drop(Reservation(&self.reserved));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::sync::atomic::AtomicIsize;
+    use crossbeam_utils::thread::scope;
+
+    #[test]
+    fn push_pop() {
+        let stack = BoundedTreiberStack::new(2);
+        assert_eq!(stack.capacity(), 2);
+        assert!(stack.try_push(1).is_ok());
+        assert!(stack.try_push(2).is_ok());
+        assert_eq!(stack.try_push(3), Err(3));
+        assert_eq!(stack.len(), 2);
+        assert_eq!(stack.pop(), Some(2));
+        assert!(stack.try_push(3).is_ok());
+        assert_eq!(stack.pop(), Some(3));
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), None);
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn capacity_0() {
+        let stack = BoundedTreiberStack::new(0);
+        assert_eq!(stack.try_push(1), Err(1));
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn capacity_1_concurrent() {
+        const THREADS: usize = 8;
+        const ELEMENTS: usize = 10_000;
+
+        let stack = BoundedTreiberStack::new(1);
+        let pushed = AtomicUsize::new(0);
+        // Elements pushed and not popped, counted after each operation succeeds. Each thread may
+        // be between an operation and its count, so the count is off by at most `THREADS` from
+        // the one element that the stack can hold.
+        let live = AtomicIsize::new(0);
+
+        scope(|scope| {
+            for _ in 0..THREADS {
+                let _ = scope.spawn(|_| {
+                    for i in 0..ELEMENTS {
+                        if stack.try_push(i).is_ok() {
+                            let _ = pushed.fetch_add(1, Ordering::Relaxed);
+                            let live = live.fetch_add(1, Ordering::Relaxed) + 1;
+                            assert!(live <= 1 + THREADS as isize);
+                        }
+                        if stack.pop().is_some() {
+                            let live = live.fetch_sub(1, Ordering::Relaxed) - 1;
+                            assert!(live >= -(THREADS as isize));
+                        }
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        assert!(pushed.load(Ordering::Relaxed) > 0);
+        let live = live.load(Ordering::Relaxed);
+        assert!(live == 0 || live == 1);
+        assert_eq!(stack.len(), live as usize);
+        assert_eq!(stack.pop().is_some(), live == 1);
+    }
+}
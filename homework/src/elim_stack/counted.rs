@@ -0,0 +1,167 @@
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicIsize, Ordering};
+use crossbeam_epoch::{pin, Guard, Owned};
+
+use super::backoff::Backoff;
+use super::base::Stack;
+use super::treiber_stack::Node;
+
+/// Stack that counts its values exactly.
+///
+/// The counter is adjusted after each successful operation on the inner stack `S`, so it lags
+/// behind the operations in progress. It is negative if a pop is counted before its push.
+#[derive(Debug)]
+pub struct CountedStack<T, S> {
+    inner: S,
+    len: AtomicIsize,
+    _marker: PhantomData<T>,
+}
+
+impl<T, S: Default> Default for CountedStack<T, S> {
+    fn default() -> Self {
+        Self {
+            inner: S::default(),
+            len: AtomicIsize::new(0),
+            _marker: PhantomData,
+        }
+    }
+}
+
+// The counter publishes no data, and its updates are read-modify-writes, which never miss each
+// other. So it is relaxed: the total is exact, and `len` is as stale as any concurrent counter.
+impl<T, S> CountedStack<T, S> {
+    /// Returns the number of values in the stack.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed).max(0) as usize
+    }
+
+    fn add(&self, n: usize) {
+        let _ = self.len.fetch_add(n as isize, Ordering::Relaxed);
+    }
+
+    fn sub(&self, n: usize) {
+        let _ = self.len.fetch_sub(n as isize, Ordering::Relaxed);
+    }
+}
+
+impl<T, S: Stack<T>> CountedStack<T, S> {
+    /// Returns `true` if the stack is empty.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty(&pin())
+    }
+}
+
+impl<T, B: Backoff, S: Stack<T, B>> Stack<T, B> for CountedStack<T, S> {
+    fn try_push(&self, req: Owned<Node<T>>, guard: &Guard) -> Result<(), Owned<Node<T>>> {
+        self.inner.try_push(req, guard).map(|_| self.add(1))
+    }
+
+    fn try_pop(&self, guard: &Guard) -> Result<Option<T>, ()> {
+        let result = self.inner.try_pop(guard)?;
+        if result.is_some() {
+            self.sub(1);
+        }
+        Ok(result)
+    }
+
+    fn is_empty(&self, guard: &Guard) -> bool {
+        self.inner.is_empty(guard)
+    }
+
+    fn push_iter<I: IntoIterator<Item = T>>(&self, iter: I) {
+        let mut n = 0;
+        self.inner.push_iter(iter.into_iter().inspect(|_| n += 1));
+        self.add(n);
+    }
+
+    fn pop_many_into(&self, buf: &mut Vec<T>, n: usize) -> usize {
+        let count = self.inner.pop_many_into(buf, n);
+        self.sub(count);
+        count
+    }
+
+    fn drain_all(&self) -> Vec<T> {
+        let result = self.inner.drain_all();
+        self.sub(result.len());
+        result
+    }
+
+    fn len_mut(&mut self) -> usize {
+        self.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::elim_stack::treiber_stack::TreiberStack;
+    use crate::elim_stack::ElimStack;
+    use core::sync::atomic::AtomicUsize;
+    use crossbeam_utils::thread::scope;
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn len() {
+        let stack = CountedStack::<_, TreiberStack<_>>::default();
+        assert_eq!(stack.len(), 0);
+        assert!(stack.is_empty());
+        stack.push(1);
+        stack.push_iter(2..5);
+        assert_eq!(stack.len(), 4);
+        assert!(!stack.is_empty());
+        assert_eq!(stack.pop(), Some(4));
+        assert_eq!(stack.pop_n(2), vec![3, 2]);
+        assert_eq!(stack.len(), 1);
+        assert_eq!(stack.drain_all(), vec![1]);
+        assert_eq!(stack.pop(), None);
+        assert_eq!(stack.len(), 0);
+        assert!(stack.is_empty());
+    }
+
+    /// After random interleavings of pushes and pops, the length is the number of pushes minus
+    /// the number of successful pops.
+    fn len_random<S: Stack<usize> + Default + Sync>() {
+        const THREADS: usize = 8;
+        const OPS: usize = 10_000;
+
+        for _ in 0..10 {
+            let stack = CountedStack::<_, S>::default();
+            let pushes = AtomicUsize::new(0);
+            let pops = AtomicUsize::new(0);
+
+            scope(|scope| {
+                for _ in 0..THREADS {
+                    let _ = scope.spawn(|_| {
+                        let mut rng = thread_rng();
+                        let ratio = rng.gen::<f64>();
+                        for i in 0..OPS {
+                            if rng.gen_bool(ratio) {
+                                stack.push(i);
+                                let _ = pushes.fetch_add(1, Ordering::Relaxed);
+                            } else if stack.pop().is_some() {
+                                let _ = pops.fetch_add(1, Ordering::Relaxed);
+                            }
+                        }
+                    });
+                }
+            })
+            .unwrap();
+
+            let len = pushes.load(Ordering::Relaxed) - pops.load(Ordering::Relaxed);
+            assert_eq!(stack.len(), len);
+            assert_eq!(stack.drain_all().len(), len);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn len_random_treiber() {
+        len_random::<TreiberStack<_>>();
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn len_random_elim() {
+        len_random::<ElimStack<_>>();
+    }
+}
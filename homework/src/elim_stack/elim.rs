@@ -1,45 +1,605 @@
+use core::iter::{self, FromIterator};
 use core::mem::ManuallyDrop;
-use core::ops::Deref;
 use core::ptr;
 use core::sync::atomic::Ordering;
-use crossbeam_epoch::{Guard, Owned, Shared};
-use std::thread;
+use crossbeam_channel::{SendError, Sender};
+use crossbeam_epoch::{pin, Guard, Owned};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::time::{Duration, Instant};
 
-use super::base::{get_random_elim_index, ElimStack, Stack, ELIM_DELAY};
+use super::backoff::Backoff;
+#[cfg(feature = "trace")]
+use super::base::CompletionPath;
+use super::base::{Contended, ElimStack, Stack};
+use super::treiber_stack::{Node, TreiberStack};
 
-impl<T, S: Stack<T>> Stack<T> for ElimStack<T, S> {
-    type PushReq = S::PushReq;
+#[cfg(feature = "trace")]
+impl<T, S: Stack<T>, B: Backoff, const ELIM_SIZE: usize> ElimStack<T, S, B, ELIM_SIZE> {
+    /// Same as [`Stack::push`], but also returns how the push completed.
+    pub fn push_traced(&self, t: T) -> CompletionPath {
+        let guard = pin();
+        let mut req = Owned::new(Node::from(t));
+        let mut backoff = B::default();
+        let mut retries = 0;
+        loop {
+            match self.try_push_counted(req, &guard) {
+                Ok(eliminated) => return CompletionPath::new(eliminated, retries),
+                Err(r) => req = r,
+            }
+            retries += 1;
+            backoff.snooze();
+        }
+    }
 
-    fn try_push(
-        &self,
-        req: Owned<Self::PushReq>,
-        guard: &Guard,
-    ) -> Result<(), Owned<Self::PushReq>> {
+    /// Same as [`Stack::pop`], but also returns how the pop completed.
+    ///
+    /// A pop that finds the stack empty completes on the inner stack.
+    pub fn pop_traced(&self) -> (Option<T>, CompletionPath) {
+        let guard = pin();
+        let mut backoff = B::default();
+        let mut retries = 0;
+        loop {
+            if let Ok((result, eliminated)) = self.try_pop_counted(&guard) {
+                return (result, CompletionPath::new(eliminated, retries));
+            }
+            retries += 1;
+            backoff.snooze();
+        }
+    }
+}
+
+#[cfg(feature = "trace")]
+impl CompletionPath {
+    fn new(eliminated: bool, retries: u32) -> Self {
+        if eliminated {
+            Self::Eliminated { retries }
+        } else {
+            Self::DirectCas
+        }
+    }
+}
+
+/// Consecutive timeouts of the push requests of a stack after which they skip elimination.
+const SKIP_AFTER_FAILURES: u32 = 4;
+/// Number of push attempts that skip elimination before it is tried again.
+const SKIP_ROUNDS: u32 = 8;
+
+impl<T, S: Stack<T>, B: Backoff, const ELIM_SIZE: usize> Stack<T, B>
+    for ElimStack<T, S, B, ELIM_SIZE>
+{
+    fn try_push(&self, req: Owned<Node<T>>, guard: &Guard) -> Result<(), Owned<Node<T>>> {
+        self.try_push_counted(req, guard).map(|_| ())
+    }
+
+    fn try_pop(&self, guard: &Guard) -> Result<Option<T>, ()> {
+        self.try_pop_counted(guard).map(|(result, _)| result)
+    }
+
+    fn is_empty(&self, guard: &Guard) -> bool {
+        self.inner.is_empty(guard)
+    }
+
+    fn push_iter<I: IntoIterator<Item = T>>(&self, iter: I) {
+        self.inner.push_iter(iter.into_iter().inspect(|_| {
+            let _ = self.len.fetch_add(1, Ordering::Relaxed);
+        }));
+        self.waiters.notify_all();
+    }
+
+    fn pop_many_into(&self, buf: &mut Vec<T>, n: usize) -> usize {
+        let count = self.inner.pop_many_into(buf, n);
+        let _ = self.len.fetch_sub(count, Ordering::Relaxed);
+        count
+    }
+
+    fn drain_all(&self) -> Vec<T> {
+        let result = self.inner.drain_all();
+        let _ = self.len.fetch_sub(result.len(), Ordering::Relaxed);
+        result
+    }
+
+    fn len_mut(&mut self) -> usize {
+        self.inner.len_mut()
+    }
+}
+
+impl<T, S: Stack<T>, B: Backoff, const ELIM_SIZE: usize> ElimStack<T, S, B, ELIM_SIZE> {
+    /// Same as [`Stack::try_push`], but returns `Ok(true)` if the request was taken by a pop in
+    /// the elimination array.
+    fn try_push_counted(&self, req: Owned<Node<T>>, guard: &Guard) -> Result<bool, Owned<Node<T>>> {
+        // Counted before the push is published so that a pop of this value can never decrement
+        // the counter below zero.
+        let _ = self.len.fetch_add(1, Ordering::Relaxed);
+        self.try_push_inner(req, guard)
+            .map(|eliminated| {
+                self.waiters.notify_one();
+                eliminated
+            })
+            .map_err(|req| {
+                let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+                req
+            })
+    }
+
+    /// Same as [`Stack::try_pop`], but also returns whether the value was taken from a push
+    /// request in the elimination array.
+    fn try_pop_counted(&self, guard: &Guard) -> Result<(Option<T>, bool), ()> {
+        if let Ok(result) = self.inner.try_pop(guard) {
+            let _ = self.counters.direct_cas.fetch_add(1, Ordering::Relaxed);
+            if result.is_some() {
+                let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+            }
+            return Ok((result, false));
+        }
+        if !self.elimination_enabled() {
+            return Err(());
+        }
+
+        // Offers nothing, so that only a push request is a useful partner.
+        let index = self.rng.gen_index(self.active_elim_range());
+        let slot = unsafe { self.slots.get_unchecked(index) };
+        let req = match slot.exchange_until(None, Instant::now(), guard) {
+            Ok(Some(req)) => req,
+            Ok(None) => {
+                // Met another pop.
+                self.grow_elim_range();
+                return Err(());
+            }
+            Err(_) => return Err(()),
+        };
+
+        let _ = self.counters.exchanges.fetch_add(1, Ordering::Relaxed);
+        self.elim_failures.store(0, Ordering::Relaxed);
+        #[cfg(test)]
+        self.eliminated.store(true, Ordering::Relaxed);
+        let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+        Ok((
+            Some(unsafe { ManuallyDrop::into_inner(ptr::read(&**req)) }),
+            true,
+        ))
+    }
+
+    /// Tries to push to the inner stack, then in the elimination array.
+    ///
+    /// Returns `Ok(true)` if the request was taken by a pop in the elimination array.
+    fn try_push_inner(&self, req: Owned<Node<T>>, guard: &Guard) -> Result<bool, Owned<Node<T>>> {
+        let req = if self.pop_reservations() {
+            match self.answer_reservation(req, guard) {
+                Ok(()) => return Ok(true),
+                Err(req) => req,
+            }
+        } else {
+            req
+        };
         let req = match self.inner.try_push(req, guard) {
-            Ok(()) => return Ok(()),
+            Ok(()) => {
+                let _ = self.counters.direct_cas.fetch_add(1, Ordering::Relaxed);
+                return Ok(false);
+            }
             Err(req) => req,
         };
+        if !self.elimination_enabled() {
+            return Err(req);
+        }
+
+        // Concurrent pushes may overwrite each other's updates, which only blurs the count.
+        let failures = self.elim_failures.load(Ordering::Relaxed);
+        if failures >= SKIP_AFTER_FAILURES {
+            // Gives the inner stack a few rounds on its own before trying elimination again.
+            let failures = failures + 1;
+            self.elim_failures.store(
+                if failures < SKIP_AFTER_FAILURES + SKIP_ROUNDS {
+                    failures
+                } else {
+                    SKIP_AFTER_FAILURES - 1
+                },
+                Ordering::Relaxed,
+            );
+            let _ = self
+                .counters
+                .skipped_exchanges
+                .fetch_add(1, Ordering::Relaxed);
+            return Err(req);
+        }
+
+        // Each consecutive timeout halves the wait.
+        let deadline = Instant::now() + self.elim_wait / (1 << failures);
+        let index = self.rng.gen_index(self.active_elim_range());
+        let slot = unsafe { self.slots.get_unchecked(index) };
+        // Only pairs with a pop, so that a failed push always gets its own request back: answers a
+        // pop already waiting, or else waits for one, never answering nor being answered by
+        // another push.
+        let result = match slot.answer_if(Some(req), Option::is_none, guard) {
+            Ok(theirs) => Ok(theirs),
+            Err(req) => slot.offer_until(req, deadline, guard),
+        };
+        match result {
+            // Met a pop, which has taken the request.
+            Ok(_) => {
+                self.push_eliminated();
+                Ok(true)
+            }
+            // The slot was taken by another push, as a timeout only returns at the deadline.
+            Err(req) if Instant::now() < deadline => {
+                self.grow_elim_range();
+                Err(req.unwrap())
+            }
+            Err(req) => {
+                self.elim_failures.store(failures + 1, Ordering::Relaxed);
+                let _ = self
+                    .counters
+                    .failed_exchanges
+                    .fetch_add(1, Ordering::Relaxed);
+                self.shrink_elim_range();
+                Err(req.unwrap())
+            }
+        }
+    }
 
-        let index = get_random_elim_index();
-        let slot_ref = unsafe { self.slots.get_unchecked(index) };
-        let slot = slot_ref.load(Ordering::Acquire, guard);
+    /// Records that a push request was taken by a pop in the elimination array, which counts the
+    /// exchange.
+    fn push_eliminated(&self) {
+        self.elim_failures.store(0, Ordering::Relaxed);
+        #[cfg(test)]
+        self.eliminated.store(true, Ordering::Relaxed);
+    }
 
-        unimplemented!()
+    /// Hands the request to a pop waiting in any slot, as the range may have shrunk since the
+    /// pop picked its slot.
+    fn answer_reservation(
+        &self,
+        mut req: Owned<Node<T>>,
+        guard: &Guard,
+    ) -> Result<(), Owned<Node<T>>> {
+        for slot in &self.slots {
+            match slot.answer_if(Some(req), Option::is_none, guard) {
+                Ok(_) => {
+                    self.push_eliminated();
+                    return Ok(());
+                }
+                Err(r) => req = r.unwrap(),
+            }
+        }
+        Err(req)
     }
 
-    fn try_pop(&self, guard: &Guard) -> Result<Option<T>, ()> {
-        if let Ok(result) = self.inner.try_pop(guard) {
+    /// Waits in a slot until `deadline` for a push to hand over its request.
+    ///
+    /// Takes a push request already waiting in the slot, or else offers a reservation, which is
+    /// withdrawn if no push arrives in time. Returns `None` if the slot was taken by another
+    /// reservation, or the reservation was withdrawn or met a pop.
+    fn wait_reservation(&self, deadline: Instant, guard: &Guard) -> Option<T> {
+        let index = self.rng.gen_index(self.active_elim_range());
+        let slot = unsafe { self.slots.get_unchecked(index) };
+        let req = match slot.answer_if(None, Option::is_some, guard) {
+            Ok(req) => req,
+            Err(_) => slot.offer_until(None, deadline, guard).ok()?,
+        }?;
+
+        let _ = self.counters.exchanges.fetch_add(1, Ordering::Relaxed);
+        #[cfg(test)]
+        self.eliminated.store(true, Ordering::Relaxed);
+        let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+        Some(unsafe { ManuallyDrop::into_inner(ptr::read(&**req)) })
+    }
+
+    /// Returns the number of elimination slots currently in use, between 1 and `ELIM_SIZE`.
+    ///
+    /// The range starts with a single slot. It grows when operations collide in a slot and shrinks
+    /// when push requests time out unmatched, so that it follows the number of concurrent
+    /// operations.
+    pub fn active_elim_range(&self) -> usize {
+        self.active_elim_range.load(Ordering::Relaxed)
+    }
+
+    fn grow_elim_range(&self) {
+        let _ = self
+            .active_elim_range
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |r| {
+                (r < ELIM_SIZE).then(|| r + 1)
+            });
+    }
+
+    fn shrink_elim_range(&self) {
+        let _ = self
+            .active_elim_range
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |r| {
+                (r > 1).then(|| r - 1)
+            });
+    }
+
+    /// Returns the approximate number of values in the stack.
+    ///
+    /// The counter is updated with relaxed ordering, so it may be slightly stale under races. It
+    /// may also count pushes that are still in progress.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Pops a value from the stack, retrying for up to `timeout` if the stack is empty.
+    ///
+    /// Unlike [`ElimStack::pop_wait_deadline`], the thread keeps trying, waiting with the backoff
+    /// strategy `B` between attempts. Returns `None` if no value was popped before the timeout.
+    ///
+    /// With [pop reservations](ElimStack::set_pop_reservations), the thread waits between
+    /// attempts in the elimination array instead, for up to the elimination wait at a time, so
+    /// that a push can hand its value over directly.
+    pub fn pop_timeout(&self, timeout: Duration) -> Option<T> {
+        let deadline = Instant::now() + timeout;
+        let guard = pin();
+        let mut backoff = B::default();
+        loop {
+            if let Ok(Some(t)) = self.try_pop(&guard) {
+                return Some(t);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            if self.pop_reservations() {
+                let reserved = self.wait_reservation(deadline.min(now + self.elim_wait), &guard);
+                if reserved.is_some() {
+                    return reserved;
+                }
+            }
+            backoff.snooze();
+        }
+    }
+
+    /// Pushes a value with a single attempt on the inner stack and at most one probe of the
+    /// elimination array, without any retry loop.
+    ///
+    /// The probe only hands the value to a pop waiting in a reservation (see
+    /// [`ElimStack::set_pop_reservations`]). Returns `Err(t)` with the original value if both
+    /// failed, i.e. the stack was [`Contended`]. The number of steps is bounded if the
+    /// `try_push` of the inner stack is.
+    pub fn try_push_once(&self, t: T) -> Result<(), T> {
+        let guard = pin();
+        // Counted before the push is published, as in `try_push`.
+        let _ = self.len.fetch_add(1, Ordering::Relaxed);
+        let mut req = match self.inner.try_push(Owned::new(Node::from(t)), &guard) {
+            Ok(()) => {
+                let _ = self.counters.direct_cas.fetch_add(1, Ordering::Relaxed);
+                self.waiters.notify_one();
+                return Ok(());
+            }
+            Err(req) => req,
+        };
+        if self.elimination_enabled() {
+            let slot = unsafe {
+                self.slots
+                    .get_unchecked(self.rng.gen_index(self.active_elim_range()))
+            };
+            match slot.answer_if(Some(req), Option::is_none, &guard) {
+                Ok(_) => {
+                    self.push_eliminated();
+                    self.waiters.notify_one();
+                    return Ok(());
+                }
+                Err(r) => req = r.unwrap(),
+            }
+        }
+        let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+        Err(unsafe { ManuallyDrop::into_inner(ptr::read(&**req)) })
+    }
+
+    /// Pops a value with a single attempt on the inner stack and at most one probe of the
+    /// elimination array, without any retry loop.
+    ///
+    /// The probe only takes a push request already waiting in the slot. Returns `Ok(Some(v))` if
+    /// `v` is popped, `Ok(None)` if the inner stack is empty, and `Err(Contended)` if both
+    /// failed. The number of steps is bounded if the `try_pop` of the inner stack is.
+    pub fn try_pop_once(&self) -> Result<Option<T>, Contended> {
+        let guard = pin();
+        if let Ok(result) = self.inner.try_pop(&guard) {
+            let _ = self.counters.direct_cas.fetch_add(1, Ordering::Relaxed);
+            if result.is_some() {
+                let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+            }
             return Ok(result);
         }
+        if !self.elimination_enabled() {
+            return Err(Contended);
+        }
+        let slot = unsafe {
+            self.slots
+                .get_unchecked(self.rng.gen_index(self.active_elim_range()))
+        };
+        let req = slot
+            .answer_if(None, Option::is_some, &guard)
+            .map_err(|_| Contended)?
+            .unwrap();
+        let _ = self.counters.exchanges.fetch_add(1, Ordering::Relaxed);
+        #[cfg(test)]
+        self.eliminated.store(true, Ordering::Relaxed);
+        let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+        Ok(Some(unsafe { ManuallyDrop::into_inner(ptr::read(&**req)) }))
+    }
+
+    /// Pops a value from the stack, blocking until one is pushed if the stack is empty.
+    pub fn pop_wait(&self) -> T {
+        self.waiters.wait(|| self.pop(), None).unwrap()
+    }
 
-        let index = get_random_elim_index();
-        let slot_ref = unsafe { self.slots.get_unchecked(index) };
-        let slot = slot_ref.load(Ordering::Acquire, guard);
+    /// Pops a value from the stack, blocking until one is pushed or `deadline` passes.
+    ///
+    /// Returns `None` if the deadline passed.
+    pub fn pop_wait_deadline(&self, deadline: Instant) -> Option<T> {
+        self.waiters.wait(|| self.pop(), Some(deadline))
+    }
+}
 
-        unimplemented!()
+impl<T, B: Backoff, const ELIM_SIZE: usize> ElimStack<T, TreiberStack<T>, B, ELIM_SIZE> {
+    /// Moves the values into a vector, so that the top value is the first one.
+    ///
+    /// See [`TreiberStack::into_vec`].
+    pub fn into_vec(self) -> Vec<T> {
+        self.into_inner().into_vec()
     }
 
-    fn is_empty(&self, guard: &Guard) -> bool {
-        self.inner.is_empty(guard)
+    /// Moves about half of the values, from the top, to `into`. See [`TreiberStack::steal_half`].
+    pub fn steal_half(&self, into: &Self) -> usize {
+        let values = self.inner.pop_half();
+        let count = values.len();
+        let _ = self.len.fetch_sub(count, Ordering::Relaxed);
+        // Counted before the values are published, as in `try_push`.
+        let _ = into.len.fetch_add(count, Ordering::Relaxed);
+        into.inner.push_chain(values, &pin());
+        into.waiters.notify_all();
+        count
+    }
+
+    /// Moves every value of `other` on top of this stack, keeping the order of both. See
+    /// [`TreiberStack::append`].
+    pub fn append(&self, other: &Self) -> usize {
+        let values = other.drain_all();
+        let count = values.len();
+        // Counted before the values are published, as in `try_push`.
+        let _ = self.len.fetch_add(count, Ordering::Relaxed);
+        self.inner.push_chain(values, &pin());
+        self.waiters.notify_all();
+        count
+    }
+
+    /// Sends every value to `tx`, from the top, and returns the number of values sent.
+    ///
+    /// The values are detached with a single swap of the head, and each send blocks while a
+    /// bounded channel is full. If the channel is disconnected, the values not sent yet are pushed
+    /// back onto the stack in the same order, rather than dropped.
+    pub fn drain_to(&self, tx: &Sender<T>) -> usize {
+        let mut values = self.drain_all().into_iter();
+        let mut sent = 0;
+        while let Some(t) = values.next() {
+            if let Err(SendError(t)) = tx.send(t) {
+                let rest = iter::once(t).chain(values).collect::<Vec<_>>();
+                let _ = self.len.fetch_add(rest.len(), Ordering::Relaxed);
+                self.inner.push_chain(rest, &pin());
+                self.waiters.notify_all();
+                break;
+            }
+            sent += 1;
+        }
+        sent
+    }
+
+    /// Removes and drops every value. See [`TreiberStack::clear`].
+    pub fn clear(&mut self) {
+        self.inner.clear();
+        *self.len.get_mut() = 0;
+    }
+
+    /// Removes and drops every value, which is equivalent to `drain_all` and discard.
+    ///
+    /// See [`TreiberStack::clear_concurrent`].
+    pub fn clear_concurrent(&self) {
+        let count = self.inner.clear_concurrent();
+        let _ = self.len.fetch_sub(count, Ordering::Relaxed);
+    }
+}
+
+impl<T: Copy, B: Backoff, const ELIM_SIZE: usize> ElimStack<T, TreiberStack<T>, B, ELIM_SIZE> {
+    /// Returns the top value of the stack without popping it.
+    ///
+    /// Elimination is skipped since nothing is modified. See [`TreiberStack::peek`].
+    pub fn peek<'g>(&self, guard: &'g Guard) -> Option<&'g T> {
+        self.inner.peek(guard)
+    }
+
+    /// Pops the top value of the stack if it satisfies `f`.
+    ///
+    /// Elimination is skipped since a pushed value must be checked against `f` before it can
+    /// be taken. See [`TreiberStack::pop_if`].
+    pub fn pop_if<F: Fn(&T) -> bool>(&self, f: F) -> Option<T> {
+        let result = self.inner.pop_if(f);
+        if result.is_some() {
+            let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+impl<T: Clone, B: Backoff, const ELIM_SIZE: usize> Clone
+    for ElimStack<T, TreiberStack<T>, B, ELIM_SIZE>
+{
+    /// Clones the values into a new stack with the same elimination settings. See
+    /// [`TreiberStack::clone`].
+    ///
+    /// The statistics of the new stack start at zero.
+    fn clone(&self) -> Self {
+        let mut inner = self.inner.clone();
+        let len = inner.len_mut();
+        let mut stack = Self::from_inner(inner, len);
+        stack.elim_wait = self.elim_wait;
+        stack.set_elimination_enabled(self.elimination_enabled());
+        stack
+    }
+}
+
+impl<T, S, B: Backoff, const ELIM_SIZE: usize> IntoIterator for ElimStack<T, S, B, ELIM_SIZE>
+where
+    S: Stack<T> + IntoIterator<Item = T>,
+{
+    type Item = T;
+    type IntoIter = S::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner.into_iter()
+    }
+}
+
+impl<T, S, B: Backoff, const ELIM_SIZE: usize> FromIterator<T> for ElimStack<T, S, B, ELIM_SIZE>
+where
+    S: Stack<T> + FromIterator<T>,
+{
+    /// Creates a stack from the values of `iter`, so that the last value is on top.
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut len = 0;
+        let inner = iter.into_iter().inspect(|_| len += 1).collect();
+        Self::from_inner(inner, len)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Serialize, B: Backoff, const ELIM_SIZE: usize> Serialize
+    for ElimStack<T, TreiberStack<T>, B, ELIM_SIZE>
+{
+    /// Serializes the values as a sequence from the top of the stack, read like
+    /// [`TreiberStack::iter`].
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.inner.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T, S, B: Backoff, const ELIM_SIZE: usize> Deserialize<'de>
+    for ElimStack<T, S, B, ELIM_SIZE>
+where
+    T: Deserialize<'de>,
+    S: Stack<T> + FromIterator<T>,
+{
+    /// Deserializes a sequence from the top of the stack, as written by `serialize`.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::deserialize(deserializer).map(|values| values.into_iter().rev().collect())
+    }
+}
+
+impl<T, S, B: Backoff, const ELIM_SIZE: usize> From<Vec<T>> for ElimStack<T, S, B, ELIM_SIZE>
+where
+    S: Stack<T> + From<Vec<T>>,
+{
+    /// Creates a stack from the values of `vec`, so that the last value is on top.
+    fn from(vec: Vec<T>) -> Self {
+        let len = vec.len();
+        Self::from_inner(S::from(vec), len)
+    }
+}
+
+impl<T, S: Stack<T>, B: Backoff, const ELIM_SIZE: usize> Extend<T>
+    for &ElimStack<T, S, B, ELIM_SIZE>
+{
+    /// Pushes the values of `iter` in order. Safe to call concurrently with other operations.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.push_iter(iter);
     }
 }
@@ -0,0 +1,335 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::{ManuallyDrop, MaybeUninit};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+use crossbeam_epoch::{pin, Atomic, Guard, Owned, Shared};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A value offered by a thread waiting in an [`Exchanger`].
+struct Offer<T> {
+    /// The value of the waiting thread, taken by its partner.
+    mine: ManuallyDrop<T>,
+    /// The value of the partner, written before `matched` is set.
+    theirs: UnsafeCell<MaybeUninit<T>>,
+    matched: AtomicBool,
+}
+
+impl<T> Offer<T> {
+    fn new(t: T) -> Self {
+        Self {
+            mine: ManuallyDrop::new(t),
+            theirs: UnsafeCell::new(MaybeUninit::uninit()),
+            matched: AtomicBool::new(false),
+        }
+    }
+}
+
+/// A slot where two threads meet and swap values.
+pub struct Exchanger<T> {
+    slot: Atomic<Offer<T>>,
+}
+
+unsafe impl<T: Send> Send for Exchanger<T> {}
+unsafe impl<T: Send> Sync for Exchanger<T> {}
+
+impl<T> Default for Exchanger<T> {
+    fn default() -> Self {
+        Self {
+            slot: Atomic::null(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Exchanger<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Exchanger").finish_non_exhaustive()
+    }
+}
+
+impl<T> Exchanger<T> {
+    /// Offers `t`, waiting for up to `timeout` for another thread to exchange with.
+    ///
+    /// Returns `Ok(v)` with the value `v` of the partner, or `Err(t)` with the original value if
+    /// no partner arrived in time.
+    pub fn exchange(&self, t: T, timeout: Duration) -> Result<T, T> {
+        self.exchange_until(t, Instant::now() + timeout, &pin())
+    }
+
+    /// Same as [`Exchanger::exchange`], but under an existing guard and with a deadline.
+    pub(crate) fn exchange_until(&self, t: T, deadline: Instant, guard: &Guard) -> Result<T, T> {
+        let mut offer = Owned::new(Offer::new(t));
+
+        loop {
+            // Acquires the value of a waiting offer from the CAS that published it.
+            let slot = self.slot.load(Ordering::Acquire, guard);
+            if let Some(slot_ref) = unsafe { slot.as_ref() } {
+                // Another thread is waiting. Takes its offer out of the slot and answers it. The
+                // take is relaxed: the load above already synchronized with the publication, and
+                // the answer is released by `matched` instead.
+                if self
+                    .slot
+                    .compare_exchange(
+                        slot,
+                        Shared::null(),
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                        guard,
+                    )
+                    .is_ok()
+                {
+                    return Ok(unsafe {
+                        Self::answer(slot_ref, ManuallyDrop::take(&mut offer.mine))
+                    });
+                }
+            } else {
+                // Publishes our offer, releasing its value to the thread that takes it.
+                match self.slot.compare_exchange(
+                    Shared::null(),
+                    offer,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                ) {
+                    Ok(offer) => return self.wait(offer, deadline, guard),
+                    Err(e) => offer = e.new,
+                }
+            }
+
+            if Instant::now() >= deadline {
+                return Err(unsafe { ManuallyDrop::take(&mut offer.mine) });
+            }
+        }
+    }
+
+    /// Returns `true` if no offer is waiting in the slot.
+    #[cfg(test)]
+    pub(crate) fn is_empty(&self, guard: &Guard) -> bool {
+        self.slot.load(Ordering::Acquire, guard).is_null()
+    }
+
+    /// Offers `t` if the slot is empty, and waits until `deadline` for a partner to answer it.
+    ///
+    /// Unlike [`Exchanger::exchange_until`], never answers an offer already waiting: returns
+    /// `Err(t)` right away if the slot is taken.
+    pub(crate) fn offer_until(&self, t: T, deadline: Instant, guard: &Guard) -> Result<T, T> {
+        // Publishes the offer like `exchange_until`.
+        match self.slot.compare_exchange(
+            Shared::null(),
+            Owned::new(Offer::new(t)),
+            Ordering::Release,
+            Ordering::Relaxed,
+            guard,
+        ) {
+            Ok(offer) => self.wait(offer, deadline, guard),
+            Err(e) => {
+                let mut offer = e.new;
+                Err(unsafe { ManuallyDrop::take(&mut offer.mine) })
+            }
+        }
+    }
+
+    /// Answers the offer waiting in the slot with `t`, if `accept` returns `true` for its value.
+    ///
+    /// Returns `Ok(v)` with the value `v` of the waiting thread, or `Err(t)` if no offer was
+    /// waiting, it was not accepted, or it was withdrawn or answered first. Never waits.
+    pub(crate) fn answer_if<F: FnOnce(&T) -> bool>(
+        &self,
+        t: T,
+        accept: F,
+        guard: &Guard,
+    ) -> Result<T, T> {
+        // Acquires the value of the offer, which `accept` reads before the take.
+        let slot = self.slot.load(Ordering::Acquire, guard);
+        let slot_ref = some_or!(unsafe { slot.as_ref() }, return Err(t));
+        // The value is read only while the offer is waiting, and its owner only reads it too.
+        if !accept(&slot_ref.mine) {
+            return Err(t);
+        }
+        // Takes the offer, relaxed like in `exchange_until`.
+        match self.slot.compare_exchange(
+            slot,
+            Shared::null(),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+            guard,
+        ) {
+            Ok(_) => Ok(unsafe { Self::answer(slot_ref, t) }),
+            Err(_) => Err(t),
+        }
+    }
+
+    /// Answers an offer taken out of the slot with `t`, and returns its value.
+    ///
+    /// # Safety
+    ///
+    /// The caller must have taken the offer out of the slot.
+    unsafe fn answer(offer: &Offer<T>, t: T) -> T {
+        let theirs = ptr::read(&offer.mine);
+        let _ = (*offer.theirs.get()).write(t);
+        // Releases our value, and the end of our read of theirs, to the waiting thread.
+        offer.matched.store(true, Ordering::Release);
+        ManuallyDrop::into_inner(theirs)
+    }
+
+    /// Waits for a partner to answer `offer`, withdrawing it at `deadline`.
+    fn wait(&self, offer: Shared<'_, Offer<T>>, deadline: Instant, guard: &Guard) -> Result<T, T> {
+        let offer_ref = unsafe { offer.deref() };
+
+        // Acquires the value of the partner from `answer`.
+        while !offer_ref.matched.load(Ordering::Acquire) {
+            if Instant::now() < deadline {
+                thread::yield_now();
+                continue;
+            }
+
+            // Withdraws the offer. If it fails, a partner has taken it and is answering. Relaxed,
+            // since on success no partner answers the offer, so nothing is acquired.
+            if self
+                .slot
+                .compare_exchange(
+                    offer,
+                    Shared::null(),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                    guard,
+                )
+                .is_ok()
+            {
+                return Err(unsafe {
+                    let mine = ptr::read(&offer_ref.mine);
+                    guard.defer_destroy(offer);
+                    ManuallyDrop::into_inner(mine)
+                });
+            }
+            while !offer_ref.matched.load(Ordering::Acquire) {
+                core::hint::spin_loop();
+            }
+        }
+
+        Ok(unsafe {
+            let theirs = ptr::read(offer_ref.theirs.get()).assume_init();
+            guard.defer_destroy(offer);
+            theirs
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crossbeam_utils::thread::scope;
+
+    #[test]
+    fn exchange_pair() {
+        let exchanger = Exchanger::default();
+
+        scope(|scope| {
+            let other = scope.spawn(|_| exchanger.exchange(1, Duration::from_secs(10)));
+            assert_eq!(exchanger.exchange(2, Duration::from_secs(10)), Ok(1));
+            assert_eq!(other.join().unwrap(), Ok(2));
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn exchange_timeout() {
+        let exchanger = Exchanger::default();
+
+        let start = Instant::now();
+        assert_eq!(exchanger.exchange(1, Duration::from_millis(100)), Err(1));
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(exchanger.exchange(2, Duration::from_secs(0)), Err(2));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn exchange_many() {
+        const THREADS: usize = 8;
+        const EXCHANGES: usize = 1_000;
+
+        let exchanger = Exchanger::default();
+
+        // Every value ends up either with another thread or back with its owner.
+        let mut values = scope(|scope| {
+            let handles = (0..THREADS)
+                .map(|t| {
+                    let exchanger = &exchanger;
+                    scope.spawn(move |_| {
+                        (0..EXCHANGES)
+                            .map(|i| {
+                                let value = t * EXCHANGES + i;
+                                match exchanger.exchange(value, Duration::from_micros(100)) {
+                                    Ok(theirs) => {
+                                        assert_ne!(theirs / EXCHANGES, t);
+                                        theirs
+                                    }
+                                    Err(mine) => {
+                                        assert_eq!(mine, value);
+                                        mine
+                                    }
+                                }
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .unwrap();
+
+        values.sort_unstable();
+        assert_eq!(values, (0..THREADS * EXCHANGES).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn exchange_message_passing() {
+        const THREADS: usize = 4;
+        const EXCHANGES: usize = 1_000;
+
+        let exchanger = Exchanger::<Box<[usize; 8]>>::default();
+
+        // The words of a value are written only before it is offered, so a too-weak ordering on
+        // either side of an exchange shows up as a torn value.
+        scope(|scope| {
+            for t in 0..THREADS {
+                let exchanger = &exchanger;
+                let _ = scope.spawn(move |_| {
+                    for i in 0..EXCHANGES {
+                        let value = Box::new([t * EXCHANGES + i; 8]);
+                        let value = exchanger
+                            .exchange(value, Duration::from_micros(100))
+                            .unwrap_or_else(|mine| mine);
+                        assert!(value.iter().all(|word| *word == value[0]), "{:?}", value);
+                    }
+                });
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn exchange_drop() {
+        use crate::elim_stack::treiber_stack::test::DropCounter;
+        use core::sync::atomic::AtomicUsize;
+
+        let drops = AtomicUsize::new(0);
+        let exchanger = Exchanger::default();
+
+        scope(|scope| {
+            let other =
+                scope.spawn(|_| exchanger.exchange(DropCounter(&drops), Duration::from_secs(10)));
+            drop(exchanger.exchange(DropCounter(&drops), Duration::from_secs(10)));
+            drop(other.join().unwrap());
+        })
+        .unwrap();
+        drop(exchanger.exchange(DropCounter(&drops), Duration::from_secs(0)));
+
+        assert_eq!(drops.load(Ordering::Relaxed), 3);
+    }
+}
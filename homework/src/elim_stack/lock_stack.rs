@@ -0,0 +1,87 @@
+use core::mem::ManuallyDrop;
+use core::ptr;
+use std::sync::{Mutex, MutexGuard, TryLockError};
+
+use crossbeam_epoch::{Guard, Owned};
+
+use super::base::Stack;
+use super::treiber_stack::Node;
+
+/// Stack protected by a lock, as a baseline for the lock-free stacks.
+///
+/// A push or pop fails if the lock is held by another thread.
+#[derive(Debug)]
+pub struct LockStack<T>(Mutex<Vec<T>>);
+
+impl<T> Default for LockStack<T> {
+    fn default() -> Self {
+        Self(Mutex::new(Vec::new()))
+    }
+}
+
+impl<T> LockStack<T> {
+    fn try_lock(&self) -> Option<MutexGuard<'_, Vec<T>>> {
+        match self.0.try_lock() {
+            Ok(values) => Some(values),
+            // A panic never leaves the vector in an inconsistent state.
+            Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+}
+
+impl<T> Stack<T> for LockStack<T> {
+    fn try_push(&self, req: Owned<Node<T>>, _guard: &Guard) -> Result<(), Owned<Node<T>>> {
+        let mut values = some_or!(self.try_lock(), return Err(req));
+        values.push(unsafe { ManuallyDrop::into_inner(ptr::read(&**req)) });
+        Ok(())
+    }
+
+    fn try_pop(&self, _guard: &Guard) -> Result<Option<T>, ()> {
+        self.try_lock().map(|mut values| values.pop()).ok_or(())
+    }
+
+    fn is_empty(&self, _guard: &Guard) -> bool {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).is_empty()
+    }
+
+    fn len_mut(&mut self) -> usize {
+        self.0.get_mut().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crossbeam_epoch::pin;
+
+    #[test]
+    fn push_pop() {
+        let stack = LockStack::default();
+        assert!(stack.is_empty(&pin()));
+        stack.push(1);
+        stack.push(2);
+        assert!(!stack.is_empty(&pin()));
+        assert_eq!(stack.pop(), Some(2));
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn try_locked() {
+        let stack = LockStack::default();
+        stack.push(1);
+
+        let guard = pin();
+        let values = stack.0.lock().unwrap();
+        let req = stack
+            .try_push(Owned::new(Node::from(2)), &guard)
+            .unwrap_err();
+        assert_eq!(***req, 2);
+        assert_eq!(stack.try_pop(&guard), Err(()));
+        drop(values);
+
+        assert!(stack.try_push(req, &guard).is_ok());
+        assert_eq!(stack.try_pop(&guard), Ok(Some(2)));
+    }
+}
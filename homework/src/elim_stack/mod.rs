@@ -1,20 +1,91 @@
 //! Elimination-backoff stack.
 
+mod backoff;
 mod base;
+mod bounded;
+mod counted;
 mod elim;
+mod exchanger;
+mod lock_stack;
+mod spin_lock_stack;
+mod striped;
 mod treiber_stack;
 
-pub use base::Stack;
+pub use backoff::{Backoff, ExponentialBackoff, SpinBackoff, YieldBackoff};
+#[cfg(feature = "trace")]
+pub use base::CompletionPath;
+pub use base::{Contended, ElimStats, Stack};
+pub use bounded::BoundedTreiberStack;
+pub use counted::CountedStack;
+pub use exchanger::Exchanger;
+pub use lock_stack::LockStack;
+pub use spin_lock_stack::SpinLockStack;
+pub use striped::StripedStack;
+#[cfg(feature = "hazard-stack")]
+pub use treiber_stack::hp::TreiberStack as HpTreiberStack;
+pub use treiber_stack::tagged::TreiberStack as TaggedTreiberStack;
+pub use treiber_stack::TreiberStack;
 
 /// Elimination-backoff stack based on Treiber's stack.
-pub type ElimStack<T> = base::ElimStack<T, treiber_stack::TreiberStack<T>>;
+pub type ElimStack<T> = ElimStackWithBackoff<T, SpinBackoff>;
+
+/// Elimination-backoff stack based on Treiber's stack, whose retry loops wait with `B`.
+pub type ElimStackWithBackoff<T, B> =
+    base::ElimStack<T, treiber_stack::TreiberStack<T>, B, { base::DEFAULT_ELIM_SIZE }>;
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::elim_stack::treiber_stack::test::DropCounter;
+    use crate::elim_stack::treiber_stack::{Node, TreiberStack};
+    use base::DEFAULT_ELIM_SIZE;
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use crossbeam_epoch::{pin, Guard, Owned};
     use crossbeam_utils::thread::scope;
+    use std::sync::Barrier;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    /// Inner stack whose operations always fail, so that everything goes through elimination.
+    #[derive(Debug, Default)]
+    pub(super) struct AlwaysContended;
+
+    impl Stack<i32> for AlwaysContended {
+        fn try_push(&self, req: Owned<Node<i32>>, _guard: &Guard) -> Result<(), Owned<Node<i32>>> {
+            Err(req)
+        }
+
+        fn try_pop(&self, _guard: &Guard) -> Result<Option<i32>, ()> {
+            Err(())
+        }
+
+        fn is_empty(&self, _guard: &Guard) -> bool {
+            true
+        }
+    }
+
+    /// Checks a stack type against a sequential oracle with [`base::test::conformance`].
+    macro_rules! conformance {
+        ($name:ident, $stack:ty) => {
+            #[test]
+            fn $name() {
+                base::test::conformance::<$stack>();
+            }
+        };
+    }
+
+    conformance!(conformance_treiber, TreiberStack<u32>);
+    conformance!(conformance_elim, ElimStack<u32>);
+    conformance!(conformance_lock, LockStack<u32>);
+    conformance!(conformance_spin_lock, SpinLockStack<u32>);
+    conformance!(conformance_counted, CountedStack<u32, TreiberStack<u32>>);
+    conformance!(conformance_striped, StripedStack<u32, TreiberStack<u32>>);
+    conformance!(conformance_tagged, TaggedTreiberStack<u32>);
+    #[cfg(feature = "hazard-stack")]
+    conformance!(conformance_hp, HpTreiberStack<u32>);
 
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn push() {
         let stack = ElimStack::default();
 
@@ -32,4 +103,1454 @@ mod test {
 
         assert!(stack.pop().is_none());
     }
+
+    fn push_pop_contended<B: Backoff, S: Stack<u32, B> + Sync>(stack: &S) {
+        scope(|scope| {
+            for _ in 0..10 {
+                scope.spawn(|_| {
+                    for i in 0..1_000 {
+                        stack.push(i);
+                        assert!(stack.pop().is_some());
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        assert!(stack.pop().is_none());
+    }
+
+    #[test]
+    fn elim_size() {
+        fn push_pop<const ELIM_SIZE: usize>() {
+            let stack = base::ElimStack::<_, TreiberStack<_>, SpinBackoff, ELIM_SIZE>::default();
+            assert_eq!(stack.slots.len(), ELIM_SIZE);
+            stack.push(1);
+            assert_eq!(stack.pop(), Some(1));
+            assert!(stack.pop().is_none());
+        }
+
+        push_pop::<1>();
+        push_pop::<2>();
+        push_pop::<7>();
+        push_pop::<32>();
+        push_pop::<64>();
+    }
+
+    #[test]
+    fn elim_size_1() {
+        push_pop_contended(&base::ElimStack::<_, TreiberStack<_>, SpinBackoff, 1>::default());
+    }
+
+    #[test]
+    fn elim_size_64() {
+        push_pop_contended(&base::ElimStack::<_, TreiberStack<_>, SpinBackoff, 64>::default());
+    }
+
+    #[test]
+    fn dyn_stack() {
+        let stacks: Vec<Box<dyn Stack<u32> + Send + Sync>> = vec![
+            Box::new(LockStack::default()),
+            Box::new(SpinLockStack::default()),
+            Box::new(TreiberStack::default()),
+            Box::new(ElimStack::default()),
+            Box::new(CountedStack::<_, TreiberStack<_>>::default()),
+        ];
+
+        for stack in &stacks {
+            // Through the blanket implementation for `Box`.
+            push_pop_contended(stack);
+            assert!(stack.is_empty(&pin()));
+
+            stack.push(1);
+            assert_eq!(stack.try_push_bounded(2, 10), Ok(()));
+            assert_eq!(stack.pop_n(1), vec![2]);
+            assert_eq!(stack.drain_all(), vec![1]);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn stress() {
+        use base::test::stress;
+
+        stress(&TreiberStack::default(), 8, 10_000);
+        stress(&ElimStack::default(), 8, 10_000);
+        stress(
+            &ElimStackWithBackoff::<_, ExponentialBackoff>::default(),
+            8,
+            10_000,
+        );
+        stress(&LockStack::default(), 8, 10_000);
+        stress(&SpinLockStack::default(), 8, 10_000);
+        stress(&CountedStack::<_, TreiberStack<_>>::default(), 8, 10_000);
+        stress(&TaggedTreiberStack::default(), 8, 10_000);
+        stress(
+            &base::ElimStack::<_, LockStack<_>, SpinBackoff, 4>::default(),
+            8,
+            10_000,
+        );
+
+        let stack: Box<dyn Stack<u64> + Send + Sync> = Box::new(ElimStack::default());
+        stress(&stack, 8, 10_000);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn message_passing() {
+        use base::test::message_passing;
+
+        message_passing(&TreiberStack::default(), 8, 10_000);
+        message_passing(&TreiberStack::with_node_cache(64), 8, 10_000);
+        message_passing(&ElimStack::default(), 8, 10_000);
+        message_passing(&CountedStack::<_, TreiberStack<_>>::default(), 8, 10_000);
+        message_passing(&TaggedTreiberStack::default(), 8, 10_000);
+        #[cfg(feature = "hazard-stack")]
+        message_passing(&HpTreiberStack::default(), 8, 10_000);
+    }
+
+    #[test]
+    fn pop_each() {
+        let stack = ElimStack::default();
+        stack.push_iter(0..10);
+
+        let mut seen = Vec::new();
+        assert_eq!(
+            stack.pop_each(|t| {
+                seen.push(t);
+                t > 5
+            }),
+            5
+        );
+        assert_eq!(seen, vec![9, 8, 7, 6, 5]);
+        assert_eq!(stack.len(), 5);
+
+        assert_eq!(stack.pop_each(|_| true), 5);
+        assert_eq!(stack.pop_each(|_| true), 0);
+        assert_eq!(stack.len(), 0);
+    }
+
+    #[test]
+    fn pop_each_panic() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let drops = AtomicUsize::new(0);
+        let stack = ElimStack::default();
+        stack.push_iter((0..10).map(|_| DropCounter(&drops)));
+
+        let mut count = 0;
+        assert!(catch_unwind(AssertUnwindSafe(|| {
+            let _ = stack.pop_each(|_| {
+                count += 1;
+                assert!(count < 3, "closure panics");
+                true
+            });
+        }))
+        .is_err());
+
+        // The value passed to the panicking call is dropped, and the others stay in the stack.
+        assert_eq!(drops.load(Ordering::Relaxed), 3);
+        assert_eq!(stack.len(), 7);
+        assert_eq!(stack.pop_each(|_| true), 7);
+        assert_eq!(drops.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn steal_half() {
+        const OPS: u64 = 10_000;
+
+        let stacks = [ElimStack::default(), ElimStack::default()];
+
+        // Two workers push and pop on their own stack and steal from each other.
+        let mut values = scope(|scope| {
+            let handles = (0..2)
+                .map(|t| {
+                    let (own, other) = (&stacks[t], &stacks[1 - t]);
+                    scope.spawn(move |_| {
+                        let mut popped = Vec::new();
+                        for seq in 0..OPS {
+                            own.push((t as u64) << 32 | seq);
+                            match seq % 4 {
+                                0 => popped.extend(own.pop()),
+                                1 => {
+                                    let _ = other.steal_half(own);
+                                }
+                                _ => {}
+                            }
+                        }
+                        popped
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .unwrap();
+
+        for stack in &stacks {
+            values.extend(stack.drain_all());
+            assert_eq!(stack.len(), 0);
+        }
+
+        values.sort_unstable();
+        let pushed = (0..2u64)
+            .flat_map(|t| (0..OPS).map(move |seq| t << 32 | seq))
+            .collect::<Vec<_>>();
+        assert_eq!(values, pushed);
+    }
+
+    #[test]
+    fn append() {
+        let stack = (0..2).collect::<ElimStack<_>>();
+        let other = (2..5).collect::<ElimStack<_>>();
+        assert_eq!(stack.append(&other), 3);
+        assert_eq!(stack.len(), 5);
+        assert_eq!(other.len(), 0);
+        assert_eq!(stack.drain_all(), vec![4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn drain_to() {
+        let stack = (0..10).collect::<ElimStack<_>>();
+        let (tx, rx) = crossbeam_channel::bounded(2);
+        scope(|scope| {
+            let receiver = scope.spawn(move |_| {
+                let mut received = Vec::new();
+                while let Ok(t) = rx.recv() {
+                    thread::sleep(Duration::from_millis(1));
+                    received.push(t);
+                }
+                received
+            });
+            assert_eq!(stack.drain_to(&tx), 10);
+            drop(tx);
+            assert_eq!(receiver.join().unwrap(), (0..10).rev().collect::<Vec<_>>());
+        })
+        .unwrap();
+        assert!(stack.pop().is_none());
+    }
+
+    #[test]
+    fn drain_to_disconnected() {
+        let stack = (0..10).collect::<ElimStack<_>>();
+        let (tx, rx) = crossbeam_channel::bounded(0);
+        scope(|scope| {
+            let _ = scope.spawn(move |_| {
+                for t in (7..10).rev() {
+                    assert_eq!(rx.recv().unwrap(), t);
+                }
+                drop(rx);
+            });
+            assert_eq!(stack.drain_to(&tx), 3);
+        })
+        .unwrap();
+        assert_eq!(stack.len(), 7);
+        assert_eq!(stack.drain_all(), (0..7).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn adaptive_elim_wait() {
+        // Without a partner, every push request that tries elimination times out.
+        let stack = base::ElimStack::<_, AlwaysContended, SpinBackoff, 1>::with_elim_wait(
+            Duration::from_millis(1),
+        );
+        assert_eq!(stack.try_push_bounded(1, 20), Err(1));
+        let stats = stack.stats();
+        // 4 timeouts, 8 skipped rounds, 1 timeout, then skipped rounds again.
+        assert_eq!(stats.failed_exchanges, 5);
+        assert_eq!(stats.skipped_exchanges, 15);
+
+        // An exchange restores the full wait.
+        scope(|scope| {
+            let _ = scope.spawn(|_| stack.push(2));
+            assert_eq!(stack.pop(), Some(2));
+        })
+        .unwrap();
+        stack.reset_stats();
+        assert_eq!(stack.try_push_bounded(3, 1), Err(3));
+        assert_eq!(stack.stats().failed_exchanges, 1);
+    }
+
+    #[test]
+    fn adaptive_elim_wait_per_stack() {
+        let stack = base::ElimStack::<_, AlwaysContended, SpinBackoff, 1>::with_elim_wait(
+            Duration::from_millis(1),
+        );
+        assert_eq!(stack.try_push_bounded(1, 5), Err(1));
+        assert_eq!(stack.stats().skipped_exchanges, 1);
+
+        // The timeouts on the first stack do not make pushes of the same thread skip elimination
+        // on another one.
+        let other = base::ElimStack::<_, AlwaysContended, SpinBackoff, 1>::with_elim_wait(
+            Duration::from_millis(1),
+        );
+        assert_eq!(other.try_push_bounded(2, 1), Err(2));
+        let stats = other.stats();
+        assert_eq!(stats.failed_exchanges, 1);
+        assert_eq!(stats.skipped_exchanges, 0);
+    }
+
+    #[cfg(feature = "trace")]
+    #[test]
+    fn traced_direct() {
+        let stack = ElimStack::default();
+        for i in 0..100 {
+            assert_eq!(stack.push_traced(i), CompletionPath::DirectCas);
+        }
+        for i in (0..100).rev() {
+            assert_eq!(stack.pop_traced(), (Some(i), CompletionPath::DirectCas));
+        }
+        assert_eq!(stack.pop_traced(), (None, CompletionPath::DirectCas));
+    }
+
+    #[cfg(feature = "trace")]
+    #[test]
+    fn traced_eliminated() {
+        // Every operation on `AlwaysContended` completes by elimination.
+        let stack = base::ElimStack::<_, AlwaysContended, SpinBackoff, 1>::default();
+        scope(|scope| {
+            let _ = scope.spawn(|_| {
+                for i in 0..10 {
+                    assert!(matches!(
+                        stack.push_traced(i),
+                        CompletionPath::Eliminated { .. }
+                    ));
+                }
+            });
+            for _ in 0..10 {
+                let (value, path) = stack.pop_traced();
+                assert!(value.is_some());
+                assert!(matches!(path, CompletionPath::Eliminated { .. }));
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn cache_padding() {
+        use core::mem::{align_of, size_of, size_of_val};
+
+        // Fails if the padding of the head or of the elimination slots disappears.
+        const MIN_LINE: usize = 32;
+        assert!(align_of::<TreiberStack<u8>>() >= MIN_LINE);
+        assert!(size_of::<TreiberStack<u8>>() >= MIN_LINE);
+
+        let stack = base::ElimStack::<u8, TreiberStack<_>, SpinBackoff, 4>::default();
+        assert!(size_of_val(&stack.slots) >= 4 * MIN_LINE);
+        assert!(size_of_val(&stack.slots[0]) >= MIN_LINE);
+    }
+
+    #[test]
+    fn lock_stack() {
+        push_pop_contended(&LockStack::default());
+        push_pop_contended(&base::ElimStack::<
+            _,
+            LockStack<_>,
+            SpinBackoff,
+            DEFAULT_ELIM_SIZE,
+        >::default());
+        push_pop_contended(
+            &base::ElimStack::<_, LockStack<_>, SpinBackoff, 1>::with_elim_wait(
+                Duration::from_nanos(0),
+            ),
+        );
+    }
+
+    #[test]
+    fn spin_lock_stack() {
+        push_pop_contended(&SpinLockStack::default());
+        push_pop_contended(&base::ElimStack::<
+            _,
+            SpinLockStack<_>,
+            SpinBackoff,
+            DEFAULT_ELIM_SIZE,
+        >::default());
+    }
+
+    #[test]
+    fn elim_wait() {
+        push_pop_contended(&ElimStack::with_elim_wait(Duration::from_nanos(0)));
+        push_pop_contended(&ElimStack::with_elim_wait(Duration::from_millis(1)));
+
+        let mut stack = ElimStack::default();
+        stack.set_elim_wait(Duration::from_micros(10));
+        push_pop_contended(&stack);
+    }
+
+    /// Reports the throughput for several elimination waits.
+    ///
+    /// Run with `cargo test --release -- --ignored elim_wait_throughput --nocapture`.
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    #[ignore]
+    fn elim_wait_throughput() {
+        for wait in &[0, 1_000, 100_000, 10_000_000] {
+            let stack = ElimStack::with_elim_wait(Duration::from_nanos(*wait));
+            let start = Instant::now();
+            push_pop_contended(&stack);
+            println!("elim_wait = {:>8}ns: {:?}", wait, start.elapsed());
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn push_pop_with() {
+        let stack = ElimStack::default();
+        let guard = pin();
+        for i in 0..1_000 {
+            stack.push_with(i, &guard);
+        }
+        assert_eq!(stack.len(), 1_000);
+        for i in (0..1_000).rev() {
+            assert_eq!(stack.pop_with(&guard), Some(i));
+        }
+        assert_eq!(stack.pop_with(&guard), None);
+        assert_eq!(stack.len(), 0);
+    }
+
+    #[test]
+    fn push_pop_with_contended() {
+        let stack = base::ElimStack::<_, AlwaysContended, SpinBackoff, 1>::default();
+
+        scope(|scope| {
+            let _ = scope.spawn(|_| {
+                let guard = pin();
+                for i in 0..10 {
+                    stack.push_with(i, &guard);
+                }
+            });
+            let guard = pin();
+            let mut popped = (0..10)
+                .map(|_| loop {
+                    if let Ok(Some(t)) = stack.try_pop(&guard) {
+                        break t;
+                    }
+                    guard.flush();
+                })
+                .collect::<Vec<_>>();
+            popped.sort_unstable();
+            assert_eq!(popped, (0..10).collect::<Vec<_>>());
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn elimination_hook() {
+        const VALUES: i32 = 10;
+
+        // `AlwaysContended` never succeeds, so the values can only cross over by elimination.
+        let mut stack = base::ElimStack::<_, AlwaysContended, SpinBackoff, 1>::default();
+        stack.set_elim_wait(Duration::from_secs(10));
+        let barrier = Barrier::new(2);
+        assert!(!stack.eliminated.load(Ordering::Relaxed));
+
+        scope(|scope| {
+            let _ = scope.spawn(|_| {
+                let _ = barrier.wait();
+                for i in 0..VALUES {
+                    stack.push(i);
+                }
+            });
+            let _ = barrier.wait();
+            let popped = (0..VALUES)
+                .map(|_| stack.pop().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(popped, (0..VALUES).collect::<Vec<_>>());
+        })
+        .unwrap();
+
+        assert!(stack.eliminated.load(Ordering::Relaxed));
+        assert_eq!(stack.stats().exchanges, VALUES as usize);
+        assert_eq!(stack.len(), 0);
+    }
+
+    /// Compares pinning for each operation against a single pin for a batch of 1000 operations.
+    ///
+    /// Run with `cargo test --release -- --ignored push_pop_with_throughput --nocapture`.
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    #[ignore]
+    fn push_pop_with_throughput() {
+        const BATCHES: usize = 1_000;
+        const BATCH: usize = 1_000;
+
+        let stack = ElimStack::default();
+
+        let start = Instant::now();
+        for _ in 0..BATCHES {
+            for i in 0..BATCH {
+                stack.push(i);
+            }
+            for _ in 0..BATCH {
+                let _ = stack.pop();
+            }
+        }
+        println!("push/pop:           {:?}", start.elapsed());
+
+        let start = Instant::now();
+        for _ in 0..BATCHES {
+            let guard = pin();
+            for i in 0..BATCH {
+                stack.push_with(i, &guard);
+            }
+            for _ in 0..BATCH {
+                let _ = stack.pop_with(&guard);
+            }
+        }
+        println!("push_with/pop_with: {:?}", start.elapsed());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn active_elim_range() {
+        fn run(threads: usize) -> usize {
+            let stack = base::ElimStack::<_, AlwaysContended, SpinBackoff, 32>::with_elim_wait(
+                Duration::from_micros(100),
+            );
+            scope(|scope| {
+                for t in 0..threads {
+                    let stack = &stack;
+                    scope.spawn(move |_| {
+                        for i in 0..100 {
+                            if t % 2 == 0 {
+                                stack.push(i);
+                            } else {
+                                assert!(stack.pop().is_some());
+                            }
+                        }
+                    });
+                }
+            })
+            .unwrap();
+            stack.active_elim_range()
+        }
+
+        // A single pusher never collides, so the range stays at its initial size.
+        let low = run(2);
+        let high = run(32);
+        assert_eq!(low, 1);
+        assert!(low < high, "{} >= {}", low, high);
+    }
+
+    static SNOOZES: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Debug, Default)]
+    struct CountingBackoff;
+
+    impl Backoff for CountingBackoff {
+        fn snooze(&mut self) {
+            let _ = SNOOZES.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    #[test]
+    fn backoff() {
+        push_pop_contended(&ElimStackWithBackoff::<_, ExponentialBackoff>::default());
+        push_pop_contended(&ElimStackWithBackoff::<_, YieldBackoff>::default());
+
+        // Every operation on `AlwaysContended` needs at least one retry.
+        let stack = base::ElimStack::<_, AlwaysContended, CountingBackoff, 1>::default();
+        scope(|scope| {
+            scope.spawn(|_| {
+                for i in 0..10 {
+                    stack.push(i);
+                }
+            });
+            scope.spawn(|_| {
+                for _ in 0..10 {
+                    assert!(stack.pop().is_some());
+                }
+            });
+        })
+        .unwrap();
+        assert!(SNOOZES.load(Ordering::Relaxed) >= 10);
+    }
+
+    #[test]
+    fn stats_exchange() {
+        let stack = base::ElimStack::<_, AlwaysContended, SpinBackoff, 1>::default();
+
+        scope(|scope| {
+            scope.spawn(|_| {
+                for i in 0..10 {
+                    stack.push(i);
+                }
+            });
+            scope.spawn(|_| {
+                let mut popped = (0..10).map(|_| stack.pop().unwrap()).collect::<Vec<_>>();
+                popped.sort_unstable();
+                assert_eq!(popped, (0..10).collect::<Vec<_>>());
+            });
+        })
+        .unwrap();
+
+        let stats = stack.stats();
+        assert_eq!(stats.exchanges, 10);
+        assert_eq!(stats.direct_cas, 0);
+
+        stack.reset_stats();
+        assert_eq!(stack.stats(), ElimStats::default());
+    }
+
+    #[test]
+    fn elimination_disabled() {
+        let stack = ElimStack::default();
+        assert!(stack.elimination_enabled());
+        stack.set_elimination_enabled(false);
+        push_pop_contended(&stack);
+
+        let stats = stack.stats();
+        assert_eq!(stats.exchanges, 0);
+        assert_eq!(stats.failed_exchanges, 0);
+        assert_eq!(stats.skipped_exchanges, 0);
+        assert!(!stack.eliminated.load(Ordering::Relaxed));
+
+        // Operations that fail on the inner stack fail right away.
+        let stack = base::ElimStack::<_, AlwaysContended, SpinBackoff, 1>::with_elim_wait(
+            Duration::from_millis(1),
+        );
+        stack.set_elimination_enabled(false);
+        let guard = pin();
+        let req = stack
+            .try_push(Owned::new(Node::from(1)), &guard)
+            .unwrap_err();
+        assert_eq!(stack.try_pop(&guard), Err(()));
+        assert_eq!(stack.stats(), ElimStats::default());
+
+        // Until elimination is enabled again.
+        stack.set_elimination_enabled(true);
+        assert!(stack.try_push(req, &guard).is_err());
+        assert_eq!(stack.stats().failed_exchanges, 1);
+    }
+
+    #[test]
+    fn seeded_elimination() {
+        const ELIM_SIZE: usize = 8;
+
+        // Finds a seed whose first two draws pick the same slot, for the push and then the pop.
+        let seed = (0..)
+            .find(|&seed| {
+                let rng = base::ElimRng::seeded(seed);
+                rng.gen_index(ELIM_SIZE) == rng.gen_index(ELIM_SIZE)
+            })
+            .unwrap();
+
+        let mut stack =
+            base::ElimStack::<_, AlwaysContended, SpinBackoff, ELIM_SIZE>::with_seed(seed);
+        stack.set_elim_wait(Duration::from_secs(10));
+        stack.active_elim_range.store(ELIM_SIZE, Ordering::Relaxed);
+
+        scope(|scope| {
+            let _ = scope.spawn(|_| stack.push(42));
+            thread::sleep(Duration::from_millis(100));
+            assert_eq!(stack.try_pop(&pin()), Ok(Some(42)));
+        })
+        .unwrap();
+        assert_eq!(stack.stats().exchanges, 1);
+        assert_eq!(stack.stats().failed_exchanges, 0);
+    }
+
+    #[test]
+    fn stats_sequential() {
+        let stack = ElimStack::default();
+        for i in 0..100 {
+            stack.push(i);
+        }
+        while stack.pop().is_some() {}
+
+        let stats = stack.stats();
+        assert_eq!(stats.exchanges, 0);
+        assert_eq!(stats.failed_exchanges, 0);
+        assert_eq!(stats.direct_cas, 201);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn is_empty() {
+        let stack = ElimStack::default();
+
+        scope(|scope| {
+            for _ in 0..5 {
+                scope.spawn(|_| {
+                    for i in 0..10_000 {
+                        stack.push(i);
+                        assert!(stack.pop().is_some());
+                    }
+                });
+            }
+            for _ in 0..5 {
+                scope.spawn(|_| {
+                    for _ in 0..10_000 {
+                        let _ = stack.is_empty(&pin());
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        assert!(stack.is_empty(&pin()));
+    }
+
+    #[test]
+    fn len() {
+        let stack = ElimStack::default();
+        assert_eq!(stack.len(), 0);
+        stack.push(1);
+        stack.push(2);
+        assert_eq!(stack.len(), 2);
+        assert_eq!(stack.pop(), Some(2));
+        assert_eq!(stack.len(), 1);
+        assert_eq!(stack.drain_all(), vec![1]);
+        assert_eq!(stack.len(), 0);
+        assert!(stack.pop().is_none());
+        assert_eq!(stack.len(), 0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn len_stress() {
+        fn stress<S: Stack<i32> + Default + Sync>() {
+            let stack = base::ElimStack::<_, S, SpinBackoff, DEFAULT_ELIM_SIZE>::default();
+
+            scope(|scope| {
+                for _ in 0..10 {
+                    scope.spawn(|_| {
+                        for i in 0..100_000 {
+                            stack.push(i);
+                            assert!(stack.pop().is_some());
+                        }
+                    });
+                }
+            })
+            .unwrap();
+
+            assert!(stack.pop().is_none());
+            assert_eq!(stack.len(), 0);
+        }
+
+        stress::<TreiberStack<_>>();
+        stress::<LockStack<_>>();
+        stress::<SpinLockStack<_>>();
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn drop_payloads() {
+        let drops = AtomicUsize::new(0);
+
+        let stack = ElimStack::default();
+        scope(|scope| {
+            for _ in 0..4 {
+                let _ = scope.spawn(|_| {
+                    for _ in 0..1_000 {
+                        stack.push(DropCounter(&drops));
+                        drop(stack.pop());
+                    }
+                    stack.flush_reclamation();
+                });
+            }
+        })
+        .unwrap();
+        stack.push_iter((0..10).map(|_| DropCounter(&drops)));
+        stack.flush_reclamation();
+        assert_eq!(drops.load(Ordering::Relaxed), 4_000);
+
+        drop(stack);
+        assert_eq!(drops.load(Ordering::Relaxed), 4_010);
+    }
+
+    #[test]
+    fn drop_panic() {
+        use crate::elim_stack::treiber_stack::test::PanicOnDrop;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let drops = AtomicUsize::new(0);
+        let stack = ElimStack::default();
+        stack.push_iter((0..10).map(|i| PanicOnDrop(&drops, i == 5)));
+        assert!(catch_unwind(AssertUnwindSafe(|| drop(stack))).is_err());
+        assert_eq!(drops.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    fn new_with() {
+        let inner = (0..10).collect::<TreiberStack<_>>();
+        let stack = ElimStack::new_with(inner);
+        assert_eq!(stack.len(), 10);
+        assert_eq!(stack.pop(), Some(9));
+        stack.push(10);
+
+        let inner = stack.into_inner();
+        let mut expected = (0..9).collect::<Vec<_>>();
+        expected.push(10);
+        expected.reverse();
+        assert_eq!(inner.drain_all(), expected);
+    }
+
+    #[test]
+    fn len_mut() {
+        fn check<S: Stack<u32> + Default>() {
+            let mut stack = S::default();
+            assert_eq!(stack.len_mut(), 0);
+            stack.push_iter(0..10);
+            assert_eq!(stack.len_mut(), 10);
+            assert_eq!(stack.pop(), Some(9));
+            assert_eq!(stack.len_mut(), 9);
+        }
+
+        check::<TreiberStack<u32>>();
+        check::<ElimStack<u32>>();
+        check::<LockStack<u32>>();
+        check::<SpinLockStack<u32>>();
+        check::<CountedStack<u32, TreiberStack<u32>>>();
+        check::<StripedStack<u32, TreiberStack<u32>>>();
+        check::<TaggedTreiberStack<u32>>();
+        #[cfg(feature = "hazard-stack")]
+        check::<HpTreiberStack<u32>>();
+    }
+
+    #[test]
+    fn from_vec() {
+        let stack = ElimStack::from(vec![1, 2, 3]);
+        assert_eq!(stack.len(), 3);
+        assert_eq!(stack.drain_all(), vec![3, 2, 1]);
+
+        let stack = ElimStack::<i32>::from(Vec::new());
+        assert_eq!(stack.len(), 0);
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn into_vec() {
+        let stack = ElimStack::default();
+        stack.push_iter(0..100_000);
+        assert_eq!(stack.into_vec(), (0..100_000).rev().collect::<Vec<_>>());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn serde_round_trip() {
+        for &n in &[0, 1, 100_000] {
+            let stack = (0..n).collect::<ElimStack<u32>>();
+            let json = serde_json::to_string(&stack).unwrap();
+            let stack = serde_json::from_str::<ElimStack<u32>>(&json).unwrap();
+            assert_eq!(stack.len(), n as usize);
+            assert_eq!(stack.into_vec(), (0..n).rev().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn clone() {
+        let stack = ElimStack::with_elim_wait(Duration::from_micros(1));
+        stack.push_iter(0..10);
+        let clone = stack.clone();
+        assert_eq!(clone.len(), 10);
+        assert_eq!(clone.elim_wait, stack.elim_wait);
+
+        assert_eq!(stack.drain_all().len(), 10);
+        assert_eq!(clone.len(), 10);
+        assert_eq!(clone.into_vec(), (0..10).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn clone_drop() {
+        let drops = AtomicUsize::new(0);
+
+        let stack = ElimStack::default();
+        stack.push_iter((0..10).map(|_| DropCounter(&drops)));
+        let clone = stack.clone();
+        assert_eq!(clone.len(), 10);
+
+        // Each copy drops its own values.
+        drop(stack);
+        assert_eq!(drops.load(Ordering::Relaxed), 10);
+        drop(clone);
+        assert_eq!(drops.load(Ordering::Relaxed), 20);
+    }
+
+    #[test]
+    fn clear() {
+        let mut stack = ElimStack::default();
+        stack.push_iter(0..10);
+        stack.clear();
+        assert_eq!(stack.len(), 0);
+        assert_eq!(stack.pop(), None);
+        stack.push(1);
+        assert_eq!(stack.len(), 1);
+
+        stack.push_iter(0..10);
+        stack.clear_concurrent();
+        assert_eq!(stack.len(), 0);
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn push_iter() {
+        let stack = ElimStack::default();
+        stack.push_iter(0..10);
+        assert_eq!(stack.len(), 10);
+        assert_eq!(stack.drain_all(), (0..10).rev().collect::<Vec<_>>());
+        assert_eq!(stack.len(), 0);
+    }
+
+    #[test]
+    fn pop_n() {
+        let stack = ElimStack::default();
+        stack.push_iter(0..10);
+        assert_eq!(stack.pop_n(4), vec![9, 8, 7, 6]);
+        assert_eq!(stack.len(), 6);
+
+        let mut buf = vec![100];
+        assert_eq!(stack.pop_many_into(&mut buf, 2), 2);
+        assert_eq!(buf, vec![100, 5, 4]);
+        assert_eq!(stack.len(), 4);
+    }
+
+    #[test]
+    fn into_iter() {
+        let drops = AtomicUsize::new(0);
+
+        let stack = ElimStack::default();
+        for i in 0..10 {
+            stack.push((i, DropCounter(&drops)));
+        }
+        for (i, (v, _)) in stack.into_iter().enumerate().take(4) {
+            assert_eq!(v, 9 - i);
+        }
+        assert_eq!(drops.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    fn from_iter() {
+        let stack: ElimStack<u32> = (0..0).collect();
+        assert!(stack.pop().is_none());
+        assert_eq!(stack.len(), 0);
+
+        let stack: ElimStack<u32> = (0..100).collect();
+        assert_eq!(stack.len(), 100);
+        assert_eq!(stack.drain_all(), (0..100).rev().collect::<Vec<_>>());
+        assert_eq!(stack.len(), 0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn extend_concurrent() {
+        const ELEMENTS: usize = 100_000;
+
+        let stack = ElimStack::default();
+        let done = AtomicBool::new(false);
+
+        let mut popped = scope(|scope| {
+            let poppers = (0..2)
+                .map(|_| {
+                    scope.spawn(|_| {
+                        let mut popped = Vec::new();
+                        while !done.load(Ordering::Acquire) {
+                            popped.extend(stack.pop());
+                        }
+                        popped
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            (&stack).extend(0..ELEMENTS / 2);
+            (&stack).extend(ELEMENTS / 2..ELEMENTS);
+            done.store(true, Ordering::Release);
+
+            poppers
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .unwrap();
+
+        popped.append(&mut stack.drain_all());
+        popped.sort_unstable();
+        assert_eq!(popped, (0..ELEMENTS).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn debug() {
+        let stack = ElimStack::default();
+        stack.push_iter(0..3);
+        let debug = format!("{:?}", stack);
+        assert!(debug.contains("TreiberStack<i32>"), "{}", debug);
+        assert!(debug.contains("TreiberStack [2, 1, 0]"), "{}", debug);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn debug_concurrent() {
+        let stack = ElimStack::default();
+        let done = AtomicBool::new(false);
+
+        scope(|scope| {
+            let _ = scope.spawn(|_| {
+                for i in 0..10_000 {
+                    stack.push(i);
+                    let _ = stack.pop();
+                }
+                done.store(true, Ordering::Release);
+            });
+            while !done.load(Ordering::Acquire) {
+                let _ = format!("{:?}", stack);
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn pop_if() {
+        let stack = ElimStack::default();
+        stack.push_iter(0..3);
+        assert_eq!(stack.pop_if(|&t| t == 0), None);
+        assert_eq!(stack.pop_if(|&t| t == 2), Some(2));
+        assert_eq!(stack.len(), 2);
+    }
+
+    #[test]
+    fn pop_timeout() {
+        let stack = ElimStackWithBackoff::<_, YieldBackoff>::default();
+
+        let start = Instant::now();
+        assert_eq!(stack.pop_timeout(Duration::from_millis(100)), None);
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(100));
+        assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+
+        stack.push(1);
+        assert_eq!(stack.pop_timeout(Duration::from_secs(0)), Some(1));
+
+        scope(|scope| {
+            let _ = scope.spawn(|_| {
+                thread::sleep(Duration::from_millis(100));
+                stack.push(2);
+            });
+            let start = Instant::now();
+            assert_eq!(stack.pop_timeout(Duration::from_secs(10)), Some(2));
+            assert!(start.elapsed() < Duration::from_secs(5));
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn pop_reservation() {
+        let stack = ElimStack::with_elim_wait(Duration::from_secs(10));
+        stack.set_pop_reservations(true);
+        assert!(stack.pop_reservations());
+
+        scope(|scope| {
+            let _ = scope.spawn(|_| {
+                thread::sleep(Duration::from_millis(50));
+                stack.push(1);
+            });
+            assert_eq!(stack.pop_timeout(Duration::from_secs(10)), Some(1));
+        })
+        .unwrap();
+
+        // The value was handed over without touching the inner stack.
+        let stats = stack.stats();
+        assert_eq!(stats.exchanges, 1);
+        assert_eq!(stats.direct_cas, 1);
+        assert_eq!(stack.len(), 0);
+    }
+
+    #[test]
+    fn pop_reservation_timeout() {
+        let stack = ElimStack::with_elim_wait(Duration::from_millis(5));
+        stack.set_pop_reservations(true);
+        assert_eq!(stack.pop_timeout(Duration::from_millis(20)), None);
+
+        // Every reservation was withdrawn, so the push goes to the inner stack.
+        assert!(stack.slots.iter().all(|slot| slot.is_empty(&pin())));
+        stack.push(1);
+        assert_eq!(stack.stats().exchanges, 0);
+        assert_eq!(stack.pop(), Some(1));
+    }
+
+    #[test]
+    fn try_push_once_reservation() {
+        let stack = base::ElimStack::<_, AlwaysContended, SpinBackoff, 1>::with_elim_wait(
+            Duration::from_secs(10),
+        );
+        stack.set_pop_reservations(true);
+        stack.elim_failures.store(3, Ordering::Relaxed);
+
+        scope(|scope| {
+            let _ = scope.spawn(|_| while stack.try_push_once(1).is_err() {});
+            assert_eq!(stack.pop_timeout(Duration::from_secs(10)), Some(1));
+        })
+        .unwrap();
+
+        // Recorded like an exchange of a retrying push.
+        assert!(stack.eliminated.load(Ordering::Relaxed));
+        assert_eq!(stack.elim_failures.load(Ordering::Relaxed), 0);
+        assert_eq!(stack.stats().exchanges, 1);
+        assert_eq!(stack.len(), 0);
+    }
+
+    /// Pushes and pops with single attempts on each of 8 threads, retrying later on
+    /// [`Contended`], and checks that no value is lost or duplicated.
+    fn try_once_stress<S: Sync>(
+        stack: &S,
+        push: fn(&S, u64) -> Result<(), u64>,
+        pop: fn(&S) -> Result<Option<u64>, Contended>,
+    ) {
+        const THREADS: u64 = 8;
+        const OPS: u64 = 10_000;
+
+        let mut values = scope(|scope| {
+            let handles = (0..THREADS)
+                .map(|t| {
+                    scope.spawn(move |_| {
+                        let mut popped = Vec::new();
+                        for seq in 0..OPS {
+                            let mut value = t << 32 | seq;
+                            while let Err(v) = push(stack, value) {
+                                value = v;
+                                thread::yield_now();
+                            }
+                            if seq % 2 == 0 {
+                                loop {
+                                    match pop(stack) {
+                                        Ok(v) => break popped.extend(v),
+                                        Err(Contended) => thread::yield_now(),
+                                    }
+                                }
+                            }
+                        }
+                        popped
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .unwrap();
+        while let Ok(Some(v)) = pop(stack) {
+            values.push(v);
+        }
+
+        values.sort_unstable();
+        let pushed = (0..THREADS)
+            .flat_map(|t| (0..OPS).map(move |seq| t << 32 | seq))
+            .collect::<Vec<_>>();
+        assert_eq!(values, pushed);
+    }
+
+    #[test]
+    fn try_once() {
+        let stack = ElimStack::default();
+        assert_eq!(stack.try_pop_once(), Ok(None));
+        assert_eq!(stack.try_push_once(1), Ok(()));
+        assert_eq!(stack.len(), 1);
+        assert_eq!(stack.try_pop_once(), Ok(Some(1)));
+        assert_eq!(stack.len(), 0);
+
+        // A single probe of the elimination array fails without a waiting partner.
+        let stack = base::ElimStack::<_, AlwaysContended, SpinBackoff, 1>::default();
+        assert_eq!(stack.try_push_once(1), Err(1));
+        assert_eq!(stack.try_pop_once(), Err(Contended));
+        assert_eq!(stack.len(), 0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn try_once_concurrent() {
+        try_once_stress(
+            &TreiberStack::default(),
+            TreiberStack::try_push_once,
+            TreiberStack::try_pop_once,
+        );
+        try_once_stress(
+            &TreiberStack::with_node_cache(16),
+            TreiberStack::try_push_once,
+            TreiberStack::try_pop_once,
+        );
+        try_once_stress(
+            &ElimStack::default(),
+            ElimStack::try_push_once,
+            ElimStack::try_pop_once,
+        );
+    }
+
+    #[test]
+    fn pop_wait() {
+        let stack = ElimStack::default();
+
+        scope(|scope| {
+            let consumer = scope.spawn(|_| stack.pop_wait());
+            thread::sleep(Duration::from_millis(100));
+            stack.push(42);
+            assert_eq!(consumer.join().unwrap(), 42);
+        })
+        .unwrap();
+
+        // The consumer sleeps instead of spinning while the stack is empty.
+        assert!(stack.waiters.parks.load(Ordering::Relaxed) <= 2);
+    }
+
+    #[test]
+    fn pop_wait_many() {
+        const CONSUMERS: usize = 8;
+
+        let stack = ElimStack::default();
+
+        let mut popped = scope(|scope| {
+            let consumers = (0..CONSUMERS)
+                .map(|_| scope.spawn(|_| stack.pop_wait()))
+                .collect::<Vec<_>>();
+            thread::sleep(Duration::from_millis(100));
+            for i in 0..CONSUMERS {
+                stack.push(i);
+            }
+            consumers
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .unwrap();
+
+        popped.sort_unstable();
+        assert_eq!(popped, (0..CONSUMERS).collect::<Vec<_>>());
+        assert!(stack.waiters.parks.load(Ordering::Relaxed) <= 4 * CONSUMERS);
+    }
+
+    #[test]
+    fn pop_wait_deadline() {
+        let stack = ElimStack::default();
+
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(100);
+        assert_eq!(stack.pop_wait_deadline(deadline), None);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        stack.push(1);
+        assert_eq!(stack.pop_wait_deadline(deadline), Some(1));
+
+        scope(|scope| {
+            let consumer =
+                scope.spawn(|_| stack.pop_wait_deadline(Instant::now() + Duration::from_secs(10)));
+            thread::sleep(Duration::from_millis(100));
+            stack.push(2);
+            assert_eq!(consumer.join().unwrap(), Some(2));
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn try_push_bounded() {
+        let stack = ElimStack::default();
+        assert_eq!(
+            stack.try_push_bounded(String::from("a"), 0),
+            Err(String::from("a"))
+        );
+        assert_eq!(stack.try_push_bounded(String::from("b"), 1), Ok(()));
+        assert_eq!(stack.pop(), Some(String::from("b")));
+        assert!(stack.pop().is_none());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn try_push_bounded_concurrent() {
+        const THREADS: usize = 16;
+        const ELEMENTS: usize = 1_000;
+
+        let stack = ElimStack::default();
+
+        let (mut rejected, mut popped) = scope(|scope| {
+            let handles = (0..THREADS)
+                .map(|t| {
+                    let stack = &stack;
+                    scope.spawn(move |_| {
+                        let mut rejected = Vec::new();
+                        let mut popped = Vec::new();
+                        for i in 0..ELEMENTS {
+                            if let Err(v) = stack.try_push_bounded(t * ELEMENTS + i, 1) {
+                                rejected.push(v);
+                            }
+                            if i % 2 == 0 {
+                                popped.extend(stack.pop());
+                            }
+                        }
+                        (rejected, popped)
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut rejected = Vec::new();
+            let mut popped = Vec::new();
+            for handle in handles {
+                let (r, p) = handle.join().unwrap();
+                rejected.extend(r);
+                popped.extend(p);
+            }
+            (rejected, popped)
+        })
+        .unwrap();
+
+        popped.append(&mut stack.drain_all());
+        popped.append(&mut rejected);
+        popped.sort_unstable();
+        assert_eq!(popped, (0..THREADS * ELEMENTS).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn try_push_bounded_own_value() {
+        const THREADS: i32 = if cfg!(miri) { 2 } else { 8 };
+        const ELEMENTS: i32 = if cfg!(miri) { 10 } else { 200 };
+
+        // Without pops, the pushes only meet each other in the elimination array, and each must
+        // get its own value back.
+        let stack = base::ElimStack::<_, AlwaysContended, SpinBackoff, 1>::with_elim_wait(
+            Duration::from_micros(100),
+        );
+        scope(|scope| {
+            for t in 0..THREADS {
+                let stack = &stack;
+                scope.spawn(move |_| {
+                    for i in 0..ELEMENTS {
+                        let value = t * ELEMENTS + i;
+                        assert_eq!(stack.try_push_bounded(value, 2), Err(value));
+                    }
+                });
+            }
+        })
+        .unwrap();
+        assert_eq!(stack.len(), 0);
+    }
+
+    #[test]
+    fn peek() {
+        let stack = ElimStack::default();
+        stack.push(1);
+        stack.push(2);
+        assert_eq!(stack.peek(&pin()), Some(&2));
+        assert_eq!(stack.pop(), Some(2));
+        assert_eq!(stack.pop(), Some(1));
+        assert!(stack.peek(&pin()).is_none());
+    }
+}
+
+/// Small tests of the unsafe code, cheap enough to run under Miri in CI with
+/// `MIRIFLAGS=-Zmiri-ignore-leaks cargo +nightly miri test -p cs431-homework elim_stack::miri`.
+///
+/// The heavier tests of this module are ignored under Miri. The leak check has to be off, since
+/// the global collector of `crossbeam_epoch` is never dropped, and neither are the thread records
+/// and garbage bags that it owns. Instead, the tests count the values dropped, and flush the
+/// collector so that Miri also runs the deferred destructions of the nodes.
+#[cfg(test)]
+mod miri {
+    use super::*;
+    use crate::elim_stack::test::AlwaysContended;
+    use crate::elim_stack::treiber_stack::test::DropCounter;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use crossbeam_epoch::pin;
+    use crossbeam_utils::thread::scope;
+    use std::time::Duration;
+
+    #[test]
+    fn treiber_push_pop() {
+        let drops = AtomicUsize::new(0);
+
+        let stack = TreiberStack::default();
+        stack.push(DropCounter(&drops));
+        stack.push_iter((0..4).map(|_| DropCounter(&drops)));
+        drop(stack.pop());
+        drop(stack.pop_n(2));
+        assert_eq!(drops.load(Ordering::Relaxed), 3);
+        drop(stack.into_iter().next());
+        assert_eq!(drops.load(Ordering::Relaxed), 5);
+        flush();
+    }
+
+    #[test]
+    fn treiber_concurrent() {
+        let stack = TreiberStack::default();
+        let popped = AtomicUsize::new(0);
+        scope(|scope| {
+            for _ in 0..2 {
+                let _ = scope.spawn(|_| {
+                    for i in 0..10 {
+                        stack.push(i);
+                        // The drain may have taken it, or left one pushed by the other thread.
+                        if stack.pop().is_some() {
+                            let _ = popped.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                });
+            }
+            let _ = scope.spawn(|_| {
+                let _ = popped.fetch_add(stack.drain_all().len(), Ordering::Relaxed);
+            });
+        })
+        .unwrap();
+        assert_eq!(popped.load(Ordering::Relaxed) + stack.drain_all().len(), 20);
+        flush();
+    }
+
+    #[test]
+    fn elim_drop() {
+        let drops = AtomicUsize::new(0);
+
+        let stack = ElimStack::default();
+        stack.push_iter((0..4).map(|_| DropCounter(&drops)));
+        drop(stack.pop());
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+        drop(stack);
+        assert_eq!(drops.load(Ordering::Relaxed), 4);
+        flush();
+    }
+
+    #[test]
+    fn elim_exchange() {
+        let stack = base::ElimStack::<_, AlwaysContended, SpinBackoff, 1>::with_elim_wait(
+            Duration::from_millis(1),
+        );
+        scope(|scope| {
+            let _ = scope.spawn(|_| (0..4).for_each(|i| stack.push(i)));
+            let popped = (0..4).map(|_| stack.pop().unwrap()).collect::<Vec<_>>();
+            assert_eq!(popped, (0..4).collect::<Vec<_>>());
+        })
+        .unwrap();
+        flush();
+    }
+
+    #[test]
+    fn exchanger() {
+        let drops = AtomicUsize::new(0);
+
+        let exchanger = Exchanger::default();
+        scope(|scope| {
+            for _ in 0..2 {
+                let _ = scope.spawn(|_| {
+                    let mut t = DropCounter(&drops);
+                    loop {
+                        match exchanger.exchange(t, Duration::from_millis(1)) {
+                            Ok(theirs) => return drop(theirs),
+                            Err(mine) => t = mine,
+                        }
+                    }
+                });
+            }
+        })
+        .unwrap();
+        assert_eq!(drops.load(Ordering::Relaxed), 2);
+        flush();
+    }
+
+    /// Flushes the deferred destructions of this thread, which frees them once the epoch advances.
+    fn flush() {
+        for _ in 0..4 {
+            pin().flush();
+        }
+    }
 }
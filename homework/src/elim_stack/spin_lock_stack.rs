@@ -0,0 +1,154 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use crossbeam_epoch::{Guard, Owned};
+
+use super::backoff::{Backoff, ExponentialBackoff};
+use super::base::Stack;
+use super::treiber_stack::Node;
+
+/// Number of attempts to take the lock before a push or pop fails.
+const ACQUIRE_ATTEMPTS: usize = 4;
+
+/// Stack protected by a spinlock, as a baseline for short critical sections.
+///
+/// Taking the lock backs off exponentially between attempts. A push or pop fails if the lock is
+/// still held after a few attempts.
+pub struct SpinLockStack<T> {
+    locked: AtomicBool,
+    values: UnsafeCell<Vec<T>>,
+}
+
+unsafe impl<T: Send> Send for SpinLockStack<T> {}
+unsafe impl<T: Send> Sync for SpinLockStack<T> {}
+
+/// Exclusive access to the values of a [`SpinLockStack`], which releases the lock when dropped.
+struct SpinLockGuard<'s, T> {
+    stack: &'s SpinLockStack<T>,
+}
+
+impl<T> Default for SpinLockStack<T> {
+    fn default() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            values: UnsafeCell::new(Vec::new()),
+        }
+    }
+}
+
+impl<T> fmt::Debug for SpinLockStack<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpinLockStack")
+            .field("locked", &self.locked.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> SpinLockStack<T> {
+    fn try_lock(&self) -> Option<SpinLockGuard<'_, T>> {
+        let mut backoff = ExponentialBackoff::default();
+        for _ in 0..ACQUIRE_ATTEMPTS {
+            // Reads first, so that waiting threads do not steal the cache line from the holder.
+            if !self.locked.load(Ordering::Relaxed)
+                && self
+                    .locked
+                    .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return Some(SpinLockGuard { stack: self });
+            }
+            backoff.snooze();
+        }
+        None
+    }
+
+    fn lock(&self) -> SpinLockGuard<'_, T> {
+        loop {
+            if let Some(guard) = self.try_lock() {
+                return guard;
+            }
+            thread::yield_now();
+        }
+    }
+}
+
+impl<T> Deref for SpinLockGuard<'_, T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.stack.values.get() }
+    }
+}
+
+impl<T> DerefMut for SpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.stack.values.get() }
+    }
+}
+
+impl<T> Drop for SpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.stack.locked.store(false, Ordering::Release);
+    }
+}
+
+impl<T> Stack<T> for SpinLockStack<T> {
+    fn try_push(&self, req: Owned<Node<T>>, _guard: &Guard) -> Result<(), Owned<Node<T>>> {
+        let mut values = some_or!(self.try_lock(), return Err(req));
+        values.push(unsafe { ManuallyDrop::into_inner(ptr::read(&**req)) });
+        Ok(())
+    }
+
+    fn try_pop(&self, _guard: &Guard) -> Result<Option<T>, ()> {
+        self.try_lock().map(|mut values| values.pop()).ok_or(())
+    }
+
+    fn is_empty(&self, _guard: &Guard) -> bool {
+        self.lock().is_empty()
+    }
+
+    fn len_mut(&mut self) -> usize {
+        self.values.get_mut().len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crossbeam_epoch::pin;
+
+    #[test]
+    fn push_pop() {
+        let stack = SpinLockStack::default();
+        assert!(stack.is_empty(&pin()));
+        stack.push(1);
+        stack.push(2);
+        assert!(!stack.is_empty(&pin()));
+        assert_eq!(stack.pop(), Some(2));
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn try_locked() {
+        let stack = SpinLockStack::default();
+        stack.push(1);
+
+        let guard = pin();
+        let values = stack.lock();
+        let req = stack
+            .try_push(Owned::new(Node::from(2)), &guard)
+            .unwrap_err();
+        assert_eq!(***req, 2);
+        assert_eq!(stack.try_pop(&guard), Err(()));
+        drop(values);
+
+        assert!(stack.try_push(req, &guard).is_ok());
+        assert_eq!(stack.try_pop(&guard), Ok(Some(2)));
+    }
+}
@@ -0,0 +1,145 @@
+use core::fmt;
+use core::iter;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crossbeam_epoch::{Guard, Owned};
+
+use super::base::Stack;
+use super::treiber_stack::Node;
+
+/// Default number of stripes of a [`StripedStack`].
+const DEFAULT_STRIPES: usize = 8;
+
+/// Source of the stripe hints of new threads.
+static NEXT_HINT: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// Stripe hint of the current thread, handed out in round-robin order.
+    static HINT: usize = NEXT_HINT.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Stack spread over several inner stacks `S`, for many concurrent producers.
+///
+/// Each thread pushes to its own stripe, and pops from the first non-empty stripe starting from
+/// its own. The values of a stripe are in LIFO order, but there is no order across stripes: a
+/// pop may return an older value from the local stripe while a newer one is in another.
+pub struct StripedStack<T, S> {
+    stripes: Box<[S]>,
+    _marker: PhantomData<T>,
+}
+
+impl<T, S: Default> StripedStack<T, S> {
+    /// Creates a new stack with `stripes` inner stacks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stripes` is zero.
+    pub fn new(stripes: usize) -> Self {
+        assert!(stripes > 0, "striped stack must have a stripe");
+        Self {
+            stripes: iter::repeat_with(S::default).take(stripes).collect(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, S> StripedStack<T, S> {
+    /// Returns the number of stripes.
+    pub fn stripes(&self) -> usize {
+        self.stripes.len()
+    }
+
+    /// Returns the index of the stripe of the current thread.
+    fn local(&self) -> usize {
+        HINT.with(|hint| *hint % self.stripes.len())
+    }
+}
+
+impl<T, S: Default> Default for StripedStack<T, S> {
+    fn default() -> Self {
+        Self::new(DEFAULT_STRIPES)
+    }
+}
+
+impl<T, S: fmt::Debug> fmt::Debug for StripedStack<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StripedStack")
+            .field("stripes", &self.stripes)
+            .finish()
+    }
+}
+
+impl<T, S: Stack<T>> Stack<T> for StripedStack<T, S> {
+    fn try_push(&self, req: Owned<Node<T>>, guard: &Guard) -> Result<(), Owned<Node<T>>> {
+        self.stripes[self.local()].try_push(req, guard)
+    }
+
+    /// Tries the stripes in turn, starting from the local one.
+    ///
+    /// Returns `Ok(None)` only if every stripe was empty, and `Err(())` if a stripe was contended
+    /// and no value was found.
+    fn try_pop(&self, guard: &Guard) -> Result<Option<T>, ()> {
+        let local = self.local();
+        let (head, tail) = self.stripes.split_at(local);
+        let mut contended = false;
+        for stripe in tail.iter().chain(head) {
+            match stripe.try_pop(guard) {
+                Ok(Some(t)) => return Ok(Some(t)),
+                Ok(None) => {}
+                Err(()) => contended = true,
+            }
+        }
+        if contended {
+            Err(())
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn is_empty(&self, guard: &Guard) -> bool {
+        self.stripes.iter().all(|stripe| stripe.is_empty(guard))
+    }
+
+    fn len_mut(&mut self) -> usize {
+        self.stripes.iter_mut().map(Stack::len_mut).sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::elim_stack::base::test::stress;
+    use crate::elim_stack::treiber_stack::TreiberStack;
+    use crate::elim_stack::ElimStack;
+    use crossbeam_epoch::pin;
+
+    #[test]
+    fn push_pop() {
+        let stack = StripedStack::<_, TreiberStack<_>>::new(4);
+        assert_eq!(stack.stripes(), 4);
+        assert!(stack.is_empty(&pin()));
+
+        // A single thread uses a single stripe, which keeps the LIFO order.
+        stack.push_iter(0..10);
+        assert!(!stack.is_empty(&pin()));
+        assert_eq!(stack.drain_all(), (0..10).rev().collect::<Vec<_>>());
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn pop_other_stripe() {
+        let stack = StripedStack::<_, TreiberStack<_>>::new(4);
+        let other = (stack.local() + 1) % stack.stripes();
+        stack.stripes[other].push(1);
+        assert_eq!(stack.pop(), Some(1));
+        assert!(stack.is_empty(&pin()));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn stress_stripes() {
+        stress(&StripedStack::<_, TreiberStack<_>>::new(1), 8, 10_000);
+        stress(&StripedStack::<_, TreiberStack<_>>::new(4), 8, 10_000);
+        stress(&StripedStack::<_, ElimStack<_>>::default(), 8, 10_000);
+    }
+}
@@ -1,24 +1,63 @@
-use core::mem::ManuallyDrop;
+use core::fmt;
+use core::iter::FromIterator;
+use core::marker::PhantomData;
+use core::mem::{self, ManuallyDrop};
 use core::ops::Deref;
 use core::ptr;
-use core::sync::atomic::Ordering;
+use core::sync::atomic::{fence, AtomicUsize, Ordering};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
 
-use crossbeam_epoch::{unprotected, Atomic, Guard, Owned};
+use crossbeam_epoch::{pin, unprotected, Atomic, Guard, Owned, Shared};
+use crossbeam_utils::CachePadded;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use super::base::Stack;
+use super::backoff::{Backoff, SpinBackoff};
+use super::base::{Contended, Stack};
 
+#[cfg(feature = "hazard-stack")]
+pub mod hp;
+pub mod tagged;
+
+/// Node of [`TreiberStack`], which is also the push request of every [`Stack`].
 #[derive(Debug)]
 pub struct Node<T> {
     data: ManuallyDrop<T>,
     next: Atomic<Node<T>>,
 }
 
-/// Treiber's lock-free stack.
+/// Treiber's lock-free stack, whose retry loops wait with `B`.
 ///
 /// Usable with any number of producers and consumers.
-#[derive(Debug)]
-pub struct TreiberStack<T> {
+pub struct TreiberStack<T, B: Backoff = SpinBackoff> {
+    /// Padded so that the head does not share a cache line with neighbouring fields.
+    head: CachePadded<Atomic<Node<T>>>,
+    /// Retired nodes for reuse by pushes, if enabled by [`TreiberStack::with_node_cache`].
+    cache: Option<Arc<NodeCache<T>>>,
+    /// Number of running [`TreiberStack::with_values`], whose values pops must not hand out.
+    ///
+    /// A pop waits for it to drop to zero, so borrowers that keep overlapping starve pops, and
+    /// a pop from inside `with_values` on the same stack never returns.
+    borrowers: AtomicUsize,
+    _marker: PhantomData<B>,
+}
+
+/// Iterator over the values of a [`TreiberStack`] from a head loaded once.
+struct Values<'g, T> {
+    node: Shared<'g, Node<T>>,
+    guard: &'g Guard,
+}
+
+/// Bounded freelist of the nodes retired by a [`TreiberStack`], whose values have been moved out.
+///
+/// A node is put back only by the deferred function of its retirement, once no thread can hold a
+/// reference to it. So a node loaded as the head by a pinned thread cannot come back to the head
+/// before that thread unpins, which rules out ABA on `head`.
+struct NodeCache<T> {
     head: Atomic<Node<T>>,
+    len: AtomicUsize,
+    capacity: usize,
 }
 
 impl<T> From<T> for Node<T> {
@@ -30,6 +69,16 @@ impl<T> From<T> for Node<T> {
     }
 }
 
+impl<'g, T> Iterator for Values<'g, T> {
+    type Item = &'g T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node_ref = unsafe { self.node.as_ref() }?;
+        self.node = node_ref.next.load(Ordering::Acquire, self.guard);
+        Some(&*node_ref.data)
+    }
+}
+
 impl<T> Deref for Node<T> {
     type Target = ManuallyDrop<T>;
 
@@ -38,24 +87,498 @@ impl<T> Deref for Node<T> {
     }
 }
 
-impl<T> Default for TreiberStack<T> {
+impl<T> Default for TreiberStack<T, SpinBackoff> {
     fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> NodeCache<T> {
+    /// Puts back a node that no thread can access, or frees it if the cache is full.
+    fn put(&self, node: Owned<Node<T>>) {
+        if self.len.fetch_add(1, Ordering::Relaxed) >= self.capacity {
+            let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+            return;
+        }
+
+        // Pushing only writes to our own node, so nothing needs to be protected.
+        let guard = unsafe { unprotected() };
+        let node = node.into_shared(guard);
+        let node_ref = unsafe { node.deref() };
+        loop {
+            let head = self.head.load(Ordering::Relaxed, guard);
+            node_ref.next.store(head, Ordering::Relaxed);
+            if self
+                .head
+                .compare_exchange(head, node, Ordering::Release, Ordering::Relaxed, guard)
+                .is_ok()
+            {
+                return;
+            }
+        }
+    }
+
+    /// Takes a node out of the cache with a single CAS.
+    ///
+    /// Returns `None` if the cache is empty or the CAS failed, so that pushes stay bounded.
+    fn try_take(&self, guard: &Guard) -> Option<Owned<Node<T>>> {
+        let head = self.head.load(Ordering::Acquire, guard);
+        let head_ref = unsafe { head.as_ref() }?;
+        let next = head_ref.next.load(Ordering::Relaxed, guard);
+        self.head
+            .compare_exchange(head, next, Ordering::Relaxed, Ordering::Relaxed, guard)
+            .ok()?;
+        let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+        // Other takers may still load `next`, but never read the value.
+        Some(unsafe { head.into_owned() })
+    }
+}
+
+impl<T> Drop for NodeCache<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let guard = unprotected();
+            let mut node = self.head.load(Ordering::Relaxed, guard);
+            while !node.is_null() {
+                let owned = node.into_owned();
+                node = owned.next.load(Ordering::Relaxed, guard);
+            }
+        }
+    }
+}
+
+impl<T> TreiberStack<T, SpinBackoff> {
+    /// Creates a new stack that spins between the attempts of its retry loops.
+    pub fn new() -> Self {
+        Self::with_backoff()
+    }
+
+    /// Creates a new stack that keeps up to `capacity` retired nodes for reuse by later pushes.
+    ///
+    /// A node is reused only once the epoch has advanced past its retirement, so pushes still
+    /// allocate while the epoch is held back.
+    pub fn with_node_cache(capacity: usize) -> Self {
+        Self::with_cache(Some(capacity))
+    }
+}
+
+impl<T, B: Backoff> TreiberStack<T, B> {
+    /// Creates a new stack whose retry loops wait with `B`, such as
+    /// `TreiberStack::<T, YieldBackoff>::with_backoff()`.
+    pub fn with_backoff() -> Self {
+        Self::with_cache(None)
+    }
+
+    /// Creates a new stack that keeps up to `capacity` retired nodes, if any.
+    fn with_cache(capacity: Option<usize>) -> Self {
         TreiberStack {
-            head: Atomic::null(),
+            head: CachePadded::new(Atomic::null()),
+            cache: capacity.map(|capacity| {
+                Arc::new(NodeCache {
+                    head: Atomic::null(),
+                    len: AtomicUsize::new(0),
+                    capacity,
+                })
+            }),
+            borrowers: AtomicUsize::new(0),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a node holding `t`, reusing a cached one if there is any and the cache is not
+    /// contended.
+    fn new_node(&self, t: T, guard: &Guard) -> Owned<Node<T>> {
+        let cache = some_or!(&self.cache, return Owned::new(Node::from(t)));
+        match cache.try_take(guard) {
+            Some(mut node) => {
+                node.data = ManuallyDrop::new(t);
+                node
+            }
+            None => Owned::new(Node::from(t)),
+        }
+    }
+
+    /// Retires a node removed from the stack, whose value has been moved out.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Guard::defer_destroy`].
+    unsafe fn retire(&self, node: Shared<'_, Node<T>>, guard: &Guard) {
+        match &self.cache {
+            None => guard.defer_destroy(node),
+            Some(cache) => {
+                let cache = cache.clone();
+                guard.defer_unchecked(move || cache.put(node.into_owned()));
+            }
+        }
+    }
+
+    /// Calls `f` with the values from the top of the stack, borrowed from their nodes.
+    ///
+    /// The head is loaded once, like in [`TreiberStack::iter`], and pops wait for `f` to return
+    /// before handing out a value it may be reading, so any `T` can be borrowed.
+    fn with_values<R, F: FnOnce(Values<'_, T>) -> R>(&self, f: F) -> R {
+        struct Release<'a>(&'a AtomicUsize);
+
+        impl Drop for Release<'_> {
+            fn drop(&mut self) {
+                let _ = self.0.fetch_sub(1, Ordering::Release);
+            }
+        }
+
+        let _ = self.borrowers.fetch_add(1, Ordering::Relaxed);
+        let _release = Release(&self.borrowers);
+        // Pairs with the fence in `wait_for_borrowers`: either the pop sees our increment, or we
+        // load the head after its CAS, from which its nodes are unreachable.
+        fence(Ordering::SeqCst);
+        let guard = pin();
+        f(Values {
+            node: self.head.load(Ordering::Acquire, &guard),
+            guard: &guard,
+        })
+    }
+
+    /// Waits until no [`TreiberStack::with_values`] that may reach the nodes just removed from
+    /// the stack is running, so that their values can be handed out.
+    fn wait_for_borrowers(&self) {
+        fence(Ordering::SeqCst);
+        let mut backoff = B::default();
+        // Acquires the reads of the borrowers, which must finish before the value is dropped.
+        while self.borrowers.load(Ordering::Acquire) != 0 {
+            backoff.snooze();
+        }
+    }
+
+    /// Pushes a value with a single CAS, in a bounded number of steps.
+    ///
+    /// Returns `Err(t)` with the original value if the CAS failed, i.e. the stack was
+    /// [`Contended`].
+    pub fn try_push_once(&self, t: T) -> Result<(), T> {
+        let guard = pin();
+        self.try_push(self.new_node(t, &guard), &guard)
+            .map_err(|req| unsafe { ManuallyDrop::into_inner(ptr::read(&**req)) })
+    }
+
+    /// Pops a value with a single CAS, in a bounded number of steps.
+    ///
+    /// Returns `Ok(Some(v))` if `v` is popped, and `Ok(None)` if the stack is empty.
+    pub fn try_pop_once(&self) -> Result<Option<T>, Contended> {
+        self.try_pop(&pin()).map_err(|()| Contended)
+    }
+
+    /// Same as [`Stack::try_push`], but with a weak CAS that may fail spuriously.
+    ///
+    /// On LL/SC architectures, this avoids a nested retry loop inside the CAS.
+    pub fn try_push_weak(&self, req: Owned<Node<T>>, guard: &Guard) -> Result<(), Owned<Node<T>>> {
+        let head = self.head.load(Ordering::Relaxed, guard);
+        req.next.store(head, Ordering::Relaxed);
+        self.head
+            .compare_exchange_weak(head, req, Ordering::Release, Ordering::Relaxed, guard)
+            .map(|_| ())
+            .map_err(|e| e.new)
+    }
+
+    /// Same as [`Stack::try_pop`], but with a weak CAS that may fail spuriously.
+    pub fn try_pop_weak(&self, guard: &Guard) -> Result<Option<T>, ()> {
+        let head = self.head.load(Ordering::Acquire, guard);
+        let head_ref = some_or!(unsafe { head.as_ref() }, return Ok(None));
+        let next = head_ref.next.load(Ordering::Relaxed, guard);
+
+        self.head
+            .compare_exchange_weak(head, next, Ordering::Relaxed, Ordering::Relaxed, guard)
+            .map_err(|_| ())?;
+
+        self.wait_for_borrowers();
+        Ok(Some(unsafe {
+            let data = ptr::read(&(*head_ref).data);
+            self.retire(head, guard);
+            ManuallyDrop::into_inner(data)
+        }))
+    }
+
+    /// Pops the top node, keeping it until the returned [`PoppedRef`] decides to take its value
+    /// or push it back.
+    ///
+    /// Retries while the CAS fails, and returns `None` if the stack is empty.
+    pub fn try_pop_ref<'g>(&'g self, guard: &'g Guard) -> Option<PoppedRef<'g, T, B>> {
+        let mut backoff = B::default();
+        loop {
+            let head = self.head.load(Ordering::Acquire, guard);
+            let head_ref = unsafe { head.as_ref() }?;
+            let next = head_ref.next.load(Ordering::Relaxed, guard);
+            if self
+                .head
+                .compare_exchange(head, next, Ordering::Relaxed, Ordering::Relaxed, guard)
+                .is_ok()
+            {
+                return Some(PoppedRef {
+                    stack: self,
+                    node: head,
+                    guard,
+                });
+            }
+            backoff.snooze();
+        }
+    }
+
+    /// Moves the values into a vector, so that the top value is the first one.
+    ///
+    /// The nodes are freed right away, as no other thread can access them.
+    pub fn into_vec(mut self) -> Vec<T> {
+        let mut vec = Vec::new();
+        let head = mem::replace(&mut *self.head, Atomic::null());
+        unsafe {
+            let guard = unprotected();
+            let mut node = head.load(Ordering::Relaxed, guard);
+            while !node.is_null() {
+                let mut owned = node.into_owned();
+                node = owned.next.load(Ordering::Relaxed, guard);
+                vec.push(ManuallyDrop::take(&mut owned.data));
+            }
+        }
+        vec
+    }
+
+    /// Removes and drops every value.
+    ///
+    /// The nodes are freed right away, as no other thread can access them. If dropping a value
+    /// panics, the other values are still dropped and the first panic is resumed afterwards.
+    pub fn clear(&mut self) {
+        let mut first_panic = None;
+        unsafe {
+            let guard = unprotected();
+            let mut node = self.head.swap(Shared::null(), Ordering::Relaxed, guard);
+            while !node.is_null() {
+                let mut owned = node.into_owned();
+                node = owned.next.load(Ordering::Relaxed, guard);
+                let dropped =
+                    panic::catch_unwind(AssertUnwindSafe(|| ManuallyDrop::drop(&mut owned.data)));
+                if let Err(payload) = dropped {
+                    let _ = first_panic.get_or_insert(payload);
+                }
+            }
+        }
+        if let Some(payload) = first_panic {
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Removes and drops every value, which is equivalent to `drain_all` and discard.
+    ///
+    /// The values are detached with a single swap of the head. Returns the number of values
+    /// dropped.
+    pub fn clear_concurrent(&self) -> usize {
+        let guard = pin();
+        let mut head = self.head.swap(Shared::null(), Ordering::Acquire, &guard);
+        self.wait_for_borrowers();
+
+        let mut count = 0;
+        while let Some(head_ref) = unsafe { head.as_ref() } {
+            let next = head_ref.next.load(Ordering::Relaxed, &guard);
+            // Concurrent `try_pop`s may still be reading the detached nodes.
+            unsafe {
+                drop(ManuallyDrop::into_inner(ptr::read(&head_ref.data)));
+                self.retire(head, &guard);
+            }
+            count += 1;
+            head = next;
+        }
+        count
+    }
+
+    /// Moves about half of the values, from the top, to `into`.
+    ///
+    /// The values keep their order, so the top of this stack becomes the top of `into`. Returns
+    /// the number of values moved.
+    pub fn steal_half(&self, into: &Self) -> usize {
+        let values = self.pop_half();
+        let count = values.len();
+        into.push_chain(values, &pin());
+        count
+    }
+
+    /// Pops the top half of the values, rounded up, with a single CAS.
+    ///
+    /// Returns the popped values in LIFO order. The values are moved out of their nodes instead
+    /// of splicing the nodes into another stack: a node that returns to a stack it was removed
+    /// from would let a CAS that expects it as the head succeed on a different chain (ABA).
+    pub(crate) fn pop_half(&self) -> Vec<T> {
+        let guard = pin();
+        let mut backoff = B::default();
+        loop {
+            let head = self.head.load(Ordering::Acquire, &guard);
+            let mut nodes = Vec::new();
+            let mut node = head;
+            while let Some(node_ref) = unsafe { node.as_ref() } {
+                nodes.push(node);
+                node = node_ref.next.load(Ordering::Acquire, &guard);
+            }
+            if nodes.is_empty() {
+                return Vec::new();
+            }
+
+            // The chain below `head` does not change while `head` is in the stack, so the CAS
+            // detaches exactly the nodes walked.
+            nodes.truncate(nodes.len() - nodes.len() / 2);
+            let middle = unsafe { nodes.last().unwrap().deref() }
+                .next
+                .load(Ordering::Relaxed, &guard);
+            if self
+                .head
+                .compare_exchange(head, middle, Ordering::Relaxed, Ordering::Relaxed, &guard)
+                .is_ok()
+            {
+                self.wait_for_borrowers();
+                return nodes
+                    .into_iter()
+                    .map(|node| unsafe {
+                        let data = ptr::read(&node.deref().data);
+                        self.retire(node, &guard);
+                        ManuallyDrop::into_inner(data)
+                    })
+                    .collect();
+            }
+            backoff.snooze();
+        }
+    }
+
+    /// Pushes `values`, given in LIFO order, with a single CAS.
+    pub(crate) fn push_chain(&self, values: Vec<T>, guard: &Guard) {
+        let mut values = values.into_iter().rev();
+        let bottom = some_or!(values.next(), return);
+        let bottom = self.new_node(bottom, guard).into_shared(guard);
+        let top = values.fold(bottom, |next, t| {
+            let node = self.new_node(t, guard);
+            node.next.store(next, Ordering::Relaxed);
+            node.into_shared(guard)
+        });
+
+        let bottom_ref = unsafe { bottom.deref() };
+        let mut backoff = B::default();
+        loop {
+            let head = self.head.load(Ordering::Relaxed, guard);
+            bottom_ref.next.store(head, Ordering::Relaxed);
+            if self
+                .head
+                .compare_exchange(head, top, Ordering::Release, Ordering::Relaxed, guard)
+                .is_ok()
+            {
+                return;
+            }
+            backoff.snooze();
+        }
+    }
+
+    /// Moves every value of `other` on top of this stack, keeping the order of both.
+    ///
+    /// The values are detached from `other` with a single swap of its head and pushed onto this
+    /// stack with a single CAS. Like in [`TreiberStack::steal_half`], they move into new nodes:
+    /// splicing the nodes of `other` would rewrite the `next` of a node that a stalled pop on
+    /// `other` may still use. Values pushed concurrently to either stack may end up above or below
+    /// the moved ones, but none is lost. Returns the number of values moved.
+    pub fn append(&self, other: &Self) -> usize {
+        let values = other.drain_all();
+        let count = values.len();
+        self.push_chain(values, &pin());
+        count
+    }
+
+    /// Same as [`Stack::push`], but retries with [`TreiberStack::try_push_weak`].
+    pub fn push_weak(&self, t: T) {
+        let guard = pin();
+        let mut req = self.new_node(t, &guard);
+        loop {
+            match self.try_push_weak(req, &guard) {
+                Ok(_) => break,
+                Err(r) => req = r,
+            }
+        }
+    }
+
+    /// Same as [`Stack::pop`], but retries with [`TreiberStack::try_pop_weak`].
+    pub fn pop_weak(&self) -> Option<T> {
+        let guard = pin();
+        loop {
+            if let Ok(result) = self.try_pop_weak(&guard) {
+                return result;
+            }
         }
     }
 }
 
-impl<T> Stack<T> for TreiberStack<T> {
-    type PushReq = Node<T>;
+impl<T: Copy, B: Backoff> TreiberStack<T, B> {
+    /// Returns the top value of the stack without popping it.
+    ///
+    /// The result is only a snapshot: the value may be popped concurrently right after it is
+    /// read. `T: Copy` is required because a concurrent `pop` moves the value out of the node,
+    /// and its new owner could drop it while the returned reference is still alive.
+    pub fn peek<'g>(&self, guard: &'g Guard) -> Option<&'g T> {
+        let head = self.head.load(Ordering::Acquire, guard);
+        unsafe { head.as_ref() }.map(|head_ref| &*head_ref.data)
+    }
+
+    /// Iterates over the values from the top of the stack without popping them.
+    ///
+    /// The head is loaded once, when the iterator is created, and the values below it are reached
+    /// through their `next` pointers. The nodes stay alive while `guard` is pinned, but the
+    /// snapshot may include values popped concurrently afterwards and misses values pushed after
+    /// the head was loaded. `T: Copy` is required for the same reason as in
+    /// [`TreiberStack::peek`].
+    pub fn iter<'g>(&self, guard: &'g Guard) -> impl Iterator<Item = &'g T> + 'g
+    where
+        T: 'g,
+    {
+        Values {
+            node: self.head.load(Ordering::Acquire, guard),
+            guard,
+        }
+    }
+
+    /// Pops the top value of the stack if it satisfies `f`.
+    ///
+    /// Returns `None` if the stack is empty or `f` returns `false` for the top value, which then
+    /// stays in the stack. If the top changes after `f` is evaluated, `f` is evaluated again for
+    /// the new top. `T: Copy` is required for the same reason as in [`TreiberStack::peek`].
+    pub fn pop_if<F: Fn(&T) -> bool>(&self, f: F) -> Option<T> {
+        let guard = pin();
+        loop {
+            let head = self.head.load(Ordering::Acquire, &guard);
+            let head_ref = unsafe { head.as_ref() }?;
+            if !f(&head_ref.data) {
+                return None;
+            }
+            let next = head_ref.next.load(Ordering::Relaxed, &guard);
+
+            if self
+                .head
+                .compare_exchange(head, next, Ordering::Relaxed, Ordering::Relaxed, &guard)
+                .is_ok()
+            {
+                unsafe { self.retire(head, &guard) };
+                return Some(*head_ref.data);
+            }
+        }
+    }
+
+    /// Pops the top value of the stack if it equals `expected`.
+    ///
+    /// Returns `Err(())` if the stack is empty or its top value differs from `expected`. Values
+    /// are compared, not nodes: this pops some node whose value equals `expected`, which need not
+    /// be the node that was on top when the caller read it.
+    pub fn compare_and_pop(&self, expected: &T) -> Result<T, ()>
+    where
+        T: PartialEq,
+    {
+        self.pop_if(|t| t == expected).ok_or(())
+    }
+}
 
-    fn try_push(
-        &self,
-        req: Owned<Self::PushReq>,
-        guard: &Guard,
-    ) -> Result<(), Owned<Self::PushReq>> {
+impl<T, B: Backoff> Stack<T, B> for TreiberStack<T, B> {
+    fn try_push(&self, req: Owned<Node<T>>, guard: &Guard) -> Result<(), Owned<Node<T>>> {
+        // The old head is only stored into our node, never dereferenced, so it needs no acquire.
         let head = self.head.load(Ordering::Relaxed, guard);
         req.next.store(head, Ordering::Relaxed);
+        // Releases the value and `next` of our node to the pops that load it as the head.
         self.head
             .compare_exchange(head, req, Ordering::Release, Ordering::Relaxed, guard)
             .map(|_| ())
@@ -63,17 +586,22 @@ impl<T> Stack<T> for TreiberStack<T> {
     }
 
     fn try_pop(&self, guard: &Guard) -> Result<Option<T>, ()> {
+        // Acquires the value and `next` of the head from the push that published it.
         let head = self.head.load(Ordering::Acquire, guard);
         let head_ref = some_or!(unsafe { head.as_ref() }, return Ok(None));
         let next = head_ref.next.load(Ordering::Relaxed, guard);
 
+        // Relaxed suffices: every write to `head` is a read-modify-write, so the push of `next`
+        // heads a release sequence that includes this CAS, and a pop that loads `next` from it
+        // still synchronizes with that push.
         self.head
             .compare_exchange(head, next, Ordering::Relaxed, Ordering::Relaxed, guard)
             .map_err(|_| ())?;
 
+        self.wait_for_borrowers();
         Ok(Some(unsafe {
             let data = ptr::read(&(*head_ref).data);
-            guard.defer_destroy(head);
+            self.retire(head, guard);
             ManuallyDrop::into_inner(data)
         }))
     }
@@ -81,38 +609,1361 @@ impl<T> Stack<T> for TreiberStack<T> {
     fn is_empty(&self, guard: &Guard) -> bool {
         self.head.load(Ordering::Acquire, guard).is_null()
     }
+
+    fn push_with(&self, t: T, guard: &Guard) {
+        let mut req = self.new_node(t, guard);
+        let mut backoff = B::default();
+        loop {
+            match self.try_push(req, guard) {
+                Ok(_) => break,
+                Err(r) => req = r,
+            }
+            backoff.snooze();
+        }
+    }
+
+    fn push_iter<I: IntoIterator<Item = T>>(&self, iter: I) {
+        let mut iter = iter.into_iter();
+        let guard = pin();
+
+        // Builds the chain locally, so that it is spliced onto the stack with a single CAS.
+        let first = some_or!(iter.next(), return);
+        let last = self.new_node(first, &guard).into_shared(&guard);
+        let mut top = last;
+        for t in iter {
+            let node = self.new_node(t, &guard);
+            node.next.store(top, Ordering::Relaxed);
+            top = node.into_shared(&guard);
+        }
+
+        let last_ref = unsafe { last.deref() };
+        loop {
+            let head = self.head.load(Ordering::Relaxed, &guard);
+            last_ref.next.store(head, Ordering::Relaxed);
+            if self
+                .head
+                .compare_exchange(head, top, Ordering::Release, Ordering::Relaxed, &guard)
+                .is_ok()
+            {
+                return;
+            }
+        }
+    }
+
+    fn pop_many_into(&self, buf: &mut Vec<T>, n: usize) -> usize {
+        if n == 0 {
+            return 0;
+        }
+
+        let guard = pin();
+        let (mut head, count) = loop {
+            let head = self.head.load(Ordering::Acquire, &guard);
+
+            // Finds the node right below the `n` nodes to detach.
+            let mut cut = head;
+            let mut count = 0;
+            while count < n {
+                let cut_ref = some_or!(unsafe { cut.as_ref() }, break);
+                cut = cut_ref.next.load(Ordering::Relaxed, &guard);
+                count += 1;
+            }
+
+            // The `next` of a node never changes once pushed, and retired nodes are never reused
+            // while we are pinned, so the chain from `head` to `cut` is unchanged if the CAS
+            // succeeds.
+            if self
+                .head
+                .compare_exchange(head, cut, Ordering::Relaxed, Ordering::Relaxed, &guard)
+                .is_ok()
+            {
+                break (head, count);
+            }
+        };
+        self.wait_for_borrowers();
+
+        buf.reserve(count);
+        for _ in 0..count {
+            let head_ref = unsafe { head.deref() };
+            let next = head_ref.next.load(Ordering::Relaxed, &guard);
+            unsafe {
+                buf.push(ManuallyDrop::into_inner(ptr::read(&head_ref.data)));
+                self.retire(head, &guard);
+            }
+            head = next;
+        }
+        count
+    }
+
+    fn drain_all(&self) -> Vec<T> {
+        let guard = pin();
+        let mut head = self.head.swap(Shared::null(), Ordering::Acquire, &guard);
+        self.wait_for_borrowers();
+
+        let mut result = Vec::new();
+        while let Some(head_ref) = unsafe { head.as_ref() } {
+            let next = head_ref.next.load(Ordering::Relaxed, &guard);
+            // Concurrent `try_pop`s may still be reading the detached nodes.
+            unsafe {
+                result.push(ManuallyDrop::into_inner(ptr::read(&head_ref.data)));
+                self.retire(head, &guard);
+            }
+            head = next;
+        }
+        result
+    }
+
+    /// Walks the chain of nodes, which no other thread can access.
+    fn len_mut(&mut self) -> usize {
+        let guard = unsafe { unprotected() };
+        let mut node = self.head.load(Ordering::Relaxed, guard);
+        let mut len = 0;
+        while let Some(node_ref) = unsafe { node.as_ref() } {
+            node = node_ref.next.load(Ordering::Relaxed, guard);
+            len += 1;
+        }
+        len
+    }
+}
+
+impl<T, B: Backoff> Drop for TreiberStack<T, B> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// Maximum number of values printed by the `Debug` implementation.
+const DEBUG_SNAPSHOT_LEN: usize = 32;
+
+impl<T: Clone, B: Backoff> Clone for TreiberStack<T, B> {
+    /// Clones the values into a new chain of nodes, in the same order.
+    ///
+    /// The values are read like [`TreiberStack::iter`], so cloning a concurrently modified stack
+    /// copies the chain below the head loaded first. Concurrent pops wait for the clones to be
+    /// made. The new stack caches as many nodes as this one.
+    fn clone(&self) -> Self {
+        let stack = Self::with_cache(self.cache.as_ref().map(|cache| cache.capacity));
+        let values = self.with_values(|values| values.cloned().collect::<Vec<_>>());
+        stack.push_chain(values, &pin());
+        stack
+    }
+}
+
+impl<T: fmt::Debug, B: Backoff> fmt::Debug for TreiberStack<T, B> {
+    /// Prints the values from the top of the stack, eliding those after the first 32.
+    ///
+    /// The values are read under a single pin, so concurrent operations may make the output
+    /// stale. Concurrent pops wait for the output to be written.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct Ellipsis;
+
+        impl fmt::Debug for Ellipsis {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("…")
+            }
+        }
+
+        f.write_str("TreiberStack ")?;
+        self.with_values(|mut values| {
+            let mut list = f.debug_list();
+            list.entries(values.by_ref().take(DEBUG_SNAPSHOT_LEN));
+            if values.next().is_some() {
+                list.entry(&Ellipsis);
+            }
+            list.finish()
+        })
+    }
 }
 
-impl<T> Drop for TreiberStack<T> {
+#[cfg(feature = "serde")]
+impl<T: Serialize, B: Backoff> Serialize for TreiberStack<T, B> {
+    /// Serializes the values as a sequence from the top of the stack.
+    ///
+    /// The values are read like [`TreiberStack::iter`], so a concurrently modified stack gives the
+    /// chain below the head loaded first. Concurrent pops wait for the values to be serialized.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.with_values(|values| serializer.collect_seq(values))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Deserialize<'de>, B: Backoff> Deserialize<'de> for TreiberStack<T, B> {
+    /// Deserializes a sequence from the top of the stack, as written by `serialize`.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::deserialize(deserializer).map(|values| values.into_iter().rev().collect())
+    }
+}
+
+impl<T, B: Backoff> FromIterator<T> for TreiberStack<T, B> {
+    /// Creates a stack from the values of `iter`, so that the last value is on top.
+    ///
+    /// The pop order is the same as pushing the values one by one.
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut stack = Self::with_backoff();
+        stack.extend(iter);
+        stack
+    }
+}
+
+impl<T, B: Backoff> Extend<T> for TreiberStack<T, B> {
+    /// Pushes the values of `iter` in order without synchronization, as we have exclusive access.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for t in iter {
+            let next = mem::replace(&mut *self.head, Atomic::null());
+            *self.head = Atomic::from(Owned::new(Node {
+                data: ManuallyDrop::new(t),
+                next,
+            }));
+        }
+    }
+}
+
+impl<T> From<Vec<T>> for TreiberStack<T, SpinBackoff> {
+    /// Creates a stack from the values of `vec`, so that the last value is on top.
+    fn from(vec: Vec<T>) -> Self {
+        vec.into_iter().collect()
+    }
+}
+
+impl<T, B: Backoff> From<TreiberStack<T, B>> for Vec<T> {
+    /// Moves the values of `stack` into a vector, so that the top value is the last one.
+    ///
+    /// This is the inverse of `TreiberStack::from(vec)`.
+    fn from(stack: TreiberStack<T, B>) -> Self {
+        let mut vec = stack.into_iter().collect::<Vec<_>>();
+        vec.reverse();
+        vec
+    }
+}
+
+/// A node popped by [`TreiberStack::try_pop_ref`], which derefs to its value.
+///
+/// The node is not retired until the pop is resolved with [`PoppedRef::into_owned`] or
+/// [`PoppedRef::push_back`]. Dropping it completes the pop and drops the value.
+pub struct PoppedRef<'g, T, B: Backoff = SpinBackoff> {
+    stack: &'g TreiberStack<T, B>,
+    node: Shared<'g, Node<T>>,
+    guard: &'g Guard,
+}
+
+impl<T, B: Backoff> PoppedRef<'_, T, B> {
+    /// Completes the pop, moving the value out and retiring the node.
+    pub fn into_owned(self) -> T {
+        let this = ManuallyDrop::new(self);
+        unsafe { this.take() }
+    }
+
+    /// Pushes the value back onto the stack.
+    ///
+    /// The node is linked back if the head is still the node right below it, which restores the
+    /// stack as it was before the pop. Otherwise, the value is pushed in a new node: linking the
+    /// node on top of other nodes would let a pop that loaded it earlier succeed with its old
+    /// `next`.
+    pub fn push_back(self) {
+        let this = ManuallyDrop::new(self);
+        let next = unsafe { this.node.deref() }
+            .next
+            .load(Ordering::Relaxed, this.guard);
+        if this
+            .stack
+            .head
+            .compare_exchange(
+                next,
+                this.node,
+                Ordering::Release,
+                Ordering::Relaxed,
+                this.guard,
+            )
+            .is_ok()
+        {
+            return;
+        }
+        let t = unsafe { this.take() };
+        this.stack.push_with(t, this.guard);
+    }
+
+    /// Moves the value out and retires the node.
+    ///
+    /// # Safety
+    ///
+    /// Must be called at most once, after which the node must not be accessed.
+    unsafe fn take(&self) -> T {
+        self.stack.wait_for_borrowers();
+        let data = ptr::read(&self.node.deref().data);
+        self.stack.retire(self.node, self.guard);
+        ManuallyDrop::into_inner(data)
+    }
+}
+
+impl<T, B: Backoff> Deref for PoppedRef<'_, T, B> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // Concurrent operations may still read the node, but no one else writes to it.
+        unsafe { &self.node.deref().data }
+    }
+}
+
+impl<T, B: Backoff> Drop for PoppedRef<'_, T, B> {
     fn drop(&mut self) {
+        drop(unsafe { self.take() });
+    }
+}
+
+impl<T: fmt::Debug, B: Backoff> fmt::Debug for PoppedRef<'_, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PoppedRef").field(&**self).finish()
+    }
+}
+
+/// An iterator that pops values from an owned [`TreiberStack`].
+pub struct IntoIter<T, B: Backoff = SpinBackoff>(TreiberStack<T, B>);
+
+impl<T, B: Backoff> fmt::Debug for IntoIter<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IntoIter").finish_non_exhaustive()
+    }
+}
+
+impl<T, B: Backoff> Iterator for IntoIter<T, B> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Nothing can race with us as we own the stack, so the node is freed right away.
         unsafe {
             let guard = unprotected();
-            while let Ok(Some(_)) = self.try_pop(guard) {}
+            let head = self.0.head.load(Ordering::Relaxed, guard);
+            if head.is_null() {
+                return None;
+            }
+            let mut owned = head.into_owned();
+            self.0
+                .head
+                .store(owned.next.load(Ordering::Relaxed, guard), Ordering::Relaxed);
+            Some(ManuallyDrop::take(&mut owned.data))
         }
     }
 }
 
+impl<T, B: Backoff> IntoIterator for TreiberStack<T, B> {
+    type Item = T;
+    type IntoIter = IntoIter<T, B>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
     use super::*;
+    use crate::elim_stack::base::test::stress;
+    use core::cell::Cell;
     use crossbeam_utils::thread::scope;
+    use rand::{thread_rng, Rng};
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::thread;
 
-    #[test]
-    fn push() {
-        let stack = TreiberStack::default();
+    /// Value of the stacks whose node allocations are counted by [`NodeAllocs`].
+    type Counted = [u64; 3];
 
-        scope(|scope| {
-            for _ in 0..10 {
-                scope.spawn(|_| {
-                    for i in 0..10_000 {
-                        stack.push(i);
-                        assert!(stack.pop().is_some());
-                    }
-                });
-            }
-        })
+    thread_local! {
+        /// Number of allocations of `Node<Counted>` on the current thread.
+        static NODE_ALLOCS: Cell<usize> = Cell::new(0);
+    }
+
+    /// System allocator that counts the allocations of `Node<Counted>` per thread.
+    struct NodeAllocs;
+
+    unsafe impl GlobalAlloc for NodeAllocs {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            if layout == Layout::new::<Node<Counted>>() {
+                let _ = NODE_ALLOCS.try_with(|n| n.set(n.get() + 1));
+            }
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: NodeAllocs = NodeAllocs;
+
+    /// Returns the number of nodes `f` allocates on the current thread.
+    fn node_allocs<F: FnOnce()>(f: F) -> usize {
+        let before = NODE_ALLOCS.with(Cell::get);
+        f();
+        NODE_ALLOCS.with(Cell::get) - before
+    }
+
+    /// Increments the counter when dropped.
+    #[derive(Debug, Clone)]
+    pub(crate) struct DropCounter<'a>(pub(crate) &'a AtomicUsize);
+
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            let _ = self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Increments the counter when dropped, then panics if the flag is set.
+    #[derive(Debug)]
+    pub(crate) struct PanicOnDrop<'a>(pub(crate) &'a AtomicUsize, pub(crate) bool);
+
+    impl Drop for PanicOnDrop<'_> {
+        fn drop(&mut self) {
+            let _ = self.0.fetch_add(1, Ordering::Relaxed);
+            assert!(!self.1, "value panics on drop");
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn push() {
+        let stack = TreiberStack::default();
+
+        scope(|scope| {
+            for _ in 0..10 {
+                scope.spawn(|_| {
+                    for i in 0..10_000 {
+                        stack.push(i);
+                        assert!(stack.pop().is_some());
+                    }
+                });
+            }
+        })
         .unwrap();
 
         assert!(stack.pop().is_none());
     }
+
+    #[test]
+    fn is_empty() {
+        let stack = TreiberStack::default();
+        assert!(stack.is_empty(&pin()));
+        stack.push(1);
+        assert!(!stack.is_empty(&pin()));
+        assert_eq!(stack.pop(), Some(1));
+        assert!(stack.is_empty(&pin()));
+    }
+
+    #[test]
+    fn peek() {
+        let stack = TreiberStack::default();
+        assert!(stack.peek(&pin()).is_none());
+
+        stack.push(1);
+        stack.push(2);
+        assert_eq!(stack.peek(&pin()), Some(&2));
+        assert_eq!(stack.pop(), Some(2));
+        assert_eq!(stack.peek(&pin()), Some(&1));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn peek_concurrent() {
+        let stack = TreiberStack::default();
+
+        scope(|scope| {
+            for _ in 0..5 {
+                scope.spawn(|_| {
+                    for i in 0..10_000 {
+                        stack.push(i);
+                        assert!(stack.pop().is_some());
+                    }
+                });
+            }
+            for _ in 0..5 {
+                scope.spawn(|_| {
+                    for _ in 0..10_000 {
+                        let guard = pin();
+                        if let Some(v) = stack.peek(&guard) {
+                            assert!(*v < 10_000);
+                        }
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        assert!(stack.peek(&pin()).is_none());
+    }
+
+    #[test]
+    fn pop_if() {
+        let stack = TreiberStack::default();
+        assert_eq!(stack.pop_if(|_| true), None);
+
+        stack.push(1);
+        stack.push(2);
+        assert_eq!(stack.pop_if(|&t| t == 1), None);
+        assert_eq!(stack.pop_if(|&t| t == 2), Some(2));
+        assert_eq!(stack.pop_if(|&t| t == 1), Some(1));
+        assert!(stack.is_empty(&pin()));
+    }
+
+    #[test]
+    fn pop_if_race() {
+        let stack = TreiberStack::default();
+        stack.push(1);
+
+        // The first evaluation pushes a new top, so that the CAS fails and `f` is retried.
+        let calls = AtomicUsize::new(0);
+        let popped = stack.pop_if(|&t| {
+            if calls.fetch_add(1, Ordering::Relaxed) == 0 {
+                stack.push(2);
+            }
+            t > 0
+        });
+        assert_eq!(popped, Some(2));
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert_eq!(stack.drain_all(), vec![1]);
+    }
+
+    #[test]
+    fn compare_and_pop() {
+        let stack = TreiberStack::default();
+        assert_eq!(stack.compare_and_pop(&1), Err(()));
+
+        stack.push(1);
+        stack.push(2);
+        assert_eq!(stack.compare_and_pop(&1), Err(()));
+        assert_eq!(stack.compare_and_pop(&2), Ok(2));
+        assert_eq!(stack.compare_and_pop(&1), Ok(1));
+        assert_eq!(stack.compare_and_pop(&1), Err(()));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn compare_and_pop_concurrent() {
+        const THREADS: usize = 4;
+        const ELEMENTS: usize = 1_000;
+
+        let stack = TreiberStack::default();
+        stack.push_iter((0..ELEMENTS).map(|i| i % 2));
+
+        // Each thread pops the top only if it is a duplicate of the value it expects.
+        let popped = AtomicUsize::new(0);
+        scope(|scope| {
+            for expected in 0..THREADS {
+                let stack = &stack;
+                let popped = &popped;
+                let _ = scope.spawn(move |_| {
+                    while !stack.is_empty(&pin()) {
+                        match stack.compare_and_pop(&(expected % 2)) {
+                            Ok(t) => {
+                                assert_eq!(t, expected % 2);
+                                let _ = popped.fetch_add(1, Ordering::Relaxed);
+                            }
+                            Err(()) => thread::yield_now(),
+                        }
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        assert_eq!(popped.load(Ordering::Relaxed), ELEMENTS);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn push_pop_weak() {
+        let stack = TreiberStack::default();
+
+        scope(|scope| {
+            for _ in 0..10 {
+                let _ = scope.spawn(|_| {
+                    for i in 0..10_000 {
+                        stack.push_weak(i);
+                        assert!(stack.pop_weak().is_some());
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        assert!(stack.pop_weak().is_none());
+    }
+
+    #[test]
+    fn push_iter() {
+        let stack = TreiberStack::default();
+        stack.push_iter(None);
+        assert!(stack.pop().is_none());
+
+        stack.push_iter(Some(1));
+        assert_eq!(stack.drain_all(), vec![1]);
+
+        stack.push(0);
+        stack.push_iter(1..10);
+        assert_eq!(stack.drain_all(), (0..10).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn push_iter_concurrent() {
+        const THREADS: usize = 10;
+        const BATCHES: usize = 100;
+        const BATCH: usize = 100;
+
+        let stack = TreiberStack::default();
+
+        scope(|scope| {
+            for t in 0..THREADS {
+                let stack = &stack;
+                scope.spawn(move |_| {
+                    for b in 0..BATCHES {
+                        let base = (t * BATCHES + b) * (BATCH + 1);
+                        stack.push_iter(base..base + BATCH);
+                        stack.push(base + BATCH);
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        let mut drained = stack.drain_all();
+        drained.sort_unstable();
+        assert_eq!(
+            drained,
+            (0..THREADS * BATCHES * (BATCH + 1)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn pop_n() {
+        let stack = TreiberStack::default();
+        assert!(stack.pop_n(3).is_empty());
+
+        stack.push_iter(0..10);
+        assert!(stack.pop_n(0).is_empty());
+        assert_eq!(stack.pop_n(3), vec![9, 8, 7]);
+        assert_eq!(stack.pop_n(100), (0..7).rev().collect::<Vec<_>>());
+        assert!(stack.pop().is_none());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn pop_n_concurrent() {
+        const THREADS: usize = 10;
+        const ELEMENTS: usize = 10_000;
+
+        let stack = TreiberStack::default();
+
+        let mut popped = scope(|scope| {
+            let handles = (0..THREADS)
+                .map(|t| {
+                    let stack = &stack;
+                    scope.spawn(move |_| {
+                        let mut popped = Vec::new();
+                        for i in 0..ELEMENTS {
+                            stack.push(t * ELEMENTS + i);
+                            if i % 4 == 0 {
+                                popped.append(&mut stack.pop_n(t + 1));
+                            }
+                        }
+                        popped
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .unwrap();
+
+        popped.append(&mut stack.drain_all());
+        popped.sort_unstable();
+        assert_eq!(popped, (0..THREADS * ELEMENTS).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn drain_all() {
+        const THREADS: usize = 10;
+        const ELEMENTS: usize = 10_000;
+
+        let stack = TreiberStack::default();
+        let done = AtomicUsize::new(0);
+
+        let mut drained = scope(|scope| {
+            for t in 0..THREADS {
+                let stack = &stack;
+                let done = &done;
+                scope.spawn(move |_| {
+                    for i in 0..ELEMENTS {
+                        stack.push(t * ELEMENTS + i);
+                    }
+                    done.fetch_add(1, Ordering::Release);
+                });
+            }
+
+            let mut drained = Vec::new();
+            while done.load(Ordering::Acquire) < THREADS {
+                drained.append(&mut stack.drain_all());
+            }
+            drained
+        })
+        .unwrap();
+        drained.append(&mut stack.drain_all());
+
+        drained.sort_unstable();
+        assert_eq!(drained, (0..THREADS * ELEMENTS).collect::<Vec<_>>());
+        assert!(stack.pop().is_none());
+    }
+
+    #[test]
+    fn drain_all_lifo() {
+        let stack = TreiberStack::default();
+        for i in 0..10 {
+            stack.push(i);
+        }
+        assert_eq!(stack.drain_all(), (0..10).rev().collect::<Vec<_>>());
+        assert!(stack.drain_all().is_empty());
+    }
+
+    #[test]
+    fn into_iter() {
+        let stack = TreiberStack::default();
+        stack.push_iter(0..10);
+        assert_eq!(
+            stack.into_iter().collect::<Vec<_>>(),
+            (0..10).rev().collect::<Vec<_>>()
+        );
+
+        let stack = TreiberStack::<i32>::default();
+        assert!(stack.into_iter().next().is_none());
+    }
+
+    #[test]
+    fn from_iter() {
+        let stack = (0..0).collect::<TreiberStack<i32>>();
+        assert!(stack.pop().is_none());
+
+        let stack = (0..10).collect::<TreiberStack<_>>();
+        assert_eq!(stack.drain_all(), (0..10).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn extend() {
+        let mut stack = TreiberStack::default();
+        stack.push(0);
+        stack.extend(1..5);
+        stack.extend(None);
+        assert_eq!(stack.drain_all(), (0..5).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn debug() {
+        let stack = TreiberStack::default();
+        assert_eq!(format!("{:?}", stack), "TreiberStack []");
+        stack.push_iter(0..3);
+        assert_eq!(format!("{:?}", stack), "TreiberStack [2, 1, 0]");
+        stack.push_iter(3..100);
+        let debug = format!("{:?}", stack);
+        assert!(debug.starts_with("TreiberStack [99, 98, "));
+        assert!(debug.ends_with(", 68, …]"));
+
+        let stack = TreiberStack::default();
+        stack.push(String::from("a"));
+        assert_eq!(format!("{:?}", stack), r#"TreiberStack ["a"]"#);
+    }
+
+    #[test]
+    fn drop_payloads() {
+        let drops = AtomicUsize::new(0);
+
+        let stack = TreiberStack::default();
+        stack.push_iter((0..100).map(|_| DropCounter(&drops)));
+        for _ in 0..50 {
+            drop(stack.pop().unwrap());
+        }
+        stack.flush_reclamation();
+        assert_eq!(drops.load(Ordering::Relaxed), 50);
+
+        drop(stack);
+        assert_eq!(drops.load(Ordering::Relaxed), 100);
+    }
+
+    #[test]
+    fn from_vec() {
+        let stack = TreiberStack::from(vec![1, 2, 3]);
+        assert_eq!(stack.pop(), Some(3));
+        assert_eq!(Vec::from(stack), vec![1, 2]);
+
+        let stack = TreiberStack::<i32>::from(Vec::new());
+        assert!(stack.is_empty(&pin()));
+        assert_eq!(Vec::from(stack), Vec::<i32>::new());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn into_vec() {
+        assert_eq!(TreiberStack::<i32>::default().into_vec(), Vec::<i32>::new());
+
+        let stack = (0..100_000).collect::<TreiberStack<_>>();
+        assert_eq!(stack.into_vec(), (0..100_000).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn drop_panic() {
+        let drops = AtomicUsize::new(0);
+
+        // The 4th and 8th values from the top panic, and only the first panic propagates.
+        let stack = TreiberStack::default();
+        stack.push_iter((0..10).map(|i| PanicOnDrop(&drops, i == 6 || i == 2)));
+        let payload = panic::catch_unwind(AssertUnwindSafe(|| drop(stack))).unwrap_err();
+        assert_eq!(payload.downcast_ref(), Some(&"value panics on drop"));
+        assert_eq!(drops.load(Ordering::Relaxed), 10);
+
+        // The stack is still usable after `clear` panics.
+        let mut stack = TreiberStack::default();
+        stack.push(PanicOnDrop(&drops, true));
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| stack.clear())).is_err());
+        assert_eq!(drops.load(Ordering::Relaxed), 11);
+        assert!(stack.is_empty(&pin()));
+        stack.push(PanicOnDrop(&drops, false));
+        drop(stack);
+        assert_eq!(drops.load(Ordering::Relaxed), 12);
+    }
+
+    #[test]
+    fn into_vec_drop() {
+        let drops = AtomicUsize::new(0);
+
+        let stack = TreiberStack::default();
+        stack.push_iter((0..100).map(|_| DropCounter(&drops)));
+        let vec = stack.into_vec();
+        assert_eq!(vec.len(), 100);
+        assert_eq!(drops.load(Ordering::Relaxed), 0);
+
+        drop(vec);
+        assert_eq!(drops.load(Ordering::Relaxed), 100);
+    }
+
+    #[test]
+    fn iter() {
+        let stack = TreiberStack::default();
+        assert_eq!(stack.iter(&pin()).next(), None);
+        stack.push_iter(0..10);
+        assert_eq!(
+            stack.iter(&pin()).copied().collect::<Vec<_>>(),
+            (0..10).rev().collect::<Vec<_>>()
+        );
+        assert_eq!(stack.pop(), Some(9));
+    }
+
+    /// Run with `cargo +nightly miri test iter_concurrent_pop` to check for use-after-free.
+    #[test]
+    fn iter_concurrent_pop() {
+        const VALUES: usize = if cfg!(miri) { 100 } else { 10_000 };
+
+        let stack = TreiberStack::default();
+        stack.push_iter(0..VALUES);
+
+        scope(|scope| {
+            for _ in 0..2 {
+                let _ = scope.spawn(|_| while stack.pop().is_some() {});
+            }
+
+            // The snapshot is a suffix of the pushed values, even if they are being popped.
+            while !stack.is_empty(&pin()) {
+                let guard = pin();
+                let values = stack.iter(&guard).copied().collect::<Vec<_>>();
+                if let Some(&top) = values.first() {
+                    assert_eq!(values, (0..=top).rev().collect::<Vec<_>>());
+                }
+            }
+        })
+        .unwrap();
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn serde_round_trip() {
+        for &n in &[0, 1, 100_000] {
+            let stack = (0..n).collect::<TreiberStack<usize>>();
+            let json = serde_json::to_string(&stack).unwrap();
+            let stack = serde_json::from_str::<TreiberStack<usize>>(&json).unwrap();
+            assert_eq!(stack.into_vec(), (0..n).rev().collect::<Vec<_>>());
+        }
+
+        let stack = TreiberStack::from(vec![String::from("a"), String::from("b")]);
+        let json = serde_json::to_string(&stack).unwrap();
+        assert_eq!(json, r#"["b","a"]"#);
+        let stack = serde_json::from_str::<TreiberStack<String>>(&json).unwrap();
+        assert_eq!(stack.into_vec(), ["b", "a"]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn serde_concurrent() {
+        use core::sync::atomic::AtomicBool;
+
+        let stack = TreiberStack::default();
+        let done = AtomicBool::new(false);
+
+        scope(|scope| {
+            let _ = scope.spawn(|_| {
+                for i in 0..10_000 {
+                    stack.push(i);
+                    if i % 3 == 0 {
+                        let _ = stack.pop();
+                    }
+                }
+                done.store(true, Ordering::Relaxed);
+            });
+
+            // Every snapshot is a chain of values pushed in increasing order.
+            while !done.load(Ordering::Relaxed) {
+                let json = serde_json::to_string(&stack).unwrap();
+                let values = serde_json::from_str::<Vec<usize>>(&json).unwrap();
+                assert!(values.windows(2).all(|w| w[0] > w[1]));
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn clone() {
+        let stack = (0..100).collect::<TreiberStack<_>>();
+        let clone = stack.clone();
+
+        assert_eq!(stack.pop(), Some(99));
+        stack.push(100);
+        drop(stack);
+        assert_eq!(clone.into_vec(), (0..100).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn clone_drop() {
+        let drops = AtomicUsize::new(0);
+
+        let stack = (0..10)
+            .map(|_| DropCounter(&drops))
+            .collect::<TreiberStack<_>>();
+        let clone = stack.clone();
+        assert_eq!(drops.load(Ordering::Relaxed), 0);
+
+        // Each copy drops its own values.
+        drop(stack);
+        assert_eq!(drops.load(Ordering::Relaxed), 10);
+        drop(clone);
+        assert_eq!(drops.load(Ordering::Relaxed), 20);
+    }
+
+    #[test]
+    fn clone_concurrent_pop() {
+        const COUNT: usize = if cfg!(miri) { 20 } else { 10_000 };
+
+        let stack = (0..COUNT)
+            .map(|i| i.to_string())
+            .collect::<TreiberStack<_>>();
+
+        scope(|scope| {
+            // The popped values are dropped right away, while the clones may still read them.
+            let _ = scope.spawn(|_| while stack.pop().is_some() {});
+
+            loop {
+                let values = stack.clone().into_vec();
+                assert!(values
+                    .windows(2)
+                    .all(|w| w[0].parse::<usize>().unwrap() > w[1].parse::<usize>().unwrap()));
+                if values.is_empty() {
+                    break;
+                }
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn clone_concurrent() {
+        let stack = TreiberStack::default();
+
+        scope(|scope| {
+            let _ = scope.spawn(|_| {
+                for i in 0..10_000 {
+                    stack.push(i);
+                    if i % 3 == 0 {
+                        let _ = stack.pop();
+                    }
+                }
+            });
+
+            // Every clone is a chain of values pushed in increasing order.
+            for _ in 0..1_000 {
+                let values = stack.clone().into_vec();
+                assert!(values.windows(2).all(|w| w[0] > w[1]));
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn steal_half() {
+        let victim = (0..9).collect::<TreiberStack<_>>();
+        let thief = (100..102).collect::<TreiberStack<_>>();
+
+        assert_eq!(victim.steal_half(&thief), 5);
+        assert_eq!(victim.into_vec(), vec![3, 2, 1, 0]);
+        assert_eq!(thief.into_vec(), vec![8, 7, 6, 5, 4, 101, 100]);
+
+        let victim = TreiberStack::<i32>::default();
+        let thief = TreiberStack::default();
+        assert_eq!(victim.steal_half(&thief), 0);
+        assert!(thief.is_empty(&pin()));
+    }
+
+    #[test]
+    fn pop_many_into() {
+        let stack = (0..10).collect::<TreiberStack<_>>();
+        let mut buf = vec![100, 101];
+
+        assert_eq!(stack.pop_many_into(&mut buf, 3), 3);
+        assert_eq!(buf, vec![100, 101, 9, 8, 7]);
+        assert_eq!(stack.pop_many_into(&mut buf, 0), 0);
+
+        buf.clear();
+        let capacity = buf.capacity();
+        assert_eq!(stack.pop_many_into(&mut buf, 4), 4);
+        assert_eq!(buf, vec![6, 5, 4, 3]);
+        assert_eq!(buf.capacity(), capacity);
+
+        buf.clear();
+        assert_eq!(stack.pop_many_into(&mut buf, 100), 3);
+        assert_eq!(buf, vec![2, 1, 0]);
+        assert_eq!(stack.pop_many_into(&mut buf, 100), 0);
+        assert_eq!(buf, vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn clear() {
+        let drops = AtomicUsize::new(0);
+
+        let mut stack = TreiberStack::default();
+        stack.push_iter((0..10).map(|_| DropCounter(&drops)));
+        stack.clear();
+        assert_eq!(drops.load(Ordering::Relaxed), 10);
+        assert!(stack.is_empty(&pin()));
+
+        stack.push(DropCounter(&drops));
+        assert!(stack.pop().is_some());
+        assert_eq!(drops.load(Ordering::Relaxed), 11);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn clear_concurrent() {
+        let drops = AtomicUsize::new(0);
+        let cleared = AtomicUsize::new(0);
+
+        let stack = TreiberStack::default();
+        scope(|scope| {
+            for _ in 0..4 {
+                let _ = scope.spawn(|_| {
+                    for _ in 0..1_000 {
+                        stack.push(DropCounter(&drops));
+                        let _ = cleared.fetch_add(stack.clear_concurrent(), Ordering::Relaxed);
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        assert_eq!(cleared.load(Ordering::Relaxed), 4_000);
+        assert_eq!(drops.load(Ordering::Relaxed), 4_000);
+        assert_eq!(stack.clear_concurrent(), 0);
+    }
+
+    #[test]
+    fn into_iter_drop() {
+        let drops = AtomicUsize::new(0);
+
+        let stack = TreiberStack::default();
+        stack.push_iter((0..10).map(|_| DropCounter(&drops)));
+        let mut iter = stack.into_iter();
+        for _ in 0..3 {
+            drop(iter.next().unwrap());
+        }
+        assert_eq!(drops.load(Ordering::Relaxed), 3);
+
+        drop(iter);
+        assert_eq!(drops.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    fn node_cache() {
+        let stack = TreiberStack::<Counted>::with_node_cache(4);
+        let push_pop = || {
+            (0..8).for_each(|i| stack.push([i; 3]));
+            (0..8).for_each(|_| assert!(stack.pop().is_some()));
+        };
+        assert_eq!(node_allocs(push_pop), 8);
+
+        // The retired nodes are cached once the epoch advances, up to the capacity.
+        let cache = stack.cache.as_ref().unwrap();
+        while cache.len.load(Ordering::Relaxed) < 4 {
+            stack.flush_reclamation();
+            thread::yield_now();
+        }
+        assert_eq!(node_allocs(push_pop), 4);
+
+        // A bounded push takes a cached node too.
+        while cache.len.load(Ordering::Relaxed) == 0 {
+            stack.flush_reclamation();
+            thread::yield_now();
+        }
+        assert_eq!(node_allocs(|| stack.try_push_once([8; 3]).unwrap()), 0);
+
+        let stack = TreiberStack::<Counted>::default();
+        stack.push([0; 3]);
+        let _ = stack.pop();
+        stack.flush_reclamation();
+        assert_eq!(node_allocs(|| stack.push([1; 3])), 1);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn node_cache_stress() {
+        stress(&TreiberStack::with_node_cache(64), 8, 10_000);
+    }
+
+    #[test]
+    fn with_backoff() {
+        use core::sync::atomic::AtomicBool;
+
+        static SNOOZES: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Debug, Default)]
+        struct CountingBackoff;
+
+        impl Backoff for CountingBackoff {
+            fn snooze(&mut self) {
+                let _ = SNOOZES.fetch_add(1, Ordering::Relaxed);
+            }
+
+            fn reset(&mut self) {}
+        }
+
+        let stack = TreiberStack::<_, CountingBackoff>::with_backoff();
+        stack.push(1);
+        let borrowing = AtomicBool::new(false);
+
+        // The pop waits with the backoff for the borrower, which returns once it snoozed.
+        scope(|scope| {
+            let _ = scope.spawn(|_| {
+                stack.with_values(|_| {
+                    borrowing.store(true, Ordering::Relaxed);
+                    while SNOOZES.load(Ordering::Relaxed) == 0 {
+                        thread::yield_now();
+                    }
+                })
+            });
+            while !borrowing.load(Ordering::Relaxed) {
+                thread::yield_now();
+            }
+            assert_eq!(stack.pop(), Some(1));
+        })
+        .unwrap();
+        assert!(SNOOZES.load(Ordering::Relaxed) > 0);
+    }
+
+    #[test]
+    fn try_pop_ref() {
+        let drops = AtomicUsize::new(0);
+        let stack = TreiberStack::default();
+        stack.push_iter((0..3).map(|i| (i, DropCounter(&drops))));
+
+        let guard = pin();
+        let popped = stack.try_pop_ref(&guard).unwrap();
+        assert_eq!(popped.0, 2);
+        popped.push_back();
+        let popped = stack.try_pop_ref(&guard).unwrap();
+        assert_eq!(popped.0, 2);
+        assert_eq!(popped.into_owned().0, 2);
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+
+        // Pushed back on top of a value pushed in between.
+        let popped = stack.try_pop_ref(&guard).unwrap();
+        stack.push((3, DropCounter(&drops)));
+        popped.push_back();
+        drop(stack.try_pop_ref(&guard));
+        assert_eq!(drops.load(Ordering::Relaxed), 2);
+        let rest = stack.drain_all();
+        assert_eq!(rest.iter().map(|(i, _)| *i).collect::<Vec<_>>(), vec![3, 0]);
+        drop(rest);
+        assert_eq!(drops.load(Ordering::Relaxed), 4);
+        assert!(stack.try_pop_ref(&guard).is_none());
+    }
+
+    #[test]
+    fn try_pop_ref_relink() {
+        let stack = TreiberStack::<Counted>::default();
+        stack.push([0; 3]);
+        stack.push([1; 3]);
+
+        let guard = pin();
+        let popped = stack.try_pop_ref(&guard).unwrap();
+        assert_eq!(node_allocs(|| popped.push_back()), 0);
+
+        let popped = stack.try_pop_ref(&guard).unwrap();
+        stack.push([2; 3]);
+        assert_eq!(node_allocs(|| popped.push_back()), 1);
+        assert_eq!(stack.drain_all(), vec![[1; 3], [2; 3], [0; 3]]);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn try_pop_ref_concurrent() {
+        const THREADS: u64 = 4;
+        const OPS: u64 = 10_000;
+
+        let stack = TreiberStack::default();
+        let mut values = scope(|scope| {
+            let handles = (0..THREADS)
+                .map(|t| {
+                    let stack = &stack;
+                    scope.spawn(move |_| {
+                        let mut rng = thread_rng();
+                        let mut popped = Vec::new();
+                        for seq in 0..OPS {
+                            stack.push(t << 32 | seq);
+                            let guard = pin();
+                            let value = some_or!(stack.try_pop_ref(&guard), continue);
+                            if rng.gen() {
+                                value.push_back();
+                            } else {
+                                popped.push(value.into_owned());
+                            }
+                        }
+                        popped
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .unwrap();
+        values.extend(stack.drain_all());
+
+        values.sort_unstable();
+        let pushed = (0..THREADS)
+            .flat_map(|t| (0..OPS).map(move |seq| t << 32 | seq))
+            .collect::<Vec<_>>();
+        assert_eq!(values, pushed);
+    }
+
+    #[test]
+    fn append() {
+        let stack = (0..2).collect::<TreiberStack<_>>();
+        let other = (2..4).collect::<TreiberStack<_>>();
+        assert_eq!(stack.append(&other), 2);
+        assert!(other.is_empty(&pin()));
+        assert_eq!(stack.append(&other), 0);
+        assert_eq!(stack.drain_all(), vec![3, 2, 1, 0]);
+
+        assert_eq!(stack.append(&(0..3).collect()), 3);
+        assert_eq!(stack.drain_all(), vec![2, 1, 0]);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn append_concurrent() {
+        const THREADS: u64 = 4;
+        const OPS: u64 = 10_000;
+
+        let stack = TreiberStack::default();
+        let other = TreiberStack::default();
+        let done = AtomicUsize::new(0);
+        let (mut values, appended) = scope(|scope| {
+            // Pushes to both stacks and pops from `stack`, while `other` is appended to `stack`.
+            let handles = (0..THREADS)
+                .map(|t| {
+                    let (stack, other, done) = (&stack, &other, &done);
+                    scope.spawn(move |_| {
+                        let mut popped = Vec::new();
+                        for seq in 0..OPS {
+                            let target = if seq % 2 == 0 { stack } else { other };
+                            target.push(t << 32 | seq);
+                            if seq % 3 == 0 {
+                                popped.extend(stack.pop());
+                            }
+                        }
+                        let _ = done.fetch_add(1, Ordering::Release);
+                        popped
+                    })
+                })
+                .collect::<Vec<_>>();
+            let appender = scope.spawn(|_| {
+                let mut appended = 0;
+                while done.load(Ordering::Acquire) < THREADS as usize {
+                    appended += stack.append(&other);
+                }
+                appended
+            });
+            let values = handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect::<Vec<_>>();
+            (values, appender.join().unwrap())
+        })
+        .unwrap();
+        assert!(appended > 0);
+        values.extend(stack.drain_all());
+        values.extend(other.drain_all());
+
+        values.sort_unstable();
+        let pushed = (0..THREADS)
+            .flat_map(|t| (0..OPS).map(move |seq| t << 32 | seq))
+            .collect::<Vec<_>>();
+        assert_eq!(values, pushed);
+    }
+
+    #[test]
+    fn append_back_and_forth() {
+        const VALUES: usize = if cfg!(miri) { 64 } else { 10_000 };
+        const APPENDS: usize = if cfg!(miri) { 16 } else { 1_000 };
+
+        let a = (0..VALUES).collect::<TreiberStack<_>>();
+        let b = TreiberStack::default();
+        let mut popped = scope(|scope| {
+            // Moves the values between the stacks while they are popped from both.
+            for (from, into) in [(&a, &b), (&b, &a)] {
+                let _ = scope.spawn(move |_| {
+                    for _ in 0..APPENDS {
+                        let _ = into.append(from);
+                    }
+                });
+            }
+            let poppers = [&a, &b]
+                .iter()
+                .map(|&stack| {
+                    scope.spawn(move |_| {
+                        (0..VALUES / 4)
+                            .filter_map(|_| stack.pop())
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            poppers
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .unwrap();
+        popped.extend(a.drain_all());
+        popped.extend(b.drain_all());
+
+        popped.sort_unstable();
+        assert_eq!(popped, (0..VALUES).collect::<Vec<_>>());
+    }
 }
@@ -0,0 +1,366 @@
+//! Treiber's stack with hazard-pointer reclamation.
+//!
+//! Each thread owns a slot of a small fixed registry, holding its hazard pointer and its retired
+//! nodes. A popped node is freed by a scan of the registry once no hazard pointer names it, so a
+//! thread stalled in the middle of an operation holds back only the node it protects, while with
+//! epochs a pinned thread holds back every node retired after it pinned.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::ManuallyDrop;
+use core::ptr;
+use core::sync::atomic::{fence, AtomicBool, AtomicPtr, Ordering};
+use std::collections::HashSet;
+
+use crossbeam_epoch::{unprotected, Guard, Owned, Shared};
+use crossbeam_utils::CachePadded;
+
+use super::super::base::Stack;
+use super::Node;
+
+/// Maximum number of threads that can use hazard-pointer stacks at the same time.
+const MAX_THREADS: usize = 64;
+
+/// Number of retired nodes of a slot at which they are scanned against the hazard pointers.
+///
+/// At least twice the number of hazard pointers, so that a scan frees at least half of them.
+const SCAN_THRESHOLD: usize = 2 * MAX_THREADS;
+
+/// Node whose value has been moved out, waiting for no hazard pointer to name it.
+struct Retired {
+    ptr: *mut u8,
+    free: unsafe fn(*mut u8),
+}
+
+/// Hazard pointer and retired nodes of a thread.
+struct HazardSlot {
+    claimed: AtomicBool,
+    hazard: AtomicPtr<u8>,
+    /// Only accessed by the thread that claimed the slot. The nodes still protected when that
+    /// thread exits are passed on to the next thread claiming the slot.
+    retired: UnsafeCell<Vec<Retired>>,
+}
+
+unsafe impl Sync for HazardSlot {}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: CachePadded<HazardSlot> = CachePadded::new(HazardSlot {
+    claimed: AtomicBool::new(false),
+    hazard: AtomicPtr::new(ptr::null_mut()),
+    retired: UnsafeCell::new(Vec::new()),
+});
+
+/// Registry of the hazard slots, shared by every hazard-pointer stack.
+static SLOTS: [CachePadded<HazardSlot>; MAX_THREADS] = [EMPTY_SLOT; MAX_THREADS];
+
+/// Claim of a hazard slot by the current thread, which releases it when the thread exits.
+struct Local {
+    slot: &'static HazardSlot,
+}
+
+thread_local! {
+    static LOCAL: Local = Local::claim();
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Number of nodes freed by the current thread.
+    static FREED: core::cell::Cell<usize> = core::cell::Cell::new(0);
+}
+
+impl Local {
+    /// # Panics
+    ///
+    /// Panics if every slot is claimed by another thread.
+    fn claim() -> Self {
+        let slot = SLOTS
+            .iter()
+            .find(|slot| {
+                !slot.claimed.load(Ordering::Relaxed)
+                    && slot
+                        .claimed
+                        .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                        .is_ok()
+            })
+            .expect("too many threads use hazard-pointer stacks");
+        Self { slot }
+    }
+}
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        self.slot.clear();
+        unsafe { self.slot.scan() };
+        self.slot.claimed.store(false, Ordering::Release);
+    }
+}
+
+/// Runs `f` with the hazard slot of the current thread.
+fn with_slot<R, F: FnOnce(&HazardSlot) -> R>(f: F) -> R {
+    LOCAL.with(|local| f(local.slot))
+}
+
+impl HazardSlot {
+    /// Loads `src` and protects the loaded pointer from being freed until [`HazardSlot::clear`].
+    fn protect<T>(&self, src: &AtomicPtr<T>) -> *mut T {
+        let mut ptr = src.load(Ordering::Relaxed);
+        loop {
+            self.hazard.store(ptr as *mut u8, Ordering::Relaxed);
+            // Orders the store before the validation, against the fence of `scan`: either the
+            // scan sees the hazard, or the validation sees the pointer unlinked.
+            fence(Ordering::SeqCst);
+            let current = src.load(Ordering::Acquire);
+            if current == ptr {
+                return ptr;
+            }
+            ptr = current;
+        }
+    }
+
+    /// Stops protecting the pointer, once the accesses through it are done.
+    fn clear(&self) {
+        self.hazard.store(ptr::null_mut(), Ordering::Release);
+    }
+
+    /// Retires an unlinked node, to be freed with `free` once no hazard pointer names it.
+    ///
+    /// # Safety
+    ///
+    /// The current thread must own the slot, and `ptr` must not be reachable by new loads.
+    unsafe fn retire(&self, ptr: *mut u8, free: unsafe fn(*mut u8)) {
+        let retired = &mut *self.retired.get();
+        retired.push(Retired { ptr, free });
+        if retired.len() >= SCAN_THRESHOLD {
+            self.scan();
+        }
+    }
+
+    /// Frees the retired nodes that no hazard pointer names.
+    ///
+    /// # Safety
+    ///
+    /// The current thread must own the slot.
+    unsafe fn scan(&self) {
+        fence(Ordering::SeqCst);
+        let hazards = SLOTS
+            .iter()
+            .map(|slot| slot.hazard.load(Ordering::Acquire))
+            .filter(|hazard| !hazard.is_null())
+            .collect::<HashSet<_>>();
+        (*self.retired.get()).retain(|retired| {
+            if hazards.contains(&retired.ptr) {
+                return true;
+            }
+            (retired.free)(retired.ptr);
+            false
+        });
+    }
+}
+
+/// Frees a retired node of a stack of `T`.
+unsafe fn free<T>(ptr: *mut u8) {
+    drop(Box::from_raw(ptr as *mut Node<T>));
+    #[cfg(test)]
+    FREED.with(|freed| freed.set(freed.get() + 1));
+}
+
+/// Treiber's lock-free stack, freeing popped nodes with hazard pointers instead of epochs.
+///
+/// Implements [`Stack`] like the epoch-based [`super::TreiberStack`], and ignores the guards it is
+/// given. Up to 64 threads can use hazard-pointer stacks at the same time.
+pub struct TreiberStack<T> {
+    head: CachePadded<AtomicPtr<Node<T>>>,
+}
+
+unsafe impl<T: Send> Send for TreiberStack<T> {}
+unsafe impl<T: Send> Sync for TreiberStack<T> {}
+
+impl<T> Default for TreiberStack<T> {
+    fn default() -> Self {
+        Self {
+            head: CachePadded::new(AtomicPtr::new(ptr::null_mut())),
+        }
+    }
+}
+
+impl<T> fmt::Debug for TreiberStack<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TreiberStack")
+            .field("head", &self.head.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl<T> Stack<T> for TreiberStack<T> {
+    fn try_push(&self, req: Owned<Node<T>>, _guard: &Guard) -> Result<(), Owned<Node<T>>> {
+        let head = self.head.load(Ordering::Relaxed);
+        req.next
+            .store(Shared::from(head as *const Node<T>), Ordering::Relaxed);
+        let node = Box::into_raw(req.into_box());
+
+        self.head
+            .compare_exchange(head, node, Ordering::Release, Ordering::Relaxed)
+            .map(|_| ())
+            .map_err(|_| Owned::from(unsafe { Box::from_raw(node) }))
+    }
+
+    fn try_pop(&self, _guard: &Guard) -> Result<Option<T>, ()> {
+        with_slot(|slot| {
+            let head = slot.protect(&self.head);
+            let result = unsafe { head.as_ref() }
+                .map(|head_ref| {
+                    // The `next` of a pushed node never changes, so a plain load suffices.
+                    let next = head_ref
+                        .next
+                        .load(Ordering::Relaxed, unsafe { unprotected() })
+                        .as_raw();
+                    self.head
+                        .compare_exchange(
+                            head,
+                            next as *mut _,
+                            Ordering::Relaxed,
+                            Ordering::Relaxed,
+                        )
+                        .map(|_| unsafe { ptr::read(&head_ref.data) })
+                        .map_err(|_| ())
+                })
+                .transpose();
+            slot.clear();
+
+            if let Ok(Some(_)) = result {
+                unsafe { slot.retire(head as *mut u8, free::<T>) };
+            }
+            result.map(|data| data.map(ManuallyDrop::into_inner))
+        })
+    }
+
+    fn is_empty(&self, _guard: &Guard) -> bool {
+        self.head.load(Ordering::Acquire).is_null()
+    }
+
+    fn len_mut(&mut self) -> usize {
+        let mut node = *self.head.get_mut();
+        let mut len = 0;
+        while let Some(node_ref) = unsafe { node.as_ref() } {
+            node = node_ref
+                .next
+                .load(Ordering::Relaxed, unsafe { unprotected() })
+                .as_raw() as *mut _;
+            len += 1;
+        }
+        len
+    }
+}
+
+impl<T> Drop for TreiberStack<T> {
+    fn drop(&mut self) {
+        let mut node = *self.head.get_mut();
+        while !node.is_null() {
+            let mut owned = unsafe { Box::from_raw(node) };
+            node = owned
+                .next
+                .load(Ordering::Relaxed, unsafe { unprotected() })
+                .as_raw() as *mut _;
+            unsafe { ManuallyDrop::drop(&mut owned.data) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::elim_stack::base::test::stress;
+    use crate::elim_stack::treiber_stack::test::DropCounter;
+    use core::sync::atomic::AtomicUsize;
+    use crossbeam_utils::thread::scope;
+    use std::sync::Barrier;
+
+    /// Returns the number of nodes `f` frees on the current thread.
+    fn freed<F: FnOnce()>(f: F) -> usize {
+        let before = FREED.with(|freed| freed.get());
+        f();
+        FREED.with(|freed| freed.get()) - before
+    }
+
+    /// Returns `true` if `node` is retired by the current thread and not freed yet.
+    fn is_retired<T>(node: *mut Node<T>) -> bool {
+        with_slot(|slot| unsafe {
+            (*slot.retired.get())
+                .iter()
+                .any(|retired| retired.ptr == node as *mut u8)
+        })
+    }
+
+    #[test]
+    fn push_pop() {
+        let stack = TreiberStack::default();
+        assert!(stack.is_empty(&crossbeam_epoch::pin()));
+        stack.push(1);
+        stack.push(2);
+        assert!(!stack.is_empty(&crossbeam_epoch::pin()));
+        assert_eq!(stack.pop(), Some(2));
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn drop_values() {
+        let dropped = AtomicUsize::new(0);
+        let stack = TreiberStack::default();
+        for _ in 0..10 {
+            stack.push(DropCounter(&dropped));
+        }
+        drop(stack.pop_n(4));
+        assert_eq!(dropped.load(Ordering::Relaxed), 4);
+        drop(stack);
+        assert_eq!(dropped.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn stress_hp() {
+        stress(&TreiberStack::default(), 8, 10_000);
+    }
+
+    #[test]
+    fn parked_thread() {
+        const RETIRED: usize = 1000;
+
+        let stack = TreiberStack::default();
+        let barrier = Barrier::new(2);
+        stack.push(0);
+
+        let protected = scope(|scope| {
+            let _ = scope.spawn(|_| {
+                // Protects the head like a pop before its CAS, then stalls.
+                with_slot(|slot| {
+                    let _ = slot.protect(&stack.head);
+                    let _ = barrier.wait();
+                    let _ = barrier.wait();
+                    slot.clear();
+                });
+            });
+
+            let _ = barrier.wait();
+            let protected = stack.head.load(Ordering::Relaxed);
+            let freed = freed(|| {
+                assert_eq!(stack.pop(), Some(0));
+                for i in 0..RETIRED {
+                    stack.push(i);
+                    assert_eq!(stack.pop(), Some(i));
+                }
+                with_slot(|slot| unsafe { slot.scan() });
+            });
+            // Every node retired after the protected one is freed, but not the protected one.
+            assert!(freed >= RETIRED, "freed {} nodes", freed);
+            assert!(is_retired(protected));
+            let _ = barrier.wait();
+            protected
+        })
+        .unwrap();
+
+        // The protected node is freed once the stalled thread moves on.
+        with_slot(|slot| unsafe { slot.scan() });
+        assert!(!is_retired(protected));
+    }
+}
@@ -0,0 +1,462 @@
+//! Treiber's stack with a tagged head, recycling its nodes through a pool.
+//!
+//! Nodes are never freed while the stack lives: a popped node goes back to a free list of the
+//! pool and is reused by a later push. A stale pop may thus read the `next` of a node that has
+//! been popped and pushed again in the meantime, which is the ABA problem. Each list head packs a
+//! generation tag next to the index of its top node, and every successful CAS increments the tag,
+//! so the CAS of a stale pop fails even if the same node is back on top.
+//!
+//! Nodes are named by 32-bit indices into the pool, so that the index and a 32-bit tag fit in the
+//! single word of an `AtomicU64`. This needs a 64-bit CAS but no double-width CAS, and no pointer
+//! bits unused by the platform. The rest of the crate needs `AtomicU64` already, so the variant is
+//! built wherever the crate is. The tag wraps around after 2^32 operations on the same list, so a
+//! pop stalled for that long between its load and its CAS could still hit ABA.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::iter;
+use core::mem::{ManuallyDrop, MaybeUninit};
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicU32, AtomicU64, Ordering};
+use std::panic::{self, AssertUnwindSafe};
+
+use crossbeam_epoch::{Guard, Owned};
+use crossbeam_utils::CachePadded;
+
+use super::super::backoff::{Backoff, SpinBackoff};
+use super::super::base::Stack;
+use super::Node;
+
+/// Index of no node, ending a list.
+const NIL: u32 = u32::MAX;
+
+/// Number of nodes of the first chunk of a pool, where each chunk is twice as large as the last.
+const BASE_CHUNK: usize = 32;
+
+/// Number of chunks of a pool, enough for every index below [`NIL`].
+const CHUNKS: usize = 28;
+
+/// Node of a [`Pool`], in either the stack or the free list.
+struct PoolNode<T> {
+    next: AtomicU32,
+    /// Initialized only while the node is in the stack.
+    data: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// Head of a list, with the index of its top node and the generation of the head.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Tagged {
+    index: u32,
+    tag: u32,
+}
+
+impl Tagged {
+    fn pack(self) -> u64 {
+        u64::from(self.tag) << 32 | u64::from(self.index)
+    }
+
+    fn unpack(word: u64) -> Self {
+        Self {
+            index: word as u32,
+            tag: (word >> 32) as u32,
+        }
+    }
+
+    /// Returns the next generation of the head, whose top node is `index`.
+    fn with(self, index: u32) -> Self {
+        Self {
+            index,
+            tag: self.tag.wrapping_add(1),
+        }
+    }
+}
+
+/// Treiber's list of pool nodes with a tagged head, used for both the stack and the free list.
+struct TaggedList {
+    head: AtomicU64,
+}
+
+impl Default for TaggedList {
+    fn default() -> Self {
+        Self {
+            head: AtomicU64::new(Tagged { index: NIL, tag: 0 }.pack()),
+        }
+    }
+}
+
+impl TaggedList {
+    fn load(&self, order: Ordering) -> Tagged {
+        Tagged::unpack(self.head.load(order))
+    }
+
+    /// Tries to link the node `index` on top, releasing the writes to it.
+    fn try_push<T>(&self, pool: &Pool<T>, index: u32) -> bool {
+        let head = self.load(Ordering::Relaxed);
+        pool.node(index).next.store(head.index, Ordering::Relaxed);
+        self.head
+            .compare_exchange(
+                head.pack(),
+                head.with(index).pack(),
+                Ordering::Release,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    }
+
+    /// Returns the head and the `next` of its node, or `None` if the list is empty.
+    ///
+    /// The node may have been unlinked and reused since, in which case `next` is stale and
+    /// [`TaggedList::try_unlink`] fails.
+    fn peek<T>(&self, pool: &Pool<T>) -> Option<(Tagged, u32)> {
+        let head = self.load(Ordering::Acquire);
+        if head.index == NIL {
+            return None;
+        }
+        Some((head, pool.node(head.index).next.load(Ordering::Relaxed)))
+    }
+
+    /// Tries to unlink the top node of `head`, which succeeds only if the head has not changed.
+    ///
+    /// Relaxed like the pop of the epoch-based stack: every write to the head is a CAS, so the
+    /// next pop still synchronizes with the push of `next`.
+    fn try_unlink(&self, head: Tagged, next: u32) -> bool {
+        self.head
+            .compare_exchange(
+                head.pack(),
+                head.with(next).pack(),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    }
+}
+
+/// Nodes of a stack, in chunks that are allocated on demand and freed with the pool.
+struct Pool<T> {
+    chunks: [AtomicPtr<PoolNode<T>>; CHUNKS],
+    /// Number of indices handed out so far.
+    allocated: AtomicU32,
+    free: TaggedList,
+}
+
+impl<T> Default for Pool<T> {
+    fn default() -> Self {
+        Self {
+            chunks: Default::default(),
+            allocated: AtomicU32::new(0),
+            free: TaggedList::default(),
+        }
+    }
+}
+
+/// Returns the chunk of the node `index` and its offset in the chunk.
+fn locate(index: u32) -> (usize, usize) {
+    let chunk = (usize::BITS - 1 - (index as usize / BASE_CHUNK + 1).leading_zeros()) as usize;
+    (chunk, index as usize - BASE_CHUNK * ((1 << chunk) - 1))
+}
+
+impl<T> Pool<T> {
+    /// Returns the node `index`, which must have been allocated.
+    fn node(&self, index: u32) -> &PoolNode<T> {
+        let (chunk, offset) = locate(index);
+        unsafe { &*self.chunks[chunk].load(Ordering::Acquire).add(offset) }
+    }
+
+    /// Takes a node out of the free list, or allocates a new one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool already has `u32::MAX` nodes.
+    fn alloc(&self) -> u32 {
+        while let Some((head, next)) = self.free.peek(self) {
+            if self.free.try_unlink(head, next) {
+                return head.index;
+            }
+        }
+
+        let index = self
+            .allocated
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                if n < NIL {
+                    Some(n + 1)
+                } else {
+                    None
+                }
+            })
+            .expect("tagged stack has too many nodes");
+        let (chunk, _) = locate(index);
+        if self.chunks[chunk].load(Ordering::Acquire).is_null() {
+            let new = iter::repeat_with(|| PoolNode {
+                next: AtomicU32::new(NIL),
+                data: UnsafeCell::new(MaybeUninit::uninit()),
+            })
+            .take(BASE_CHUNK << chunk)
+            .collect::<Box<[PoolNode<T>]>>();
+            let new = Box::into_raw(new) as *mut PoolNode<T>;
+            if self.chunks[chunk]
+                .compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire)
+                .is_err()
+            {
+                // Another thread allocated the chunk first.
+                unsafe { drop(Box::from_raw(chunk_slice(new, chunk))) };
+            }
+        }
+        index
+    }
+
+    /// Puts the node `index`, whose value has been moved out, back into the free list.
+    fn release(&self, index: u32) {
+        let mut backoff = SpinBackoff;
+        while !self.free.try_push(self, index) {
+            backoff.snooze();
+        }
+    }
+}
+
+/// Returns the chunk `chunk` starting at `ptr` as a slice.
+fn chunk_slice<T>(ptr: *mut PoolNode<T>, chunk: usize) -> *mut [PoolNode<T>] {
+    ptr::slice_from_raw_parts_mut(ptr, BASE_CHUNK << chunk)
+}
+
+impl<T> Drop for Pool<T> {
+    fn drop(&mut self) {
+        for (i, chunk) in self.chunks.iter_mut().enumerate() {
+            let ptr = *chunk.get_mut();
+            if !ptr.is_null() {
+                unsafe { drop(Box::from_raw(chunk_slice(ptr, i))) };
+            }
+        }
+    }
+}
+
+/// Treiber's lock-free stack whose head is tagged against ABA, and whose nodes are recycled
+/// instead of reclaimed.
+///
+/// Implements [`Stack`] like the epoch-based [`super::TreiberStack`], and ignores the guards it is
+/// given. The memory of the nodes is kept until the stack is dropped, so it is as large as the
+/// largest number of values the stack ever held.
+pub struct TreiberStack<T> {
+    head: CachePadded<TaggedList>,
+    pool: Pool<T>,
+}
+
+unsafe impl<T: Send> Send for TreiberStack<T> {}
+unsafe impl<T: Send> Sync for TreiberStack<T> {}
+
+impl<T> Default for TreiberStack<T> {
+    fn default() -> Self {
+        Self {
+            head: CachePadded::new(TaggedList::default()),
+            pool: Pool::default(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for TreiberStack<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TreiberStack")
+            .field("head", &self.head.load(Ordering::Relaxed))
+            .field("nodes", &self.pool.allocated.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl<T> TreiberStack<T> {
+    /// Moves `t` into a node of the pool, not linked yet.
+    fn new_node(&self, t: T) -> u32 {
+        let index = self.pool.alloc();
+        unsafe { *self.pool.node(index).data.get() = MaybeUninit::new(t) };
+        index
+    }
+
+    /// Moves the value out of the node `index`, not linked, and gives the node back to the pool.
+    fn take_node(&self, index: u32) -> T {
+        let t = unsafe { ptr::read(self.pool.node(index).data.get()).assume_init() };
+        self.pool.release(index);
+        t
+    }
+}
+
+impl<T> Stack<T> for TreiberStack<T> {
+    fn try_push(&self, req: Owned<Node<T>>, _guard: &Guard) -> Result<(), Owned<Node<T>>> {
+        let index = self.new_node(unsafe { ManuallyDrop::into_inner(ptr::read(&**req)) });
+        if self.head.try_push(&self.pool, index) {
+            // The value has been moved into the pool, and the request does not drop it.
+            drop(req.into_box());
+            Ok(())
+        } else {
+            // The copy in the pool is forgotten, and the request keeps the value. Callers that
+            // retry should use `push_with` or `try_push_bounded`, which keep the node.
+            self.pool.release(index);
+            Err(req)
+        }
+    }
+
+    fn try_pop(&self, _guard: &Guard) -> Result<Option<T>, ()> {
+        let (head, next) = some_or!(self.head.peek(&self.pool), return Ok(None));
+        if !self.head.try_unlink(head, next) {
+            return Err(());
+        }
+        Ok(Some(self.take_node(head.index)))
+    }
+
+    fn is_empty(&self, _guard: &Guard) -> bool {
+        self.head.load(Ordering::Acquire).index == NIL
+    }
+
+    fn push_with(&self, t: T, _guard: &Guard) {
+        // Takes the node once for all attempts, unlike a loop of `try_push`.
+        let index = self.new_node(t);
+        let mut backoff = SpinBackoff;
+        while !self.head.try_push(&self.pool, index) {
+            backoff.snooze();
+        }
+    }
+
+    fn try_push_bounded(&self, t: T, max_attempts: usize) -> Result<(), T> {
+        let index = self.new_node(t);
+        let mut backoff = SpinBackoff;
+        for _ in 0..max_attempts {
+            if self.head.try_push(&self.pool, index) {
+                return Ok(());
+            }
+            backoff.snooze();
+        }
+        Err(self.take_node(index))
+    }
+
+    fn len_mut(&mut self) -> usize {
+        let mut index = self.head.load(Ordering::Relaxed).index;
+        let mut len = 0;
+        while index != NIL {
+            index = self.pool.node(index).next.load(Ordering::Relaxed);
+            len += 1;
+        }
+        len
+    }
+}
+
+impl<T> Drop for TreiberStack<T> {
+    fn drop(&mut self) {
+        // Like `super::TreiberStack::clear`, if dropping a value panics, the other values are
+        // still dropped and the first panic is resumed afterwards.
+        let mut first_panic = None;
+        let mut index = self.head.load(Ordering::Relaxed).index;
+        while index != NIL {
+            let node = self.pool.node(index);
+            index = node.next.load(Ordering::Relaxed);
+            let dropped = panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+                ptr::drop_in_place((*node.data.get()).as_mut_ptr())
+            }));
+            if let Err(payload) = dropped {
+                let _ = first_panic.get_or_insert(payload);
+            }
+        }
+        if let Some(payload) = first_panic {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::elim_stack::treiber_stack::test::{DropCounter, PanicOnDrop};
+    use core::sync::atomic::AtomicUsize;
+    use crossbeam_epoch::pin;
+
+    #[test]
+    fn locate_chunks() {
+        assert_eq!(locate(0), (0, 0));
+        assert_eq!(locate(31), (0, 31));
+        assert_eq!(locate(32), (1, 0));
+        assert_eq!(locate(95), (1, 63));
+        assert_eq!(locate(96), (2, 0));
+        assert_eq!(locate(NIL - 1).0, CHUNKS - 1);
+    }
+
+    #[test]
+    fn push_pop() {
+        let stack = TreiberStack::default();
+        assert!(stack.is_empty(&pin()));
+        stack.push(1);
+        stack.push(2);
+        assert!(!stack.is_empty(&pin()));
+        assert_eq!(stack.pop(), Some(2));
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn reuse_nodes() {
+        let stack = TreiberStack::default();
+        for i in 0..1000 {
+            stack.push_iter(0..10);
+            assert_eq!(stack.pop_n(10).len(), 10, "round {}", i);
+        }
+        assert_eq!(stack.pool.allocated.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    fn drop_values() {
+        let dropped = AtomicUsize::new(0);
+        let stack = TreiberStack::default();
+        for _ in 0..100 {
+            stack.push(DropCounter(&dropped));
+        }
+        drop(stack.pop_n(40));
+        assert_eq!(dropped.load(Ordering::Relaxed), 40);
+        drop(stack);
+        assert_eq!(dropped.load(Ordering::Relaxed), 100);
+    }
+
+    #[test]
+    fn drop_panic() {
+        let drops = AtomicUsize::new(0);
+
+        // The 4th and 8th values from the top panic, and only the first panic propagates.
+        let stack = TreiberStack::default();
+        stack.push_iter((0..10).map(|i| PanicOnDrop(&drops, i == 6 || i == 2)));
+        let payload = panic::catch_unwind(AssertUnwindSafe(|| drop(stack))).unwrap_err();
+        assert_eq!(payload.downcast_ref(), Some(&"value panics on drop"));
+        assert_eq!(drops.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    fn try_push_bounded_reuses_node() {
+        let stack = TreiberStack::default();
+        assert_eq!(stack.try_push_bounded(1, 0), Err(1));
+        assert_eq!(stack.try_push_bounded(2, 1), Ok(()));
+        assert_eq!(stack.pop(), Some(2));
+        assert_eq!(stack.pool.allocated.load(Ordering::Relaxed), 1);
+    }
+
+    /// The pop-pop-push interleaving, with the steps of the stalled pop run by hand.
+    #[test]
+    fn aba() {
+        let stack = TreiberStack::default();
+        stack.push(1);
+        stack.push(2);
+
+        // A pop loads the head `a` and its next `b`, then stalls before its CAS.
+        let (head, next) = stack.head.peek(&stack.pool).unwrap();
+
+        // Meanwhile, two pops free `a` and `b`, then two pushes reuse `b` and `a` in turn.
+        assert_eq!(stack.pop(), Some(2));
+        assert_eq!(stack.pop(), Some(1));
+        stack.push(3);
+        stack.push(4);
+        let current = stack.head.load(Ordering::Relaxed);
+        assert_eq!(current.index, head.index);
+        assert_eq!(
+            stack.pool.node(current.index).next.load(Ordering::Relaxed),
+            next
+        );
+
+        // Comparing the indices alone, the stalled CAS would succeed and lose 4. The tag has
+        // moved on, so it fails.
+        assert_ne!(current.tag, head.tag);
+        assert!(!stack.head.try_unlink(head, next));
+        assert_eq!(stack.drain_all(), vec![4, 3]);
+    }
+}
@@ -21,10 +21,20 @@ mod map;
 pub use arc::Arc;
 pub use art::{Art, Entry};
 pub use bst::Bst;
-pub use elim_stack::ElimStack;
+#[cfg(feature = "trace")]
+pub use elim_stack::CompletionPath;
+#[cfg(feature = "hazard-stack")]
+pub use elim_stack::HpTreiberStack;
+pub use elim_stack::{
+    Backoff, BoundedTreiberStack, Contended, CountedStack, ElimStack, ElimStackWithBackoff,
+    ElimStats, Exchanger, ExponentialBackoff, LockStack, SpinBackoff, SpinLockStack, Stack,
+    StripedStack, TaggedTreiberStack, TreiberStack, YieldBackoff,
+};
 pub use hash_table::{GrowableArray, SplitOrderedList};
 pub use linked_list::LinkedList;
-pub use list_set::OrderedListSet;
+pub use list_set::{
+    HarrisListSet, LazyListSet, LockSet, OptimisticListSet, OrderedListMap, OrderedListSet,
+};
 pub use map::{
     ConcurrentMap, NonblockingConcurrentMap, NonblockingMap, RandGen, SequentialMap, StrStringMap,
 };
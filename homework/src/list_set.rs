@@ -1,7 +1,29 @@
 #![allow(clippy::mutex_atomic)]
+use std::borrow::Borrow;
 use std::cmp;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::Deref;
 use std::ptr;
-use std::sync::{Mutex, MutexGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError, TryLockError};
+
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+mod harris;
+mod lazy;
+mod lock_set;
+mod map;
+mod optimistic;
+
+pub use harris::HarrisListSet;
+pub use lazy::LazyListSet;
+pub use lock_set::LockSet;
+pub use map::OrderedListMap;
+pub use optimistic::OptimisticListSet;
 
 #[derive(Debug)]
 struct Node<T> {
@@ -13,17 +35,46 @@ unsafe impl<T: Send> Send for Node<T> {}
 unsafe impl<T: Sync> Sync for Node<T> {}
 
 /// Concurrent sorted singly linked list using lock-coupling.
-#[derive(Debug)]
+///
+/// If a comparison panics, the operation unwinds without changing the list: every operation
+/// finishes comparing before it allocates, links or unlinks a node. So the list stays sorted and
+/// linked, and nothing leaks. The set remains usable afterwards, see `lock`. Operations on a
+/// batch keep the elements they inserted or removed before the panic, and drop the rest.
 pub struct OrderedListSet<T> {
     head: Mutex<*mut Node<T>>,
+    /// Number of elements, adjusted under the lock of the link that an insert or remove changes.
+    len: AtomicUsize,
 }
 
 unsafe impl<T: Send> Send for OrderedListSet<T> {}
 unsafe impl<T: Sync> Sync for OrderedListSet<T> {}
 
+/// Number of elements that `Debug` prints before an ellipsis.
+const DEBUG_LIMIT: usize = 32;
+
 // reference to the `next` field of previous node which points to the current node
+#[derive(Debug)]
 struct Cursor<'l, T>(MutexGuard<'l, *mut Node<T>>);
 
+/// Locks the mutex, ignoring poisoning.
+///
+/// A mutex is poisoned when a thread panics while holding it, e.g. in a comparison of `find` or
+/// the closure of `retain`. The protected pointers are only written by single stores once an
+/// operation can no longer fail, so the list is still sorted and linked. The mutexes of the other
+/// lists protect `()`, so there is nothing a panic can leave inconsistent.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Locks the mutex if no other thread holds it, ignoring poisoning like `lock`.
+fn try_lock<T>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+    match mutex.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
 impl<T> Node<T> {
     fn new(data: T, next: *mut Self) -> *mut Self {
         Box::into_raw(Box::new(Self {
@@ -36,8 +87,62 @@ impl<T> Node<T> {
 impl<'l, T: Ord> Cursor<'l, T> {
     /// Move the cursor to the position of key in the sorted list. If the key is found in the list,
     /// return `true`.
-    fn find(&mut self, key: &T) -> bool {
-        todo!()
+    ///
+    /// The key may be any borrowed form of the elements, whose order must agree with theirs.
+    fn find<Q>(&mut self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        loop {
+            let node = some_or!(unsafe { self.0.as_ref() }, return false);
+            match node.data.borrow().cmp(key) {
+                // Locks the next field before the current one is released.
+                cmp::Ordering::Less => self.0 = lock(&node.next),
+                cmp::Ordering::Equal => return true,
+                cmp::Ordering::Greater => return false,
+            }
+        }
+    }
+}
+
+impl<'l, T: Ord> Cursor<'l, T> {
+    /// Like `find`, but returns `None` as soon as a lock is held by another thread.
+    fn try_find<Q>(&mut self, key: &Q) -> Option<bool>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        loop {
+            let node = some_or!(unsafe { self.0.as_ref() }, return Some(false));
+            match node.data.borrow().cmp(key) {
+                cmp::Ordering::Less => self.0 = try_lock(&node.next)?,
+                cmp::Ordering::Equal => return Some(true),
+                cmp::Ordering::Greater => return Some(false),
+            }
+        }
+    }
+}
+
+impl<'l, T> Cursor<'l, T> {
+    /// Like `unlink`, but returns `None` without unlinking if the lock of the `next` of the node
+    /// is held by another thread.
+    fn try_unlink(&mut self) -> Option<T> {
+        let node = *self.0;
+        let next = *try_lock(&unsafe { &*node }.next)?;
+        *self.0 = next;
+        Some(unsafe { Box::from_raw(node) }.data)
+    }
+
+    /// Unlinks the node the cursor points to, which must not be null, and returns its element.
+    fn unlink(&mut self) -> T {
+        let node = *self.0;
+        // Reads the next pointer before the node is reclaimed. Taking the lock also waits for a
+        // thread that has moved past the node to release it; no other thread can reach the node,
+        // since we hold the lock of the link to it.
+        let next = *lock(&unsafe { &*node }.next);
+        *self.0 = next;
+        unsafe { Box::from_raw(node) }.data
     }
 }
 
@@ -46,52 +151,795 @@ impl<T> OrderedListSet<T> {
     pub fn new() -> Self {
         Self {
             head: Mutex::new(ptr::null_mut()),
+            len: AtomicUsize::new(0),
         }
     }
+
+    /// Creates a set from strictly increasing elements.
+    ///
+    /// Builds the chain from the back without locking, since no other thread can access the new
+    /// set.
+    fn from_sorted(elems: Vec<T>) -> Self {
+        let len = elems.len();
+        let mut head = ptr::null_mut();
+        for data in elems.into_iter().rev() {
+            head = Node::new(data, head);
+        }
+        Self {
+            head: Mutex::new(head),
+            len: AtomicUsize::new(len),
+        }
+    }
+
+    /// Returns the number of elements in the set.
+    ///
+    /// Reads a counter kept by `insert` and `remove`, without locking. Under concurrent
+    /// modifications, the value is a snapshot that may be stale by the time it is returned. See
+    /// `approx_len` for how far off it may be.
+    pub fn len(&self) -> usize {
+        self.approx_len()
+    }
+
+    /// Returns the number of elements in the set in `O(1)`, which is exact when no operation is
+    /// in progress.
+    ///
+    /// Every operation that links or unlinks a node adjusts the counter right after, before it
+    /// releases the lock of the link it changed, and the operations that find the key present or
+    /// absent leave it alone. So at any time the counter is off from the number of linked
+    /// elements by at most the number of modifications in progress, and it never underflows. The
+    /// exception is `clear`, which counts down while it frees the detached chain. Unlike
+    /// `len_exact`, it takes no lock.
+    pub fn approx_len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if the set has no element.
+    ///
+    /// Only takes the lock of the head, which comes first in the lock order of every operation,
+    /// so it never deadlocks with them.
+    pub fn is_empty(&self) -> bool {
+        lock(&self.head).is_null()
+    }
+
+    /// Removes the smallest element and returns it, or `None` if the set is empty.
+    ///
+    /// Linearizes when the head is locked, so an insert of a smaller key that locks the head
+    /// first is the one returned.
+    pub fn pop_min(&self) -> Option<T> {
+        let mut cursor = Cursor(lock(&self.head));
+        if cursor.0.is_null() {
+            return None;
+        }
+        let data = cursor.unlink();
+        let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+        Some(data)
+    }
+
+    /// Removes the largest element and returns it, or `None` if the set is empty.
+    ///
+    /// Traverses with lock-coupling to the last node, keeping the lock of the link to a node
+    /// while checking whether the node is the last. It linearizes when the `next` of the last
+    /// node is seen null: both locks are held then, so an insert of a new maximum either
+    /// completed before, and is found by the traversal, or waits and lands after the removal.
+    pub fn pop_max(&self) -> Option<T> {
+        let mut cursor = Cursor(lock(&self.head));
+        loop {
+            let node = unsafe { cursor.0.as_ref() }?;
+            let next = lock(&node.next);
+            if next.is_null() {
+                // Released for `unlink`, which locks it again. No other thread can take it in
+                // between, since we hold the lock of the link to the node.
+                drop(next);
+                break;
+            }
+            cursor.0 = next;
+        }
+        let data = cursor.unlink();
+        let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+        Some(data)
+    }
+
+    /// Returns a clone of the smallest element, or `None` if the set is empty.
+    ///
+    /// Only takes the lock of the head, like `is_empty`.
+    pub fn first(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.first_with(T::clone)
+    }
+
+    /// Calls `f` with the smallest element and returns the result, or `None` if the set is empty.
+    ///
+    /// `f` is called with the lock of the head held, so the element cannot be removed underneath
+    /// it.
+    pub fn first_with<R, F: FnOnce(&T) -> R>(&self, f: F) -> Option<R> {
+        let head = lock(&self.head);
+        let node = unsafe { head.as_ref() }?;
+        Some(f(&node.data))
+    }
+
+    /// Returns a clone of the largest element, or `None` if the set is empty.
+    ///
+    /// Traverses the whole list, like `pop_max`.
+    pub fn last(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.last_with(T::clone)
+    }
+
+    /// Calls `f` with the largest element and returns the result, or `None` if the set is empty.
+    ///
+    /// Traverses with lock-coupling to the last node like `pop_max`, and calls `f` with the locks
+    /// of the link to the node and of its null `next` held, so that the node is still the last.
+    pub fn last_with<R, F: FnOnce(&T) -> R>(&self, f: F) -> Option<R> {
+        let mut cursor = lock(&self.head);
+        loop {
+            let node = unsafe { cursor.as_ref() }?;
+            let next = lock(&node.next);
+            if next.is_null() {
+                return Some(f(&node.data));
+            }
+            cursor = next;
+        }
+    }
+
+    /// Removes every element for which `f` returns `false`, in a single pass.
+    ///
+    /// `f` is called with the lock of the link to the element held, so the element cannot be
+    /// removed underneath it. After a removal the link points to the next element, which is
+    /// checked before moving on, so consecutive removed elements are not skipped.
+    pub fn retain<F: FnMut(&T) -> bool>(&self, mut f: F) {
+        let mut cursor = Cursor(lock(&self.head));
+        while let Some(node) = unsafe { cursor.0.as_ref() } {
+            if f(&node.data) {
+                cursor.0 = lock(&node.next);
+            } else {
+                let data = cursor.unlink();
+                let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+                // Dropped after the counter is adjusted, in case it panics.
+                drop(data);
+            }
+        }
+    }
+
+    /// Removes every element for which `f` returns `true` in a single pass, and returns them in
+    /// increasing order.
+    ///
+    /// The counterpart of `retain` that keeps the removed elements: `f` is called with the lock
+    /// of the link to the element held in the same way, so a concurrent `remove` cannot free it
+    /// first, and consecutive matches are not skipped.
+    pub fn extract_if<F: FnMut(&T) -> bool>(&self, mut f: F) -> Vec<T> {
+        let mut extracted = Vec::new();
+        let mut cursor = Cursor(lock(&self.head));
+        while let Some(node) = unsafe { cursor.0.as_ref() } {
+            if f(&node.data) {
+                extracted.push(cursor.unlink());
+                let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+            } else {
+                cursor.0 = lock(&node.next);
+            }
+        }
+        extracted
+    }
+
+    /// Removes every element.
+    ///
+    /// Detaches the whole chain under the lock of the head, and frees it after releasing the
+    /// head, so that other operations can proceed meanwhile. Before freeing a node, takes the
+    /// lock of its `next` to wait for the threads still inside the detached chain, which only
+    /// move forward. Their operations linearize before the clear.
+    pub fn clear(&self) {
+        let mut node = mem::replace(&mut *lock(&self.head), ptr::null_mut());
+        while !node.is_null() {
+            let next = *lock(&unsafe { &*node }.next);
+            drop(unsafe { Box::from_raw(node) });
+            let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+            node = next;
+        }
+    }
+
+    /// Removes every element, without locking since no other thread can access the set.
+    pub fn clear_mut(&mut self) {
+        let mut node = mem::replace(
+            self.head.get_mut().unwrap_or_else(PoisonError::into_inner),
+            ptr::null_mut(),
+        );
+        while !node.is_null() {
+            let mut owned = unsafe { Box::from_raw(node) };
+            node = *owned.next.get_mut().unwrap_or_else(PoisonError::into_inner);
+        }
+        *self.len.get_mut() = 0;
+    }
+
+    /// Returns the number of elements in the set, counted in a single traversal.
+    ///
+    /// The traversal couples the locks like `find`, so concurrent operations wait or are waited
+    /// for but never deadlock. The count is a snapshot of what the traversal observed.
+    pub fn len_exact(&self) -> usize {
+        let mut cursor = lock(&self.head);
+        let mut len = 0;
+        while let Some(node) = unsafe { cursor.as_ref() } {
+            cursor = lock(&node.next);
+            len += 1;
+        }
+        len
+    }
 }
 
 impl<T: Ord> OrderedListSet<T> {
-    fn find(&self, key: &T) -> (bool, Cursor<T>) {
-        todo!()
+    fn find<Q>(&self, key: &Q) -> (bool, Cursor<T>)
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut cursor = Cursor(lock(&self.head));
+        let found = cursor.find(key);
+        (found, cursor)
     }
 
     /// Returns `true` if the set contains the key.
-    pub fn contains(&self, key: &T) -> bool {
-        todo!()
+    ///
+    /// The key may be any borrowed form of the elements, like a `&str` for a set of `String`s,
+    /// but its order must agree with theirs.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(key).0
+    }
+
+    /// Returns `true` if the set contains every key of `keys`.
+    ///
+    /// The keys are sorted first, then looked up with one lock-coupled traversal that continues
+    /// from the previous position, like in `insert_sorted_batch`. It stops at the first key that
+    /// the set does not have.
+    pub fn contains_all<I: IntoIterator<Item = T>>(&self, keys: I) -> bool {
+        let mut keys = keys.into_iter().collect::<Vec<_>>();
+        keys.sort_unstable();
+        let mut cursor = Cursor(lock(&self.head));
+        keys.iter().all(|key| cursor.find(key))
+    }
+
+    /// Calls `f` on the element equal to `key`, and returns its result, or `None` if the set does
+    /// not have the key.
+    ///
+    /// `f` runs while the lock of the link to the element is held, so the element cannot be
+    /// removed in the meantime. The reference cannot outlive the call, since `f` must accept any
+    /// lifetime.
+    pub fn read<Q, R, F>(&self, key: &Q, f: F) -> Option<R>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+        F: FnOnce(&T) -> R,
+    {
+        let (found, cursor) = self.find(key);
+        if !found {
+            return None;
+        }
+        Some(f(unsafe { &(**cursor.0).data }))
     }
 
     /// Insert a key to the set. If the set already has the key, return the provided key in `Err`.
     pub fn insert(&self, key: T) -> Result<(), T> {
-        todo!()
+        let (found, mut cursor) = self.find(&key);
+        if found {
+            return Err(key);
+        }
+        *cursor.0 = Node::new(key, *cursor.0);
+        let _ = self.len.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Inserts the value, replacing the equal element if the set has one, and returns the
+    /// replaced element.
+    ///
+    /// Like `BTreeSet::replace`, for elements whose order only depends on part of them. The
+    /// element is swapped in place, without unlinking the node, while holding the locks of the
+    /// link to the node and of its `next`: a `CursorMut` reads the element before it with only
+    /// the latter held.
+    pub fn replace(&self, value: T) -> Option<T> {
+        let (found, mut cursor) = self.find(&value);
+        let node = *cursor.0;
+        if !found {
+            *cursor.0 = Node::new(value, node);
+            let _ = self.len.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        let _next = lock(unsafe { &(*node).next });
+        Some(mem::replace(unsafe { &mut (*node).data }, value))
+    }
+
+    /// Inserts the element made by `f` if the set does not have the key, and returns `true` if it
+    /// did so.
+    ///
+    /// `f` is called at most once, and not at all if the set has the key. It is called with
+    /// the lock of the insertion point held, so no other thread can insert the key in between.
+    /// The element must be equal to the key.
+    pub fn get_or_insert_with<Q, F>(&self, key: &Q, f: F) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+        F: FnOnce() -> T,
+    {
+        let (found, mut cursor) = self.find(key);
+        if found {
+            return false;
+        }
+        let data = f();
+        debug_assert!(data.borrow() == key, "the element differs from the key");
+        *cursor.0 = Node::new(data, *cursor.0);
+        let _ = self.len.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// Moves the elements not less than `key` to a new set, and returns it.
+    ///
+    /// Traverses to the split point with lock-coupling, and keeps the lock of the link there, so
+    /// that no other operation can enter the suffix. It then counts the suffix with lock-coupling,
+    /// following the operations already in it, which only move forward and never wait for the
+    /// kept lock, until they leave. The split linearizes when the link is cut, with no operation
+    /// in progress on either part of the chain, so the counters of both sets are exact.
+    /// Operations waiting at the link then continue in `self`.
+    pub fn split_off<Q>(&self, key: &Q) -> Self
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (_, mut cursor) = self.find(key);
+        let mut len = 0;
+        let mut curr = *cursor.0;
+        let mut last = None;
+        while let Some(node) = unsafe { curr.as_ref() } {
+            let next = lock(&node.next);
+            curr = *next;
+            // Releases the lock of the link to the node only now.
+            last = Some(next);
+            len += 1;
+        }
+        drop(last);
+        let head = mem::replace(&mut *cursor.0, ptr::null_mut());
+        let _ = self.len.fetch_sub(len, Ordering::Relaxed);
+        Self {
+            head: Mutex::new(head),
+            len: AtomicUsize::new(len),
+        }
+    }
+
+    /// Inserts the elements of `iter` that the set does not have yet, in a single pass.
+    ///
+    /// Like `insert_sorted_batch`, dropping the rejected elements.
+    pub fn extend<I: IntoIterator<Item = T>>(&self, iter: I) {
+        drop(self.insert_sorted_batch(iter.into_iter().collect()));
     }
 
-    /// Remove the key from the set and return it.
-    pub fn remove(&self, key: &T) -> Result<T, ()> {
-        todo!()
+    /// Inserts the elements of `batch` that the set does not have yet, in a single pass, and
+    /// returns the others in increasing order.
+    ///
+    /// The batch is sorted first, which is linear if it already is, then merged into the list with
+    /// one lock-coupled traversal that restarts from neither the head nor the new nodes. So
+    /// inserting `k` elements into `n` takes `O(n + k)` steps, apart from the sort. Of equal
+    /// elements in the batch, the first is inserted, unless the set already has it.
+    pub fn insert_sorted_batch(&self, mut batch: Vec<T>) -> Vec<T> {
+        let mut rejected = Vec::new();
+        if batch.is_empty() {
+            return rejected;
+        }
+        batch.sort();
+        let mut cursor = Cursor(lock(&self.head));
+        for key in batch {
+            // Continues from the previous position, since the keys are not decreasing. After an
+            // insertion, the cursor points to the new node, so an equal key is found there.
+            if cursor.find(&key) {
+                rejected.push(key);
+                continue;
+            }
+            *cursor.0 = Node::new(key, *cursor.0);
+            let _ = self.len.fetch_add(1, Ordering::Relaxed);
+        }
+        rejected
+    }
+
+    /// Remove the key from the set and return it, or `None` if the set does not have the key.
+    ///
+    /// Like in `contains`, the key may be any borrowed form of the elements.
+    pub fn remove<Q>(&self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (found, mut cursor) = self.find(key);
+        if !found {
+            return None;
+        }
+        let data = cursor.unlink();
+        let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+        Some(data)
+    }
+
+    /// Like `contains`, but never waits for a lock. Returns `Err` as soon as a lock on the way is
+    /// held by another thread, releasing the locks taken so far.
+    ///
+    /// So it may fail whenever another operation is in progress nearby, even if it would not have
+    /// had to wait long.
+    pub fn try_contains<Q>(&self, key: &Q) -> Result<bool, ()>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut cursor = Cursor(try_lock(&self.head).ok_or(())?);
+        cursor.try_find(key).ok_or(())
+    }
+
+    /// Like `insert`, but never waits for a lock. Returns the key in the outer `Err` as soon as a
+    /// lock on the way is held by another thread, like `try_contains`.
+    pub fn try_insert(&self, key: T) -> Result<Result<(), T>, T> {
+        let mut cursor = Cursor(some_or!(try_lock(&self.head), return Err(key)));
+        match cursor.try_find(&key) {
+            None => Err(key),
+            Some(true) => Ok(Err(key)),
+            Some(false) => {
+                *cursor.0 = Node::new(key, *cursor.0);
+                let _ = self.len.fetch_add(1, Ordering::Relaxed);
+                Ok(Ok(()))
+            }
+        }
+    }
+
+    /// Like `remove`, but never waits for a lock. Returns `Err` as soon as a lock on the way is
+    /// held by another thread, like `try_contains`, including the lock of the `next` of the node
+    /// to remove.
+    pub fn try_remove<Q>(&self, key: &Q) -> Result<Option<T>, ()>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut cursor = Cursor(try_lock(&self.head).ok_or(())?);
+        if !cursor.try_find(key).ok_or(())? {
+            return Ok(None);
+        }
+        let data = cursor.try_unlink().ok_or(())?;
+        let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+        Ok(Some(data))
     }
 }
 
+/// An iterator over the elements of an [`OrderedListSet`], in increasing order.
 #[derive(Debug)]
 pub struct Iter<'l, T>(Option<MutexGuard<'l, *mut Node<T>>>);
 
+/// An iterator over the elements of an [`OrderedListSet`] in a range, in increasing order.
+#[derive(Debug)]
+pub struct RangeIter<'l, T> {
+    iter: Iter<'l, T>,
+    hi: &'l T,
+}
+
+/// A cursor into an [`OrderedListSet`], created by [`OrderedListSet::lower_bound`].
+///
+/// The cursor points to an element, or past the last one, and holds the lock of the link to it,
+/// like [`Ref`]. So the element cannot be removed, and nothing can be inserted right before it,
+/// by anyone but the cursor. It only moves forward, coupling the locks like every operation.
+///
+/// Other operations that reach the position wait until the cursor moves past it or is dropped.
+/// So the thread holding a cursor must not use the same set otherwise, not even through a second
+/// cursor, or it may deadlock with itself.
+#[derive(Debug)]
+pub struct CursorMut<'l, T> {
+    cursor: Cursor<'l, T>,
+    /// The element before the current one. It cannot be removed, since that locks its `next`,
+    /// which the cursor holds.
+    prev: Option<&'l T>,
+    len: &'l AtomicUsize,
+}
+
+/// An iterator moving the elements out of an [`OrderedListSet`], in increasing order.
+///
+/// The remaining elements are dropped with the iterator.
+#[derive(Debug)]
+pub struct IntoIter<T>(OrderedListSet<T>);
+
+/// An element yielded by [`Iter`].
+///
+/// Holds the lock of the link to the element, so that it cannot be removed until this is dropped.
+/// Removals of the element and insertions right before it wait in the meantime.
+pub struct Ref<'l, T>(MutexGuard<'l, *mut Node<T>>);
+
 impl<T> OrderedListSet<T> {
     /// An iterator visiting all elements.
     pub fn iter(&self) -> Iter<T> {
-        Iter(Some(self.head.lock().unwrap()))
+        Iter(Some(lock(&self.head)))
+    }
+
+    /// Calls `f` on the elements in increasing order until it returns `false`, and returns the
+    /// number of elements it returned `true` for.
+    ///
+    /// Traverses with lock-coupling like `iter`, calling `f` with the lock of the link to the
+    /// element held. Unlike with `iter().take_while(f)`, every lock is released as soon as `f`
+    /// returns `false`, rather than when the iterator is dropped.
+    pub fn iter_while<F: FnMut(&T) -> bool>(&self, mut f: F) -> usize {
+        let mut cursor = lock(&self.head);
+        let mut count = 0;
+        while let Some(node) = unsafe { cursor.as_ref() } {
+            if !f(&node.data) {
+                break;
+            }
+            count += 1;
+            cursor = lock(&node.next);
+        }
+        count
+    }
+
+    /// Returns clones of all elements, in increasing order.
+    ///
+    /// Unlike with `iter`, no lock is held once this returns, so the set may be used while going
+    /// through the result. The traversal couples the locks, cloning each element while the lock of
+    /// the link to it is held, so the result is a point-in-time snapshot of what it observed.
+    pub fn snapshot(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.iter().map(|elem| (*elem).clone()).collect()
+    }
+}
+
+impl<T: Ord> OrderedListSet<T> {
+    /// Returns a cursor pointing to the first element not less than `key`, or past the last one.
+    ///
+    /// Like in `contains`, the key may be any borrowed form of the elements.
+    pub fn lower_bound<Q>(&self, key: &Q) -> CursorMut<'_, T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut cursor = CursorMut {
+            cursor: Cursor(lock(&self.head)),
+            prev: None,
+            len: &self.len,
+        };
+        while matches!(cursor.current(), Some(data) if data.borrow() < key) {
+            cursor.move_next();
+        }
+        cursor
+    }
+}
+
+impl<'l, T> CursorMut<'l, T> {
+    /// Returns the element the cursor points to, or `None` if it is past the last one.
+    pub fn current(&self) -> Option<&T> {
+        unsafe { self.cursor.0.as_ref() }.map(|node| &node.data)
+    }
+
+    /// Moves the cursor to the next element. Does nothing if it is past the last one.
+    pub fn move_next(&mut self) {
+        let node = some_or!(unsafe { self.cursor.0.as_ref() }, return);
+        // Locks the next field before the current one is released.
+        self.cursor.0 = lock(&node.next);
+        self.prev = Some(&node.data);
+    }
+
+    /// Removes the element the cursor points to and returns it, or `None` if it is past the last
+    /// one. The cursor then points to the next element.
+    pub fn remove_current(&mut self) -> Option<T> {
+        if self.cursor.0.is_null() {
+            return None;
+        }
+        let data = self.cursor.unlink();
+        let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+        Some(data)
+    }
+}
+
+impl<'l, T: Ord> CursorMut<'l, T> {
+    /// Inserts a key right before the element the cursor points to, which stays the current one.
+    ///
+    /// If the key is not between the previous and the current element, which would break the
+    /// order, returns it in `Err`. This includes keys equal to either element.
+    pub fn insert_before(&mut self, key: T) -> Result<(), T> {
+        if matches!(self.prev, Some(prev) if *prev >= key)
+            || matches!(self.current(), Some(curr) if *curr <= key)
+        {
+            return Err(key);
+        }
+        *self.cursor.0 = Node::new(key, *self.cursor.0);
+        let _ = self.len.fetch_add(1, Ordering::Relaxed);
+        // Moves past the new node. No other thread can reach its lock yet.
+        self.move_next();
+        Ok(())
     }
 }
 
 impl<'l, T> Iterator for Iter<'l, T> {
-    type Item = &'l T;
+    type Item = Ref<'l, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let guard = self.0.take()?;
+        // At the end, the lock is released so that the finished iterator blocks no one.
+        let node = unsafe { guard.as_ref() }?;
+        self.0 = Some(lock(&node.next));
+        Some(Ref(guard))
+    }
+}
+
+impl<'l, T> IntoIterator for &'l OrderedListSet<T> {
+    type Item = Ref<'l, T>;
+    type IntoIter = Iter<'l, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T> IntoIterator for OrderedListSet<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // No other thread can access the set, so the links are read and changed without locking.
+        let head = self
+            .0
+            .head
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        if head.is_null() {
+            return None;
+        }
+        let node = unsafe { Box::from_raw(*head) };
+        *head = node
+            .next
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        *self.0.len.get_mut() -= 1;
+        Some(node.data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.0.len();
+        (len, Some(len))
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+// The set operations merge a snapshot of `other` with a traversal of `self`, so they never hold
+// locks of both sets and never deadlock, even on the same set. The result reflects the snapshot
+// and what the traversal observed.
+impl<T: Ord + Clone> OrderedListSet<T> {
+    /// Returns a new set with the elements in `self` or `other`.
+    pub fn union_into(&self, other: &Self) -> Self {
+        let mut theirs = other.snapshot().into_iter().peekable();
+        let mut elems = Vec::new();
+        for mine in self.iter() {
+            while let Some(elem) = theirs.next_if(|elem| *elem < *mine) {
+                elems.push(elem);
+            }
+            let _ = theirs.next_if(|elem| *elem == *mine);
+            elems.push((*mine).clone());
+        }
+        elems.extend(theirs);
+        Self::from_sorted(elems)
+    }
+
+    /// Returns a new set with the elements in both `self` and `other`.
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut theirs = other.snapshot().into_iter().peekable();
+        let mut elems = Vec::new();
+        for mine in self.iter() {
+            while theirs.next_if(|elem| *elem < *mine).is_some() {}
+            if theirs.peek().is_none() {
+                break;
+            }
+            if theirs.next_if(|elem| *elem == *mine).is_some() {
+                elems.push((*mine).clone());
+            }
+        }
+        Self::from_sorted(elems)
+    }
+
+    /// Returns `true` if every element of `self` is in `other`.
+    ///
+    /// Stops at the first element of `self` that is not in `other`.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        let mut theirs = other.snapshot().into_iter().peekable();
+        for mine in self.iter() {
+            while theirs.next_if(|elem| *elem < *mine).is_some() {}
+            if theirs.next_if(|elem| *elem == *mine).is_none() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Returns a new set with the elements in `self` but not in `other`.
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut theirs = other.snapshot().into_iter().peekable();
+        let mut elems = Vec::new();
+        for mine in self.iter() {
+            while theirs.next_if(|elem| *elem < *mine).is_some() {}
+            if theirs.next_if(|elem| *elem == *mine).is_none() {
+                elems.push((*mine).clone());
+            }
+        }
+        Self::from_sorted(elems)
+    }
+}
+
+impl<T: Ord> OrderedListSet<T> {
+    /// An iterator visiting the elements in `[lo, hi)`.
+    ///
+    /// Skips to the first element `>= lo` with lock-coupling, and releases every lock once it
+    /// sees an element `>= hi`, never touching the rest of the list.
+    pub fn iter_range<'l>(&'l self, lo: &T, hi: &'l T) -> RangeIter<'l, T> {
+        let iter = if lo < hi {
+            let (_, cursor) = self.find(lo);
+            Iter(Some(cursor.0))
+        } else {
+            Iter(None)
+        };
+        RangeIter { iter, hi }
+    }
+}
+
+impl<'l, T: Ord> Iterator for RangeIter<'l, T> {
+    type Item = Ref<'l, T>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        todo!()
+        let guard = self.iter.0.as_ref()?;
+        if let Some(node) = unsafe { guard.as_ref() } {
+            if node.data >= *self.hi {
+                self.iter.0 = None;
+                return None;
+            }
+        }
+        self.iter.next()
+    }
+}
+
+impl<T> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &(**self.0).data }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Ref<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Ref").field(&**self).finish()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OrderedListSet<T> {
+    /// Prints the elements like a set, up to `DEBUG_LIMIT` of them followed by `...`.
+    ///
+    /// Traverses like `iter`, waiting for the locks held by other threads. So it deadlocks if the
+    /// current thread holds a lock of the set itself, e.g. in an `Iter` or `Ref`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut set = f.debug_set();
+        for (i, elem) in self.iter().enumerate() {
+            if i == DEBUG_LIMIT {
+                let _ = set.entry(&format_args!("..."));
+                break;
+            }
+            let _ = set.entry(&*elem);
+        }
+        set.finish()
     }
 }
 
 impl<T> Drop for OrderedListSet<T> {
     fn drop(&mut self) {
-        todo!()
+        self.clear_mut();
     }
 }
 
@@ -100,3 +948,141 @@ impl<T> Default for OrderedListSet<T> {
         Self::new()
     }
 }
+
+impl<T: Clone> Clone for OrderedListSet<T> {
+    /// Creates an independent set with clones of the elements.
+    ///
+    /// Clones a `snapshot`, so under concurrent modifications the new set has the elements that
+    /// its traversal observed, which are still increasing.
+    fn clone(&self) -> Self {
+        Self::from_sorted(self.snapshot())
+    }
+}
+
+impl<T: PartialEq> PartialEq for OrderedListSet<T> {
+    /// Returns `true` if the sets have the same elements.
+    ///
+    /// Compares snapshots under concurrent modifications. Both lists are traversed together with
+    /// lock-coupling, so each of them has a single link locked, plus the next one while the
+    /// traversal advances. The lists are entered and advanced in the order of their addresses, so
+    /// that two comparisons of the same lists never overtake each other in one list while waiting
+    /// for each other in the other.
+    fn eq(&self, other: &Self) -> bool {
+        if ptr::eq(self, other) {
+            return true;
+        }
+        let (first, second) = if (self as *const Self) < (other as *const Self) {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let mut first_link = lock(&first.head);
+        let mut second_link = lock(&second.head);
+        loop {
+            let (a, b) = match unsafe { (first_link.as_ref(), second_link.as_ref()) } {
+                (None, None) => return true,
+                (Some(a), Some(b)) => (a, b),
+                _ => return false,
+            };
+            if a.data != b.data {
+                return false;
+            }
+            // Locks the next fields before the current ones are released.
+            first_link = lock(&a.next);
+            second_link = lock(&b.next);
+        }
+    }
+}
+
+impl<T: Eq> Eq for OrderedListSet<T> {}
+
+impl<T: PartialEq> PartialEq<[T]> for OrderedListSet<T> {
+    /// Returns `true` if the set has the elements of the slice, in the same order.
+    fn eq(&self, other: &[T]) -> bool {
+        let mut iter = self.iter();
+        other
+            .iter()
+            .all(|elem| matches!(iter.next(), Some(e) if *e == *elem))
+            && iter.next().is_none()
+    }
+}
+
+impl<T: PartialEq> PartialEq<Vec<T>> for OrderedListSet<T> {
+    fn eq(&self, other: &Vec<T>) -> bool {
+        *self == **other
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Serialize> Serialize for OrderedListSet<T> {
+    /// Serializes the elements as an increasing sequence.
+    ///
+    /// Locks every link first, like a traversal that keeps all its locks, so that the length is
+    /// known up front and the sequence is a snapshot. Other threads wait in the meantime.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let elems = self.iter().collect::<Vec<_>>();
+        serializer.collect_seq(elems.iter().map(|elem| &**elem))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Ord + Deserialize<'de>> Deserialize<'de> for OrderedListSet<T> {
+    /// Deserializes a strictly increasing sequence, as written by `serialize`.
+    ///
+    /// Fails on a sequence that is out of order or has duplicates rather than fixing it, since
+    /// `serialize` never writes one.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let elems = Vec::<T>::deserialize(deserializer)?;
+        if !elems.windows(2).all(|w| w[0] < w[1]) {
+            return Err(de::Error::custom("elements are not strictly increasing"));
+        }
+        Ok(Self::from_sorted(elems))
+    }
+}
+
+// The conversions own the sets, so they build and take apart the chains without locking.
+impl<T: Ord> From<Vec<T>> for OrderedListSet<T> {
+    /// Sorts the elements, keeping the first of equal ones like `FromIterator`.
+    fn from(elems: Vec<T>) -> Self {
+        elems.into_iter().collect()
+    }
+}
+
+impl<T> From<BTreeSet<T>> for OrderedListSet<T> {
+    fn from(elems: BTreeSet<T>) -> Self {
+        Self::from_sorted(elems.into_iter().collect())
+    }
+}
+
+impl<T> From<OrderedListSet<T>> for Vec<T> {
+    /// Moves the elements out in increasing order.
+    fn from(set: OrderedListSet<T>) -> Self {
+        set.into_iter().collect()
+    }
+}
+
+impl<T: Ord> From<OrderedListSet<T>> for BTreeSet<T> {
+    fn from(set: OrderedListSet<T>) -> Self {
+        set.into_iter().collect()
+    }
+}
+
+impl<T: Ord> Extend<T> for OrderedListSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        OrderedListSet::extend(&*self, iter)
+    }
+}
+
+impl<T: Ord> FromIterator<T> for OrderedListSet<T> {
+    /// Creates a set from the elements of `iter`.
+    ///
+    /// Of the elements that are equal, only the first is kept, like a sequence of `insert`s. The
+    /// elements are sorted first, and the chain is built without locking.
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut elems = iter.into_iter().collect::<Vec<_>>();
+        // The sort is stable, so the first of equal elements stays first after it.
+        elems.sort();
+        elems.dedup_by(|later, earlier| later == earlier);
+        Self::from_sorted(elems)
+    }
+}
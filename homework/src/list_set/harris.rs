@@ -0,0 +1,246 @@
+use std::borrow::Borrow;
+use std::cmp;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crossbeam_epoch::{pin, unprotected, Atomic, Guard, Owned, Shared};
+
+#[derive(Debug)]
+struct Node<T> {
+    data: T,
+    /// Tagged with 1 once the node is removed, which also freezes the pointer.
+    next: Atomic<Node<T>>,
+}
+
+/// Lock-free concurrent sorted singly linked list, after Harris and Michael.
+///
+/// A node is removed in two steps: it is first marked by tagging its `next` with a CAS, which
+/// removes it logically and keeps any other thread from inserting after it, and then unlinked by
+/// a CAS on the link to it. Traversals help: a marked node they run into is unlinked before they
+/// move on, and they restart from the head if that fails. So every operation is lock-free, and
+/// only links to unmarked nodes are ever changed.
+///
+/// Removed nodes are reclaimed with `crossbeam_epoch` by the thread that unlinked them. Since a
+/// node is not freed while a thread that might have seen it is pinned, a link that is changed
+/// back to a node it pointed to before (ABA) always points to the same, live node.
+///
+/// For the same reason, the API differs from `OrderedListSet` in two ways. `remove` needs
+/// `T: Clone` and returns a clone, since other threads may still read the element of a removed
+/// node. And `iter` takes a `Guard` from the caller, whose pin keeps the yielded elements alive.
+#[derive(Debug)]
+pub struct HarrisListSet<T> {
+    head: Atomic<Node<T>>,
+    /// Number of elements, adjusted by the CASs that insert and mark nodes.
+    len: AtomicUsize,
+}
+
+impl<T> HarrisListSet<T> {
+    /// Creates a new list.
+    pub fn new() -> Self {
+        Self {
+            head: Atomic::null(),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of elements in the set.
+    ///
+    /// Like `OrderedListSet::len`, a snapshot that may be stale under concurrent modifications.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if the set has no element.
+    pub fn is_empty(&self) -> bool {
+        self.iter(&pin()).next().is_none()
+    }
+
+    /// An iterator visiting all elements, without helping.
+    ///
+    /// Skips marked nodes. The elements are increasing, but concurrent modifications may or may
+    /// not be seen.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, T> {
+        Iter {
+            curr: self.head.load(Ordering::Acquire, guard),
+            guard,
+        }
+    }
+}
+
+impl<T: Ord> HarrisListSet<T> {
+    /// Returns the link to the position of the key, and the first node not less than the key,
+    /// both unmarked when seen. The node has the key if `true` is returned.
+    ///
+    /// Unlinks the marked nodes on the way.
+    fn find<'g, Q>(
+        &'g self,
+        key: &Q,
+        guard: &'g Guard,
+    ) -> (bool, &'g Atomic<Node<T>>, Shared<'g, Node<T>>)
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        'retry: loop {
+            let mut prev = &self.head;
+            // Acquires the nodes from the CASs that inserted them. The head is never marked, and
+            // `curr` is only ever moved to an unmarked `next`, so it is never tagged.
+            let mut curr = prev.load(Ordering::Acquire, guard);
+            loop {
+                let node = some_or!(unsafe { curr.as_ref() }, return (false, prev, curr));
+                let next = node.next.load(Ordering::Acquire, guard);
+                if next.tag() == 1 {
+                    // Helps the removal of `curr`. Fails if `prev` changed or is marked itself,
+                    // in which case the traversal is no longer in the list.
+                    let next = next.with_tag(0);
+                    if prev
+                        .compare_exchange(curr, next, Ordering::Release, Ordering::Relaxed, guard)
+                        .is_err()
+                    {
+                        continue 'retry;
+                    }
+                    // Only the thread whose CAS unlinked the node retires it.
+                    unsafe { guard.defer_destroy(curr) };
+                    curr = next;
+                    continue;
+                }
+                match node.data.borrow().cmp(key) {
+                    cmp::Ordering::Less => {
+                        prev = &node.next;
+                        curr = next;
+                    }
+                    cmp::Ordering::Equal => return (true, prev, curr),
+                    cmp::Ordering::Greater => return (false, prev, curr),
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if the set contains the key.
+    ///
+    /// The key may be any borrowed form of the elements, whose order must agree with theirs.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(key, &pin()).0
+    }
+
+    /// Insert a key to the set. If the set already has the key, return the provided key in `Err`.
+    pub fn insert(&self, key: T) -> Result<(), T> {
+        let guard = &pin();
+        let mut node = Owned::new(Node {
+            data: key,
+            next: Atomic::null(),
+        });
+        loop {
+            let (found, prev, curr) = self.find(&node.data, guard);
+            if found {
+                return Err(node.into_box().data);
+            }
+            node.next.store(curr, Ordering::Relaxed);
+            // Releases the node to the traversals. Fails if `prev` changed, or was marked since so
+            // that inserting after it would lose the node.
+            match prev.compare_exchange(curr, node, Ordering::Release, Ordering::Relaxed, guard) {
+                Ok(_) => {
+                    let _ = self.len.fetch_add(1, Ordering::Relaxed);
+                    return Ok(());
+                }
+                Err(e) => node = e.new,
+            }
+        }
+    }
+
+    /// Remove the key from the set and return it, or `None` if the set does not have the key.
+    ///
+    /// Returns a clone of the element, since traversals may still be reading it until the node is
+    /// reclaimed.
+    pub fn remove<Q>(&self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q> + Clone,
+        Q: Ord + ?Sized,
+    {
+        let guard = &pin();
+        loop {
+            let (found, prev, curr) = self.find(key, guard);
+            if !found {
+                return None;
+            }
+            let node = unsafe { curr.deref() };
+            let next = node.next.load(Ordering::Acquire, guard);
+            if next.tag() == 1 {
+                // Removed by another thread in the meantime, which `find` helps with.
+                continue;
+            }
+            // The logical removal, which linearizes the operation. Fails if a node was inserted
+            // after `curr`, or another thread marked it first.
+            if node
+                .next
+                .compare_exchange(
+                    next,
+                    next.with_tag(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                    guard,
+                )
+                .is_err()
+            {
+                continue;
+            }
+            let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+            let data = node.data.clone();
+            if prev
+                .compare_exchange(curr, next, Ordering::Release, Ordering::Relaxed, guard)
+                .is_ok()
+            {
+                unsafe { guard.defer_destroy(curr) };
+            } else {
+                // Leaves the unlinking to a traversal, starting one right away.
+                let _ = self.find(key, guard);
+            }
+            return Some(data);
+        }
+    }
+}
+
+/// An iterator over the elements of a [`HarrisListSet`], in increasing order.
+#[derive(Debug)]
+pub struct Iter<'g, T> {
+    curr: Shared<'g, Node<T>>,
+    guard: &'g Guard,
+}
+
+impl<'g, T> Iterator for Iter<'g, T> {
+    type Item = &'g T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let node = unsafe { self.curr.as_ref() }?;
+            let next = node.next.load(Ordering::Acquire, self.guard);
+            self.curr = next.with_tag(0);
+            if next.tag() == 0 {
+                return Some(&node.data);
+            }
+        }
+    }
+}
+
+impl<T> Drop for HarrisListSet<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let guard = unprotected();
+            let mut curr = self.head.load(Ordering::Relaxed, guard);
+            while !curr.is_null() {
+                let next = curr.deref().next.load(Ordering::Relaxed, guard).with_tag(0);
+                drop(curr.into_owned());
+                curr = next;
+            }
+        }
+    }
+}
+
+impl<T> Default for HarrisListSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
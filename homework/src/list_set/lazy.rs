@@ -0,0 +1,255 @@
+use std::borrow::Borrow;
+use std::cmp;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use crossbeam_epoch::{pin, unprotected, Atomic, Guard, Owned, Shared};
+
+use super::lock;
+
+/// The `next` field of a node or the head, with the lock that its writers hold.
+#[derive(Debug)]
+struct Link<T> {
+    lock: Mutex<()>,
+    next: Atomic<Node<T>>,
+    /// Set under the lock once the node owning the link is removed. Never set for the head.
+    marked: AtomicBool,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    data: T,
+    link: Link<T>,
+}
+
+/// Concurrent sorted singly linked list using lazy synchronization.
+///
+/// A node is removed in two steps: it is first marked, which removes it logically, and then
+/// unlinked. Lookups traverse without locking or retrying, and only check the mark of the node
+/// they end at, so they are wait-free. Modifications traverse the same way, then lock the link
+/// to their position and the link out of the node there, and validate the position without
+/// traversing again: neither node is marked, and the first link still points to the node. Since
+/// a node is marked before it is unlinked, an unmarked node is still reachable.
+///
+/// Removed nodes are reclaimed with `crossbeam_epoch`, since unlocked traversals may still be
+/// reading them. So unlike `OrderedListSet`, `remove` needs `T: Clone` and returns a clone of
+/// the element, which stays readable until its node is reclaimed, and `iter` borrows the elements
+/// for as long as a `Guard` passed by the caller.
+#[derive(Debug)]
+pub struct LazyListSet<T> {
+    head: Link<T>,
+    /// Number of elements, adjusted under the locks of the position that an operation changes.
+    len: AtomicUsize,
+}
+
+/// A validated position in the list, locked until dropped.
+struct Window<'g, T> {
+    /// `true` if `curr` has the key looked for.
+    found: bool,
+    /// The link to `curr`.
+    pred: &'g Link<T>,
+    /// The first node not less than the key, or null.
+    curr: Shared<'g, Node<T>>,
+    _locks: (MutexGuard<'g, ()>, Option<MutexGuard<'g, ()>>),
+}
+
+impl<T> Link<T> {
+    fn new(next: Shared<'_, Node<T>>) -> Self {
+        Self {
+            lock: Mutex::new(()),
+            next: Atomic::from(next),
+            marked: AtomicBool::new(false),
+        }
+    }
+}
+
+impl<T> LazyListSet<T> {
+    /// Creates a new list.
+    pub fn new() -> Self {
+        Self {
+            head: Link::new(Shared::null()),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of elements in the set.
+    ///
+    /// Like `OrderedListSet::len`, a snapshot that may be stale under concurrent modifications.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if the set has no element.
+    pub fn is_empty(&self) -> bool {
+        self.iter(&pin()).next().is_none()
+    }
+
+    /// An iterator visiting all elements, without locking.
+    ///
+    /// Skips marked nodes. The elements are increasing, but concurrent modifications may or may
+    /// not be seen.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, T> {
+        Iter {
+            curr: self.head.next.load(Ordering::Acquire, guard),
+            guard,
+        }
+    }
+}
+
+impl<T: Ord> LazyListSet<T> {
+    /// Finds the position of the key without locking.
+    fn search<'g, Q>(
+        &'g self,
+        key: &Q,
+        guard: &'g Guard,
+    ) -> (bool, &'g Link<T>, Shared<'g, Node<T>>)
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut pred = &self.head;
+        loop {
+            // Acquires the node from the store that inserted it.
+            let curr = pred.next.load(Ordering::Acquire, guard);
+            let node = some_or!(unsafe { curr.as_ref() }, return (false, pred, curr));
+            match node.data.borrow().cmp(key) {
+                cmp::Ordering::Less => pred = &node.link,
+                cmp::Ordering::Equal => return (true, pred, curr),
+                cmp::Ordering::Greater => return (false, pred, curr),
+            }
+        }
+    }
+
+    /// Finds the position of the key, and locks and validates it, retrying until it succeeds.
+    fn locate<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> Window<'g, T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        loop {
+            let (found, pred, curr) = self.search(key, guard);
+            // Locked in the order of the list, like every other operation, so they never deadlock.
+            let pred_lock = lock(&pred.lock);
+            let curr_ref = unsafe { curr.as_ref() };
+            let curr_lock = curr_ref.map(|node| lock(&node.link.lock));
+            // Relaxed, since the marks and `pred` are only written under the locks held.
+            if !pred.marked.load(Ordering::Relaxed)
+                && !matches!(curr_ref, Some(node) if node.link.marked.load(Ordering::Relaxed))
+                && pred.next.load(Ordering::Relaxed, guard) == curr
+            {
+                return Window {
+                    found,
+                    pred,
+                    curr,
+                    _locks: (pred_lock, curr_lock),
+                };
+            }
+        }
+    }
+
+    /// Returns `true` if the set contains the key.
+    ///
+    /// Never locks nor retries. Linearizes when it reads the mark of the node with the key.
+    ///
+    /// The key may be any borrowed form of the elements, whose order must agree with theirs.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let guard = &pin();
+        let (found, _, curr) = self.search(key, guard);
+        // Acquires the removal that marked the node, like `Iter`.
+        found && !unsafe { curr.deref() }.link.marked.load(Ordering::Acquire)
+    }
+
+    /// Insert a key to the set. If the set already has the key, return the provided key in `Err`.
+    pub fn insert(&self, key: T) -> Result<(), T> {
+        let guard = &pin();
+        let window = self.locate(&key, guard);
+        if window.found {
+            return Err(key);
+        }
+        let node = Owned::new(Node {
+            data: key,
+            link: Link::new(window.curr),
+        });
+        // Releases the node to unlocked traversals.
+        window.pred.next.store(node, Ordering::Release);
+        let _ = self.len.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Remove the key from the set and return it, or `None` if the set does not have the key.
+    ///
+    /// Returns a clone of the element, since unlocked traversals may still be reading it until the
+    /// node is reclaimed.
+    pub fn remove<Q>(&self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q> + Clone,
+        Q: Ord + ?Sized,
+    {
+        let guard = &pin();
+        let window = self.locate(key, guard);
+        if !window.found {
+            return None;
+        }
+        let node = unsafe { window.curr.deref() };
+        // The logical removal, released to lookups so that a lookup that sees the mark linearizes
+        // after it.
+        node.link.marked.store(true, Ordering::Release);
+        // Relaxed, since the lock of the node is held. The store releases the next node again, to
+        // the traversals that reach it through `pred`.
+        let next = node.link.next.load(Ordering::Relaxed, guard);
+        window.pred.next.store(next, Ordering::Release);
+        let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+        // Threads waiting for the lock of the node are pinned, so it outlives them. They fail to
+        // validate, since the node is marked.
+        unsafe { guard.defer_destroy(window.curr) };
+        drop(window);
+        // Cloned after the locks are released, so that a panicking `Clone` leaves the node
+        // retired. The node is not destroyed before `guard` is unpinned.
+        Some(node.data.clone())
+    }
+}
+
+/// An iterator over the elements of a [`LazyListSet`], in increasing order.
+#[derive(Debug)]
+pub struct Iter<'g, T> {
+    curr: Shared<'g, Node<T>>,
+    guard: &'g Guard,
+}
+
+impl<'g, T> Iterator for Iter<'g, T> {
+    type Item = &'g T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let node = unsafe { self.curr.as_ref() }?;
+            self.curr = node.link.next.load(Ordering::Acquire, self.guard);
+            if !node.link.marked.load(Ordering::Acquire) {
+                return Some(&node.data);
+            }
+        }
+    }
+}
+
+impl<T> Drop for LazyListSet<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let guard = unprotected();
+            let mut curr = self.head.next.load(Ordering::Relaxed, guard);
+            while !curr.is_null() {
+                let next = curr.deref().link.next.load(Ordering::Relaxed, guard);
+                drop(curr.into_owned());
+                curr = next;
+            }
+        }
+    }
+}
+
+impl<T> Default for LazyListSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,76 @@
+use std::borrow::Borrow;
+use std::collections::BTreeSet;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Set protected by a single lock, as a baseline for the list sets.
+///
+/// Has the same operations as [`OrderedListSet`](super::OrderedListSet), each of which takes the
+/// lock for its whole duration.
+#[derive(Debug)]
+pub struct LockSet<T>(Mutex<BTreeSet<T>>);
+
+impl<T> LockSet<T> {
+    /// Creates a new set.
+    pub fn new() -> Self {
+        Self(Mutex::new(BTreeSet::new()))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeSet<T>> {
+        // A panic never leaves the tree in an inconsistent state.
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the number of elements in the set.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if the set has no element.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Returns clones of all elements, in increasing order.
+    pub fn snapshot(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.lock().iter().cloned().collect()
+    }
+}
+
+impl<T: Ord> LockSet<T> {
+    /// Returns `true` if the set contains the key.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.lock().contains(key)
+    }
+
+    /// Insert a key to the set. If the set already has the key, return the provided key in `Err`.
+    pub fn insert(&self, key: T) -> Result<(), T> {
+        let mut set = self.lock();
+        if set.contains(&key) {
+            return Err(key);
+        }
+        let _ = set.insert(key);
+        Ok(())
+    }
+
+    /// Remove the key from the set and return it, or `None` if the set does not have the key.
+    pub fn remove<Q>(&self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.lock().take(key)
+    }
+}
+
+impl<T> Default for LockSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
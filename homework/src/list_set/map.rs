@@ -0,0 +1,189 @@
+use std::borrow::Borrow;
+use std::cell::UnsafeCell;
+use std::cmp;
+use std::fmt;
+use std::mem;
+
+use super::OrderedListSet;
+
+/// An entry of an [`OrderedListMap`], ordered by its key only.
+///
+/// The value is only accessed with the lock of the link to the node of the entry held, as is
+/// every element of the set, so it is mutated through a shared reference.
+struct Entry<K, V> {
+    key: K,
+    value: UnsafeCell<V>,
+}
+
+impl<K, V> Entry<K, V> {
+    /// # Safety
+    ///
+    /// The lock of the link to the node of the entry must be held.
+    unsafe fn value(&self) -> &V {
+        &*self.value.get()
+    }
+
+    /// # Safety
+    ///
+    /// Like `value`, and no other reference to the value may exist meanwhile.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn value_mut(&self) -> &mut V {
+        &mut *self.value.get()
+    }
+}
+
+impl<K: PartialEq, V> PartialEq for Entry<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<K: Eq, V> Eq for Entry<K, V> {}
+
+impl<K: Ord, V> PartialOrd for Entry<K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: Ord, V> Ord for Entry<K, V> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl<K, V> Borrow<K> for Entry<K, V> {
+    fn borrow(&self) -> &K {
+        &self.key
+    }
+}
+
+/// Concurrent sorted map using lock-coupling.
+///
+/// An [`OrderedListSet`] of entries ordered by their keys, so it shares the locking of the set:
+/// a value is only read or updated with the lock of the link to its entry held, and so never
+/// concurrently with a removal of the entry or another access to the value.
+pub struct OrderedListMap<K, V> {
+    set: OrderedListSet<Entry<K, V>>,
+}
+
+// Values are updated in place by any thread holding the lock, like the data of a `Mutex`, and
+// entries are moved out by any thread removing them.
+unsafe impl<K: Send + Sync, V: Send + Sync> Sync for OrderedListMap<K, V> {}
+
+impl<K, V> OrderedListMap<K, V> {
+    /// Creates a new map.
+    pub fn new() -> Self {
+        Self {
+            set: OrderedListSet::new(),
+        }
+    }
+
+    /// Returns the number of entries in the map.
+    ///
+    /// Like `OrderedListSet::len`, a snapshot that may be stale under concurrent modifications.
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    /// Returns `true` if the map has no entry.
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    /// An iterator visiting clones of all entries, in increasing order of their keys.
+    ///
+    /// Like `OrderedListSet::iter`, holds the lock of the link to the next entry between calls
+    /// to `next`.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter(self.set.iter())
+    }
+}
+
+impl<K: Ord, V> OrderedListMap<K, V> {
+    /// Returns `true` if the map has an entry with the key.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.set.contains(key)
+    }
+
+    /// Inserts an entry, and returns the value the map had for the key, if any.
+    ///
+    /// Finds the position with a cursor, so the value is replaced or the entry inserted in a
+    /// single traversal.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        let mut cursor = self.set.lower_bound(&key);
+        if let Some(entry) = cursor.current() {
+            if entry.key == key {
+                // The cursor holds the lock of the link to the entry.
+                return Some(mem::replace(unsafe { entry.value_mut() }, value));
+            }
+        }
+        let entry = Entry {
+            key,
+            value: UnsafeCell::new(value),
+        };
+        // Cannot fail, since the keys before and at the cursor are less and greater.
+        assert!(cursor.insert_before(entry).is_ok());
+        None
+    }
+
+    /// Calls `f` on the value for the key, and returns its result, or `None` if the map does not
+    /// have the key.
+    ///
+    /// Like `OrderedListSet::read`, `f` runs while the entry cannot be removed.
+    pub fn get_with<R, F: FnOnce(&V) -> R>(&self, key: &K, f: F) -> Option<R> {
+        self.set.read(key, |entry| f(unsafe { entry.value() }))
+    }
+
+    /// Calls `f` on the value for the key to update it in place, and returns its result, or `None`
+    /// if the map does not have the key.
+    ///
+    /// `f` runs with the lock of the link to the entry held, so concurrent updates of the value
+    /// are serialized, like with a `Mutex`.
+    pub fn update<R, F: FnOnce(&mut V) -> R>(&self, key: &K, f: F) -> Option<R> {
+        self.set.read(key, |entry| f(unsafe { entry.value_mut() }))
+    }
+
+    /// Removes the entry with the key and returns its value, or `None` if the map does not have
+    /// the key.
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.set.remove(key).map(|entry| entry.value.into_inner())
+    }
+}
+
+/// An iterator over clones of the entries of an [`OrderedListMap`], in increasing order of their
+/// keys.
+#[derive(Debug)]
+pub struct Iter<'l, K, V>(super::Iter<'l, Entry<K, V>>);
+
+impl<'l, K: Clone, V: Clone> Iterator for Iter<'l, K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.0.next()?;
+        // The `Ref` holds the lock of the link to the entry.
+        Some((entry.key.clone(), unsafe { entry.value() }.clone()))
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for Entry<K, V> {
+    /// Prints the entry like one of a map, so that the set of entries prints like a map.
+    ///
+    /// Only called by the `Debug` of the set, which holds the lock of the link to the entry.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {:?}", self.key, unsafe { self.value() })
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for OrderedListMap<K, V> {
+    /// Prints the entries like a map, up to the same limit as `OrderedListSet`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.set.fmt(f)
+    }
+}
+
+impl<K, V> Default for OrderedListMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
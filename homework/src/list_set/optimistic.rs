@@ -0,0 +1,260 @@
+use std::borrow::Borrow;
+use std::cmp;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use crossbeam_epoch::{pin, unprotected, Atomic, Guard, Owned, Shared};
+
+use super::lock;
+
+/// The `next` field of a node or the head, with the lock that its writers hold.
+#[derive(Debug)]
+struct Link<T> {
+    lock: Mutex<()>,
+    next: Atomic<Node<T>>,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    data: T,
+    link: Link<T>,
+}
+
+/// Concurrent sorted singly linked list using optimistic synchronization.
+///
+/// Traversals take no lock. An operation then locks only the link to the position it found and
+/// the link out of the node there, and validates the position by traversing again from the head:
+/// the first link must still be reachable and still point to the node. Otherwise it unlocks and
+/// retries. Once validated, the position cannot change until unlocked, since removing a node
+/// takes the locks of both the link to it and its own link.
+///
+/// Removed nodes are reclaimed with `crossbeam_epoch`, since unlocked traversals may still be
+/// reading them. This is also where the API differs from `OrderedListSet`: `remove` needs
+/// `T: Clone` and returns a clone, as the removed element stays readable until the node is
+/// reclaimed, and `iter` takes a `Guard` from the caller that keeps the yielded references alive.
+#[derive(Debug)]
+pub struct OptimisticListSet<T> {
+    head: Link<T>,
+    /// Number of elements, adjusted under the locks of the position that an operation changes.
+    len: AtomicUsize,
+}
+
+/// A validated position in the list, locked until dropped.
+struct Window<'g, T> {
+    /// `true` if `curr` has the key looked for.
+    found: bool,
+    /// The link to `curr`.
+    pred: &'g Link<T>,
+    /// The first node not less than the key, or null.
+    curr: Shared<'g, Node<T>>,
+    _locks: (MutexGuard<'g, ()>, Option<MutexGuard<'g, ()>>),
+}
+
+impl<T> Link<T> {
+    fn new(next: Shared<'_, Node<T>>) -> Self {
+        Self {
+            lock: Mutex::new(()),
+            next: Atomic::from(next),
+        }
+    }
+}
+
+impl<T> OptimisticListSet<T> {
+    /// Creates a new list.
+    pub fn new() -> Self {
+        Self {
+            head: Link::new(Shared::null()),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of elements in the set.
+    ///
+    /// Like `OrderedListSet::len`, a snapshot that may be stale under concurrent modifications.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if the set has no element.
+    pub fn is_empty(&self) -> bool {
+        self.head.next.load(Ordering::Acquire, &pin()).is_null()
+    }
+
+    /// An iterator visiting all elements, without locking.
+    ///
+    /// The elements are increasing, but concurrent modifications may or may not be seen, and a
+    /// removed element may still be yielded if the iterator had reached it.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, T> {
+        Iter {
+            curr: self.head.next.load(Ordering::Acquire, guard),
+            guard,
+        }
+    }
+}
+
+impl<T: Ord> OptimisticListSet<T> {
+    /// Finds the position of the key without locking.
+    fn search<'g, Q>(
+        &'g self,
+        key: &Q,
+        guard: &'g Guard,
+    ) -> (bool, &'g Link<T>, Shared<'g, Node<T>>)
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut pred = &self.head;
+        loop {
+            // Acquires the node from the store that inserted it.
+            let curr = pred.next.load(Ordering::Acquire, guard);
+            let node = some_or!(unsafe { curr.as_ref() }, return (false, pred, curr));
+            match node.data.borrow().cmp(key) {
+                cmp::Ordering::Less => pred = &node.link,
+                cmp::Ordering::Equal => return (true, pred, curr),
+                cmp::Ordering::Greater => return (false, pred, curr),
+            }
+        }
+    }
+
+    /// Returns `true` if `pred` is reachable from the head and points to `curr`. Both must be
+    /// locked, and `pred` must come before the key.
+    fn validate<Q>(&self, key: &Q, pred: &Link<T>, curr: Shared<'_, Node<T>>, guard: &Guard) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut link = &self.head;
+        loop {
+            if ptr::eq(link, pred) {
+                // Relaxed, since the lock of `pred` is held.
+                return link.next.load(Ordering::Relaxed, guard) == curr;
+            }
+            let node = some_or!(
+                unsafe { link.next.load(Ordering::Acquire, guard).as_ref() },
+                return false
+            );
+            // Every reachable link before `pred` is out of a node less than the key.
+            if node.data.borrow() >= key {
+                return false;
+            }
+            link = &node.link;
+        }
+    }
+
+    /// Finds the position of the key, and locks and validates it, retrying until it succeeds.
+    fn locate<'g, Q>(&'g self, key: &Q, guard: &'g Guard) -> Window<'g, T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        loop {
+            let (found, pred, curr) = self.search(key, guard);
+            // Locked in the order of the list, like every other operation, so they never deadlock.
+            let pred_lock = lock(&pred.lock);
+            let curr_lock = unsafe { curr.as_ref() }.map(|node| lock(&node.link.lock));
+            if self.validate(key, pred, curr, guard) {
+                return Window {
+                    found,
+                    pred,
+                    curr,
+                    _locks: (pred_lock, curr_lock),
+                };
+            }
+        }
+    }
+
+    /// Returns `true` if the set contains the key.
+    ///
+    /// The key may be any borrowed form of the elements, whose order must agree with theirs.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.locate(key, &pin()).found
+    }
+
+    /// Insert a key to the set. If the set already has the key, return the provided key in `Err`.
+    pub fn insert(&self, key: T) -> Result<(), T> {
+        let guard = &pin();
+        let window = self.locate(&key, guard);
+        if window.found {
+            return Err(key);
+        }
+        let node = Owned::new(Node {
+            data: key,
+            link: Link::new(window.curr),
+        });
+        // Releases the node to unlocked traversals.
+        window.pred.next.store(node, Ordering::Release);
+        let _ = self.len.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Remove the key from the set and return it, or `None` if the set does not have the key.
+    ///
+    /// Returns a clone of the element, since unlocked traversals may still be reading it until the
+    /// node is reclaimed.
+    pub fn remove<Q>(&self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q> + Clone,
+        Q: Ord + ?Sized,
+    {
+        let guard = &pin();
+        let window = self.locate(key, guard);
+        if !window.found {
+            return None;
+        }
+        let node = unsafe { window.curr.deref() };
+        // Relaxed, since the lock of the node is held. The store releases the next node again, to
+        // the traversals that reach it through `pred`.
+        let next = node.link.next.load(Ordering::Relaxed, guard);
+        window.pred.next.store(next, Ordering::Release);
+        let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+        // Threads waiting for the lock of the node are pinned, so it outlives them. They fail to
+        // validate, since the node is no longer reachable.
+        unsafe { guard.defer_destroy(window.curr) };
+        drop(window);
+        // Cloned after the locks are released, so that a panicking `Clone` leaves the node
+        // retired. The node is not destroyed before `guard` is unpinned.
+        Some(node.data.clone())
+    }
+}
+
+/// An iterator over the elements of an [`OptimisticListSet`], in increasing order.
+#[derive(Debug)]
+pub struct Iter<'g, T> {
+    curr: Shared<'g, Node<T>>,
+    guard: &'g Guard,
+}
+
+impl<'g, T> Iterator for Iter<'g, T> {
+    type Item = &'g T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = unsafe { self.curr.as_ref() }?;
+        self.curr = node.link.next.load(Ordering::Acquire, self.guard);
+        Some(&node.data)
+    }
+}
+
+impl<T> Drop for OptimisticListSet<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let guard = unprotected();
+            let mut curr = self.head.next.load(Ordering::Relaxed, guard);
+            while !curr.is_null() {
+                let next = curr.deref().link.next.load(Ordering::Relaxed, guard);
+                drop(curr.into_owned());
+                curr = next;
+            }
+        }
+    }
+}
+
+impl<T> Default for OptimisticListSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,428 @@
+//! Tests shared by the list sets.
+
+// Each test crate uses only some of the tests.
+#![allow(dead_code)]
+
+use crossbeam_utils::thread;
+use cs431_homework::LockSet;
+use rand::distributions::Alphanumeric;
+use rand::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering::{Acquire, Release},
+};
+
+/// A concurrent set under test.
+pub trait ConcurrentSet<T>: Default + Sync {
+    /// Returns `true` if the set contains the key.
+    fn contains(&self, key: &T) -> bool;
+
+    /// Inserts the key, and returns `true` if it was not in the set.
+    fn insert(&self, key: T) -> bool;
+
+    /// Removes the key, and returns `true` if it was in the set.
+    fn remove(&self, key: &T) -> bool;
+
+    /// Returns the number of elements in the set.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if the set has no element.
+    fn is_empty(&self) -> bool;
+
+    /// Returns the elements in the order of an iteration.
+    fn to_vec(&self) -> Vec<T>;
+}
+
+/// A set of strings that is also looked up by `&str`.
+pub trait BorrowSet: ConcurrentSet<String> {
+    /// Returns `true` if the set contains the key.
+    fn contains_str(&self, key: &str) -> bool;
+
+    /// Removes the key, and returns `true` if it was in the set.
+    fn remove_str(&self, key: &str) -> bool;
+}
+
+/// Runs each operation once.
+pub fn smoke<S: ConcurrentSet<i32>>() {
+    let set = S::default();
+    assert!(set.is_empty());
+    assert!(set.insert(1));
+    assert!(set.insert(3));
+    assert!(set.insert(2));
+    assert!(!set.insert(2));
+    assert_eq!(set.len(), 3);
+    assert!(set.remove(&2));
+    assert!(!set.remove(&2));
+    assert!(set.contains(&1));
+    assert!(!set.contains(&2));
+    assert_eq!(set.to_vec(), vec![1, 3]);
+    assert!(set.remove(&1));
+    assert!(set.remove(&3));
+    assert!(set.is_empty());
+    assert_eq!(set.len(), 0);
+}
+
+/// Looks up `String` keys by `&str`.
+pub fn borrowed_keys<S: BorrowSet>() {
+    let set = S::default();
+    for key in &["b", "d", "a"] {
+        assert!(set.insert(key.to_string()));
+    }
+    assert!(set.contains_str("a"));
+    assert!(!set.contains_str("c"));
+    assert!(set.remove_str("b"));
+    assert!(set.contains(&"d".to_string()));
+}
+
+/// Removes every key on many threads at once.
+pub fn remove_head_concurrent<S: ConcurrentSet<usize>>() {
+    const KEYS: usize = if cfg!(miri) { 32 } else { 1024 };
+
+    // Every thread removes the current minimum, so they all contend on the head.
+    let set = S::default();
+    for k in 0..KEYS {
+        assert!(set.insert(k));
+    }
+    let mut removed = thread::scope(|s| {
+        let handles = (0..4)
+            .map(|_| s.spawn(|_| (0..KEYS).filter(|k| set.remove(k)).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect::<Vec<_>>()
+    })
+    .unwrap();
+
+    removed.sort_unstable();
+    assert_eq!(removed, (0..KEYS).collect::<Vec<_>>());
+    assert!(set.is_empty());
+}
+
+/// Panics when the element `POISON` is cloned.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Touchy(usize);
+
+const POISON: usize = 13;
+
+impl Clone for Touchy {
+    fn clone(&self) -> Self {
+        if self.0 == POISON {
+            panic!("cloned the poison");
+        }
+        Self(self.0)
+    }
+}
+
+/// Checks that a panic in the clone returned by `remove` leaves the set usable.
+pub fn panicking_clone<S: ConcurrentSet<Touchy>>() {
+    let set = S::default();
+    for k in 0..POISON * 2 {
+        assert!(set.insert(Touchy(k)));
+    }
+
+    // the element is removed before the clone panics
+    let set = &set;
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| set.remove(&Touchy(POISON)))).is_err());
+    assert!(!set.contains(&Touchy(POISON)));
+    assert_eq!(set.len(), POISON * 2 - 1);
+
+    // the neighbours are not left locked
+    assert!(set.remove(&Touchy(POISON - 1)));
+    assert!(set.remove(&Touchy(POISON + 1)));
+    assert!(set.insert(Touchy(POISON)));
+    for k in 0..POISON * 2 {
+        assert_eq!(set.contains(&Touchy(k)), k != POISON - 1 && k != POISON + 1);
+    }
+}
+
+/// Checks random operations against a `LockSet`, on a single thread.
+pub fn stress_sequential<S: ConcurrentSet<String>>() {
+    #[derive(Debug)]
+    enum Ops {
+        ContainsSome,
+        ContainsNone,
+        Insert,
+        RemoveSome,
+        RemoveNone,
+        Iterate,
+    }
+
+    let ops = [
+        Ops::ContainsSome,
+        Ops::ContainsNone,
+        Ops::Insert,
+        Ops::RemoveSome,
+        Ops::RemoveNone,
+        Ops::Iterate,
+    ];
+    let mut rng = thread_rng();
+    let set = S::default();
+    let oracle = LockSet::<String>::new();
+
+    const OPS: usize = if cfg!(miri) { 256 } else { 4096 };
+
+    for i in 0..OPS {
+        let op = ops.choose(&mut rng).unwrap();
+
+        match op {
+            Ops::ContainsSome => {
+                if let Some(key) = oracle.snapshot().into_iter().choose(&mut rng) {
+                    println!("iteration {}: contains({:?}) (existing)", i, key);
+                    assert_eq!(set.contains(&key), oracle.contains(&key));
+                }
+            }
+            Ops::ContainsNone => {
+                let key = generate_random_string(&mut rng);
+                println!("iteration {}: contains({:?}) (non-existing)", i, key);
+                assert_eq!(set.contains(&key), oracle.contains(&key));
+            }
+            Ops::Insert => {
+                let key = generate_random_string(&mut rng);
+                println!("iteration {}: insert({:?})", i, key);
+                assert_eq!(set.insert(key.clone()), oracle.insert(key).is_ok());
+            }
+            Ops::RemoveSome => {
+                if let Some(key) = oracle.snapshot().into_iter().choose(&mut rng) {
+                    println!("iteration {}: remove({:?}) (existing)", i, key);
+                    assert_eq!(set.remove(&key), oracle.remove(&key).is_some());
+                }
+            }
+            Ops::RemoveNone => {
+                let key = generate_random_string(&mut rng);
+                println!("iteration {}: remove({:?}) (non-existing)", i, key);
+                assert_eq!(set.remove(&key), oracle.remove(&key).is_some());
+            }
+            Ops::Iterate => {
+                let result = set.to_vec();
+                println!("iteration {}: iter() → {:?}", i, result);
+                assert_eq!(result, oracle.snapshot());
+            }
+        }
+    }
+}
+
+const THREADS: usize = if cfg!(miri) { 4 } else { 16 };
+const STEPS: usize = if cfg!(miri) { 64 } else { 4096 * 8 };
+
+fn generate_random_string(rng: &mut ThreadRng) -> String {
+    rng.sample_iter(&Alphanumeric)
+        .take(1)
+        .map(|x| x as char)
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Ops {
+    Contains,
+    Insert,
+    Remove,
+}
+
+#[derive(Debug, Clone)]
+enum Log {
+    Contains { key: String, result: bool },
+    Insert { key: String, result: bool },
+    Remove { key: String, result: bool },
+}
+
+impl Log {
+    fn key(&self) -> &String {
+        match self {
+            Self::Contains { key, .. } => key,
+            Self::Insert { key, .. } => key,
+            Self::Remove { key, .. } => key,
+        }
+    }
+}
+
+/// Runs random operations on many threads.
+pub fn stress_concurrent<S: ConcurrentSet<String>>() {
+    let ops = [Ops::Contains, Ops::Insert, Ops::Remove, Ops::Remove];
+
+    let set = S::default();
+
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                let mut rng = thread_rng();
+                for _ in 0..STEPS {
+                    let op = ops.choose(&mut rng).unwrap();
+
+                    match op {
+                        Ops::Contains => {
+                            let value = generate_random_string(&mut rng);
+                            let _ = set.contains(&value);
+                        }
+                        Ops::Insert => {
+                            let value = generate_random_string(&mut rng);
+                            let _ = set.insert(value);
+                        }
+                        Ops::Remove => {
+                            let value = generate_random_string(&mut rng);
+                            let _ = set.remove(&value);
+                        }
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+}
+
+fn assert_logs_consistent(logs: &Vec<Vec<Log>>) {
+    let mut per_key_logs = HashMap::<String, Vec<Log>>::new();
+    for ls in logs {
+        for l in ls {
+            per_key_logs
+                .entry(l.key().clone())
+                .or_default()
+                .push(l.clone());
+        }
+    }
+
+    for (k, logs) in &per_key_logs {
+        let mut inserts = HashMap::<String, usize>::new();
+        let mut deletes = HashMap::<String, usize>::new();
+
+        for l in logs {
+            match l {
+                Log::Insert { result: true, .. } => *inserts.entry(k.clone()).or_insert(0) += 1,
+                Log::Remove { result: true, .. } => *deletes.entry(k.clone()).or_insert(0) += 1,
+                _ => (),
+            }
+        }
+
+        for l in logs {
+            if let Log::Contains { key, result: true } = l {
+                assert!(inserts.contains_key(key));
+            }
+        }
+
+        for (k, v) in &deletes {
+            assert!(inserts.get(k).unwrap() >= v);
+        }
+    }
+}
+
+/// Runs random operations on many threads, and checks that their results are consistent.
+pub fn log_concurrent<S: ConcurrentSet<String>>() {
+    let ops = [Ops::Contains, Ops::Insert, Ops::Remove];
+
+    const THREADS: usize = if cfg!(miri) { 4 } else { 16 };
+    const STEPS: usize = if cfg!(miri) { 64 } else { 4096 * 12 };
+
+    let set = S::default();
+
+    let logs = thread::scope(|s| {
+        let mut handles = Vec::new();
+        for _ in 0..THREADS {
+            let handle = s.spawn(|_| {
+                let mut rng = thread_rng();
+                let mut logs = Vec::new();
+                for _ in 0..STEPS {
+                    let op = ops.choose(&mut rng).unwrap();
+
+                    match op {
+                        Ops::Contains => {
+                            let key = generate_random_string(&mut rng);
+                            let result = set.contains(&key);
+                            logs.push(Log::Contains {
+                                key: key.clone(),
+                                result,
+                            });
+                        }
+                        Ops::Insert => {
+                            let key = generate_random_string(&mut rng);
+                            let result = set.insert(key.clone());
+                            logs.push(Log::Insert { key, result });
+                        }
+                        Ops::Remove => {
+                            let key = generate_random_string(&mut rng);
+                            let result = set.remove(&key);
+                            logs.push(Log::Remove {
+                                key: key.clone(),
+                                result,
+                            });
+                        }
+                    }
+                }
+                logs
+            });
+            handles.push(handle);
+        }
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<_>>()
+    })
+    .unwrap();
+
+    assert_logs_consistent(&logs);
+
+    // The keys left are those inserted once more than they were removed.
+    let mut net = HashMap::<String, isize>::new();
+    for l in logs.iter().flatten() {
+        match l {
+            Log::Insert { key, result: true } => *net.entry(key.clone()).or_insert(0) += 1,
+            Log::Remove { key, result: true } => *net.entry(key.clone()).or_insert(0) -= 1,
+            _ => (),
+        }
+    }
+    let oracle = LockSet::new();
+    for (key, n) in net {
+        assert!(n == 0 || n == 1);
+        if n == 1 {
+            oracle.insert(key).unwrap();
+        }
+    }
+    assert_eq!(set.to_vec(), oracle.snapshot());
+}
+
+/// Checks that iterations racing with modifications yield increasing elements, including
+/// those never modified.
+pub fn iter_consistent<S: ConcurrentSet<usize>>() {
+    const THREADS: usize = if cfg!(miri) { 3 } else { 15 };
+    const STEPS: usize = if cfg!(miri) { 64 } else { 4096 * 12 };
+
+    let set = S::default();
+
+    // pre-fill with even numbers
+    for i in (0..100).step_by(2).rev() {
+        let _ = set.insert(i);
+    }
+    let evens = set.to_vec().into_iter().collect::<HashSet<_>>();
+
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        // insert or remove odd numbers
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                let mut rng = thread_rng();
+                for _ in 0..STEPS {
+                    let key = 2 * rng.gen_range(0..50) + 1;
+                    if rng.gen() {
+                        let _ = set.insert(key);
+                    } else {
+                        let _ = set.remove(&key);
+                    }
+                }
+                done.store(true, Release);
+            });
+        }
+        // iterator consistency check
+        s.spawn(|_| {
+            while !done.load(Acquire) {
+                let snapshot = set.to_vec();
+                // sorted
+                assert!(snapshot.windows(2).all(|k| k[0] <= k[1]));
+                // even numbers are not touched
+                let snapshot = snapshot.into_iter().collect::<HashSet<_>>();
+                assert!(evens.is_subset(&snapshot));
+            }
+        });
+    })
+    .unwrap();
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use common::{BorrowSet, ConcurrentSet};
+use crossbeam_epoch::pin;
+use crossbeam_utils::thread;
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering::{Acquire, Release},
+};
+
+use cs431_homework::HarrisListSet;
+
+impl<T: Ord + Clone + Send + Sync> ConcurrentSet<T> for HarrisListSet<T> {
+    fn contains(&self, key: &T) -> bool {
+        HarrisListSet::contains(self, key)
+    }
+
+    fn insert(&self, key: T) -> bool {
+        HarrisListSet::insert(self, key).is_ok()
+    }
+
+    fn remove(&self, key: &T) -> bool {
+        HarrisListSet::remove(self, key).is_some()
+    }
+
+    fn len(&self) -> usize {
+        HarrisListSet::len(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        HarrisListSet::is_empty(self)
+    }
+
+    fn to_vec(&self) -> Vec<T> {
+        self.iter(&pin()).cloned().collect()
+    }
+}
+
+impl BorrowSet for HarrisListSet<String> {
+    fn contains_str(&self, key: &str) -> bool {
+        HarrisListSet::contains(self, key)
+    }
+
+    fn remove_str(&self, key: &str) -> bool {
+        HarrisListSet::remove(self, key).is_some()
+    }
+}
+
+#[test]
+fn smoke() {
+    common::smoke::<HarrisListSet<_>>();
+}
+
+#[test]
+fn borrowed_keys() {
+    common::borrowed_keys::<HarrisListSet<_>>();
+}
+
+#[test]
+fn remove_head_concurrent() {
+    common::remove_head_concurrent::<HarrisListSet<_>>();
+}
+
+#[test]
+fn reinsert() {
+    let set = HarrisListSet::new();
+    for _ in 0..4 {
+        set.insert(1).unwrap();
+        set.insert(0).unwrap();
+        assert_eq!(set.remove(&1), Some(1));
+        assert!(!set.contains(&1));
+        assert_eq!(set.remove(&0), Some(0));
+    }
+    assert!(set.is_empty());
+    assert_eq!(set.len(), 0);
+}
+
+#[test]
+fn reinsert_concurrent() {
+    const KEYS: usize = 4;
+    const STEPS: usize = if cfg!(miri) { 32 } else { 4096 };
+
+    // Every thread removes and reinserts the same few keys, so the links to them keep changing
+    // back to an earlier value. Each remove that succeeds is made up for by the following insert.
+    let set = HarrisListSet::new();
+    for k in 0..KEYS {
+        set.insert(k).unwrap();
+    }
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        for t in 0..4 {
+            let (set, done) = (&set, &done);
+            s.spawn(move |_| {
+                for i in 0..STEPS {
+                    let key = (t + i) % KEYS;
+                    if set.remove(&key).is_some() {
+                        set.insert(key).unwrap();
+                    }
+                }
+                done.store(true, Release);
+            });
+        }
+        s.spawn(|_| {
+            while !done.load(Acquire) {
+                let elems = set.to_vec();
+                assert!(elems.windows(2).all(|w| w[0] < w[1]));
+            }
+        });
+    })
+    .unwrap();
+
+    assert_eq!(set.to_vec(), (0..KEYS).collect::<Vec<_>>());
+    assert_eq!(set.len(), KEYS);
+}
+
+#[test]
+fn insert_between_reinserts() {
+    const STEPS: usize = if cfg!(miri) { 32 } else { 4096 };
+
+    // Inserts next to a key that is removed and reinserted concurrently land exactly once, never
+    // after a removed node.
+    let set = HarrisListSet::new();
+    set.insert(0).unwrap();
+    thread::scope(|s| {
+        s.spawn(|_| {
+            for _ in 0..STEPS {
+                let key = set.remove(&0).unwrap();
+                set.insert(key).unwrap();
+            }
+        });
+        s.spawn(|_| {
+            for k in 1..=STEPS {
+                set.insert(k).unwrap();
+            }
+        });
+    })
+    .unwrap();
+
+    assert_eq!(set.to_vec(), (0..=STEPS).collect::<Vec<_>>());
+    assert_eq!(set.len(), STEPS + 1);
+}
+
+#[test]
+fn stress_sequential() {
+    common::stress_sequential::<HarrisListSet<_>>();
+}
+
+#[test]
+fn stress_concurrent() {
+    common::stress_concurrent::<HarrisListSet<_>>();
+}
+
+#[test]
+fn log_concurrent() {
+    common::log_concurrent::<HarrisListSet<_>>();
+}
+
+#[test]
+fn iter_consistent() {
+    common::iter_consistent::<HarrisListSet<_>>();
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use common::{BorrowSet, ConcurrentSet};
+use crossbeam_epoch::pin;
+use crossbeam_utils::thread;
+use rand::prelude::*;
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering::{Acquire, Release},
+};
+
+use cs431_homework::LazyListSet;
+
+impl<T: Ord + Clone + Send + Sync> ConcurrentSet<T> for LazyListSet<T> {
+    fn contains(&self, key: &T) -> bool {
+        LazyListSet::contains(self, key)
+    }
+
+    fn insert(&self, key: T) -> bool {
+        LazyListSet::insert(self, key).is_ok()
+    }
+
+    fn remove(&self, key: &T) -> bool {
+        LazyListSet::remove(self, key).is_some()
+    }
+
+    fn len(&self) -> usize {
+        LazyListSet::len(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        LazyListSet::is_empty(self)
+    }
+
+    fn to_vec(&self) -> Vec<T> {
+        self.iter(&pin()).cloned().collect()
+    }
+}
+
+impl BorrowSet for LazyListSet<String> {
+    fn contains_str(&self, key: &str) -> bool {
+        LazyListSet::contains(self, key)
+    }
+
+    fn remove_str(&self, key: &str) -> bool {
+        LazyListSet::remove(self, key).is_some()
+    }
+}
+
+#[test]
+fn smoke() {
+    common::smoke::<LazyListSet<_>>();
+}
+
+#[test]
+fn borrowed_keys() {
+    common::borrowed_keys::<LazyListSet<_>>();
+}
+
+#[test]
+fn remove_head_concurrent() {
+    common::remove_head_concurrent::<LazyListSet<_>>();
+}
+
+#[test]
+fn contains_concurrent() {
+    const THREADS: usize = if cfg!(miri) { 3 } else { 8 };
+    const STEPS: usize = if cfg!(miri) { 64 } else { 4096 * 4 };
+
+    // Lookups of the even keys never miss, while their neighbours are inserted and removed.
+    let set = LazyListSet::new();
+    for i in (0..100).step_by(2) {
+        set.insert(i).unwrap();
+    }
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                let mut rng = thread_rng();
+                for _ in 0..STEPS {
+                    let key = 2 * rng.gen_range(0..50) + 1;
+                    if rng.gen() {
+                        let _ = set.insert(key);
+                    } else {
+                        let _ = set.remove(&key);
+                    }
+                }
+                done.store(true, Release);
+            });
+        }
+        s.spawn(|_| {
+            let mut rng = thread_rng();
+            while !done.load(Acquire) {
+                assert!(set.contains(&(2 * rng.gen_range(0..50))));
+            }
+        });
+    })
+    .unwrap();
+}
+
+#[test]
+fn panicking_clone() {
+    common::panicking_clone::<LazyListSet<_>>();
+}
+
+#[test]
+fn stress_sequential() {
+    common::stress_sequential::<LazyListSet<_>>();
+}
+
+#[test]
+fn stress_concurrent() {
+    common::stress_concurrent::<LazyListSet<_>>();
+}
+
+#[test]
+fn log_concurrent() {
+    common::log_concurrent::<LazyListSet<_>>();
+}
+
+#[test]
+fn iter_consistent() {
+    common::iter_consistent::<LazyListSet<_>>();
+}
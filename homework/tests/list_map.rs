@@ -0,0 +1,201 @@
+mod common;
+
+use common::ConcurrentSet;
+use crossbeam_utils::thread;
+use rand::prelude::*;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+use cs431_homework::OrderedListMap;
+
+impl<T: Ord + Clone + Send + Sync> ConcurrentSet<T> for OrderedListMap<T, ()> {
+    fn contains(&self, key: &T) -> bool {
+        self.contains_key(key)
+    }
+
+    fn insert(&self, key: T) -> bool {
+        OrderedListMap::insert(self, key, ()).is_none()
+    }
+
+    fn remove(&self, key: &T) -> bool {
+        OrderedListMap::remove(self, key).is_some()
+    }
+
+    fn len(&self) -> usize {
+        OrderedListMap::len(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        OrderedListMap::is_empty(self)
+    }
+
+    fn to_vec(&self) -> Vec<T> {
+        self.iter().map(|(k, _)| k).collect()
+    }
+}
+
+#[test]
+fn smoke() {
+    let map = OrderedListMap::new();
+    assert!(map.is_empty());
+    assert_eq!(map.insert(1, "a"), None);
+    assert_eq!(map.insert(3, "c"), None);
+    assert_eq!(map.insert(2, "b"), None);
+    assert_eq!(map.len(), 3);
+    assert!(map.contains_key(&2));
+    assert_eq!(map.get_with(&2, |v| v.to_uppercase()).as_deref(), Some("B"));
+    assert_eq!(map.get_with(&4, |_| unreachable!()), None::<()>);
+    assert_eq!(map.remove(&2), Some("b"));
+    assert_eq!(map.remove(&2), None);
+    assert!(!map.contains_key(&2));
+    assert_eq!(map.iter().collect::<Vec<_>>(), [(1, "a"), (3, "c")]);
+    assert_eq!(map.remove(&1), Some("a"));
+    assert_eq!(map.remove(&3), Some("c"));
+    assert!(map.is_empty());
+    assert_eq!(map.len(), 0);
+}
+
+#[test]
+fn insert_replaces() {
+    let map = OrderedListMap::new();
+    assert_eq!(map.insert("k", 1), None);
+    assert_eq!(map.insert("k", 2), Some(1));
+    assert_eq!(map.get_with(&"k", |v| *v), Some(2));
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn update() {
+    let map = (0..4).fold(OrderedListMap::new(), |map, i| {
+        let _ = map.insert(i, vec![i]);
+        map
+    });
+    assert_eq!(map.update(&2, |v| v.push(20)), Some(()));
+    assert_eq!(map.update(&3, |v| v.len()), Some(1));
+    assert_eq!(map.update(&4, |_| unreachable!()), None::<()>);
+    assert_eq!(
+        map.iter().collect::<Vec<_>>(),
+        [(0, vec![0]), (1, vec![1]), (2, vec![2, 20]), (3, vec![3])]
+    );
+}
+
+#[test]
+fn debug() {
+    let map = OrderedListMap::new();
+    assert_eq!(format!("{:?}", map), "{}");
+    let _ = map.insert(2, "b");
+    let _ = map.insert(1, "a");
+    assert_eq!(format!("{:?}", map), r#"{1: "a", 2: "b"}"#);
+}
+
+struct DropCounter<'a>(&'a AtomicUsize);
+
+impl Drop for DropCounter<'_> {
+    fn drop(&mut self) {
+        let _ = self.0.fetch_add(1, Relaxed);
+    }
+}
+
+#[test]
+fn drop_values() {
+    let dropped = AtomicUsize::new(0);
+    let map = OrderedListMap::new();
+    for i in 0..4 {
+        assert!(map.insert(i, DropCounter(&dropped)).is_none());
+    }
+    drop(map.insert(0, DropCounter(&dropped)));
+    drop(map.remove(&1));
+    assert_eq!(dropped.load(Relaxed), 2);
+    drop(map);
+    assert_eq!(dropped.load(Relaxed), 5);
+}
+
+#[test]
+fn stress_map_sequential() {
+    const OPS: usize = if cfg!(miri) { 256 } else { 4096 };
+
+    let mut rng = thread_rng();
+    let map = OrderedListMap::new();
+    let mut btree = BTreeMap::new();
+    for _ in 0..OPS {
+        let key = rng.gen_range(0..64);
+        match rng.gen_range(0..4) {
+            0 => {
+                let value = rng.gen::<u32>();
+                assert_eq!(map.insert(key, value), btree.insert(key, value));
+            }
+            1 => assert_eq!(map.remove(&key), btree.remove(&key)),
+            2 => assert_eq!(
+                map.update(&key, increment),
+                btree.get_mut(&key).map(increment)
+            ),
+            _ => assert_eq!(map.get_with(&key, |v| *v), btree.get(&key).copied()),
+        }
+        assert_eq!(map.len(), btree.len());
+    }
+    assert_eq!(
+        map.iter().collect::<Vec<_>>(),
+        btree.into_iter().collect::<Vec<_>>()
+    );
+}
+
+/// Increments the value and returns the old one.
+fn increment(v: &mut u32) -> u32 {
+    let old = *v;
+    *v = old.wrapping_add(1);
+    old
+}
+
+#[test]
+fn update_concurrent() {
+    const THREADS: usize = if cfg!(miri) { 2 } else { 4 };
+    const KEYS: usize = 8;
+    const STEPS: usize = if cfg!(miri) { 32 } else { 1024 };
+
+    // counters updated by every thread, while others come and go between them
+    let map = OrderedListMap::new();
+    for k in 0..KEYS {
+        let _ = map.insert(k * 2, 0);
+    }
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                let mut rng = thread_rng();
+                for _ in 0..STEPS {
+                    for k in 0..KEYS {
+                        assert!(map.update(&(k * 2), |v| *v += 1).is_some());
+                    }
+                    let other = rng.gen_range(0..KEYS) * 2 + 1;
+                    if map.insert(other, 0).is_some() {
+                        let _ = map.remove(&other);
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    for k in 0..KEYS {
+        assert_eq!(map.get_with(&(k * 2), |v| *v), Some(THREADS * STEPS));
+    }
+}
+
+#[test]
+fn stress_sequential() {
+    common::stress_sequential::<OrderedListMap<_, ()>>();
+}
+
+#[test]
+fn stress_concurrent() {
+    common::stress_concurrent::<OrderedListMap<_, ()>>();
+}
+
+#[test]
+fn log_concurrent() {
+    common::log_concurrent::<OrderedListMap<_, ()>>();
+}
+
+#[test]
+fn iter_consistent() {
+    common::iter_consistent::<OrderedListMap<_, ()>>();
+}
@@ -1,317 +1,2180 @@
+mod common;
+
+use common::ConcurrentSet;
 use crossbeam_utils::thread;
-use rand::distributions::Alphanumeric;
 use rand::prelude::*;
-use std::collections::{HashMap, HashSet};
+use std::cell::Cell;
+use std::cmp;
+use std::collections::{BTreeSet, HashSet};
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::atomic::{
-    AtomicBool,
-    Ordering::{Acquire, Release},
+    AtomicBool, AtomicUsize,
+    Ordering::{Acquire, Relaxed, Release},
 };
+use std::sync::{mpsc, Barrier};
+use std::time::{Duration, Instant};
 
 use cs431_homework::OrderedListSet;
 
+impl<T: Ord + Clone + Send + Sync> ConcurrentSet<T> for OrderedListSet<T> {
+    fn contains(&self, key: &T) -> bool {
+        OrderedListSet::contains(self, key)
+    }
+
+    fn insert(&self, key: T) -> bool {
+        OrderedListSet::insert(self, key).is_ok()
+    }
+
+    fn remove(&self, key: &T) -> bool {
+        OrderedListSet::remove(self, key).is_some()
+    }
+
+    fn len(&self) -> usize {
+        OrderedListSet::len(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        OrderedListSet::is_empty(self)
+    }
+
+    fn to_vec(&self) -> Vec<T> {
+        self.iter().map(|k| k.clone()).collect()
+    }
+}
+
 #[test]
 fn smoke() {
     let set = OrderedListSet::new();
     set.insert(1).unwrap();
     set.insert(2).unwrap();
     set.insert(3).unwrap();
-    assert_eq!(set.remove(&2), Ok(2));
+    assert_eq!(set.remove(&2), Some(2));
     for i in set.iter() {
-        println!("{}", i);
+        println!("{}", *i);
     }
-    assert_eq!(set.remove(&3), Ok(3));
+    assert_eq!(set.remove(&3), Some(3));
 }
 
 #[test]
-fn parallel_iter_end() {
+fn remove_edges() {
     let set = OrderedListSet::new();
+    assert_eq!(set.remove(&1), None);
+
+    // the only element
+    set.insert(1).unwrap();
+    assert_eq!(set.remove(&1), Some(1));
+    assert!(set.is_empty());
+
     set.insert(1).unwrap();
     set.insert(2).unwrap();
-    let mut iter = set.iter();
-    iter.next();
-    iter.next();
-    iter.next();
+    set.insert(3).unwrap();
+    // a missing key larger than everything
+    assert_eq!(set.remove(&4), None);
+    // a missing key smaller than everything
+    assert_eq!(set.remove(&0), None);
+    // the last element
+    assert_eq!(set.remove(&3), Some(3));
+    assert_eq!(set.iter().map(|k| *k).collect::<Vec<_>>(), vec![1, 2]);
+    assert_eq!(set.remove(&3), None);
+    assert_eq!(set.len(), 2);
+}
+
+#[test]
+fn iter_range() {
+    let set = OrderedListSet::new();
+    let mut oracle = BTreeSet::new();
+    for i in (0..20).step_by(2) {
+        set.insert(i).unwrap();
+        let _ = oracle.insert(i);
+    }
+
+    // empty ranges, ranges outside of the list, and bounds equal to elements
+    for &(lo, hi) in &[
+        (5, 5),
+        (6, 2),
+        (-5, -1),
+        (-5, 0),
+        (18, 30),
+        (19, 30),
+        (4, 10),
+        (3, 11),
+    ] {
+        assert_eq!(
+            set.iter_range(&lo, &hi).map(|k| *k).collect::<Vec<_>>(),
+            if lo < hi {
+                oracle.range(lo..hi).copied().collect::<Vec<_>>()
+            } else {
+                Vec::new()
+            },
+            "range [{}, {})",
+            lo,
+            hi
+        );
+    }
+
+    let mut rng = thread_rng();
+    for _ in 0..100 {
+        let (lo, hi) = (rng.gen_range(-2..22), rng.gen_range(-2..22));
+        if lo < hi {
+            assert_eq!(
+                set.iter_range(&lo, &hi).map(|k| *k).collect::<Vec<_>>(),
+                oracle.range(lo..hi).copied().collect::<Vec<_>>()
+            );
+        }
+    }
+}
+
+#[test]
+fn iter_range_releases() {
+    let set = OrderedListSet::new();
+    for i in 0..10 {
+        set.insert(i).unwrap();
+    }
+    let mut iter = set.iter_range(&2, &4);
+    assert_eq!(iter.next().map(|k| *k), Some(2));
+    assert_eq!(iter.next().map(|k| *k), Some(3));
+    assert!(iter.next().is_none());
     thread::scope(|s| {
         s.spawn(|_| {
-            // this shouldn't block
-            let _ = set.iter().collect::<Vec<_>>();
+            // the tail is not locked by the finished iterator
+            assert_eq!(set.remove(&4), Some(4));
+            set.insert(10).unwrap();
         });
     })
     .unwrap();
     drop(iter);
 }
 
+/// Ordered by `key` only, with a payload that is not `Clone`.
+#[derive(Debug)]
+struct Entry {
+    key: usize,
+    payload: Vec<usize>,
+}
+
+impl Entry {
+    fn new(key: usize) -> Self {
+        Self {
+            key,
+            payload: vec![key; 4],
+        }
+    }
+
+    fn key(key: usize) -> Self {
+        Self {
+            key,
+            payload: Vec::new(),
+        }
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
 #[test]
-fn stress_sequential() {
-    #[derive(Debug)]
-    enum Ops {
-        ContainsSome,
-        ContainsNone,
-        Insert,
-        RemoveSome,
-        RemoveNone,
-        Iterate,
-    }
-
-    let ops = [
-        Ops::ContainsSome,
-        Ops::ContainsNone,
-        Ops::Insert,
-        Ops::RemoveSome,
-        Ops::RemoveNone,
-        Ops::Iterate,
-    ];
-    let mut rng = thread_rng();
-    let set = OrderedListSet::default();
-    let mut hashset = HashSet::<String>::new();
+fn read() {
+    let set = OrderedListSet::new();
+    set.insert(Entry::new(1)).unwrap();
+    set.insert(Entry::new(3)).unwrap();
 
-    const OPS: usize = 4096;
+    assert_eq!(
+        set.read(&Entry::key(1), |e| e.payload.clone()),
+        Some(vec![1; 4])
+    );
+    assert_eq!(
+        set.read(&Entry::key(3), |e| e.payload.iter().sum()),
+        Some(12)
+    );
+    assert_eq!(set.read(&Entry::key(2), |e| e.payload.len()), None);
+    assert_eq!(set.read(&Entry::key(4), |e| e.payload.len()), None);
+}
 
-    for i in 0..OPS {
-        let op = ops.choose(&mut rng).unwrap();
+#[test]
+fn read_concurrent_remove() {
+    const KEYS: usize = if cfg!(miri) { 16 } else { 1024 };
 
-        match op {
-            Ops::ContainsSome => {
-                if let Some(key) = hashset.iter().choose(&mut rng) {
-                    println!("iteration {}: contains({:?}) (existing)", i, key);
-                    assert_eq!(set.contains(key), hashset.contains(key));
-                }
-            }
-            Ops::ContainsNone => {
-                let key = generate_random_string(&mut rng);
-                println!("iteration {}: contains({:?}) (non-existing)", i, key);
-                assert_eq!(set.contains(&key), hashset.contains(&key));
-            }
-            Ops::Insert => {
-                let key = generate_random_string(&mut rng);
-                println!("iteration {}: insert({:?})", i, key);
-                assert_eq!(set.insert(key.clone()).is_ok(), hashset.insert(key));
+    let set = OrderedListSet::new();
+    for i in 0..KEYS {
+        set.insert(Entry::new(i)).unwrap();
+    }
+    thread::scope(|s| {
+        s.spawn(|_| {
+            for i in 0..KEYS {
+                assert!(set.remove(&Entry::key(i)).is_some());
             }
-            Ops::RemoveSome => {
-                let key = hashset.iter().choose(&mut rng).map(Clone::clone);
-                if let Some(key) = key {
-                    println!("iteration {}: remove({:?}) (existing)", i, key);
-                    assert_eq!(set.remove(&key).is_ok(), hashset.remove(&key));
+        });
+        // an element read before its removal is intact
+        s.spawn(|_| {
+            for i in 0..KEYS {
+                if let Some(sum) = set.read(&Entry::key(i), |e| e.payload.iter().sum::<usize>()) {
+                    assert_eq!(sum, 4 * i);
                 }
             }
-            Ops::RemoveNone => {
-                let key = generate_random_string(&mut rng);
-                println!("iteration {}: remove({:?}) (non-existing)", i, key);
-                assert_eq!(set.remove(&key).is_ok(), hashset.remove(&key));
-            }
-            Ops::Iterate => {
-                let result = set.iter().map(Clone::clone).collect::<HashSet<_>>();
-                println!("iteration {}: iter() → {:?}", i, result);
-                assert_eq!(result, hashset);
+        });
+    })
+    .unwrap();
+    assert!(set.is_empty());
+}
+
+#[test]
+fn pop_min() {
+    let set = OrderedListSet::new();
+    assert_eq!(set.pop_min(), None);
+    for &i in &[3, 1, 2] {
+        set.insert(i).unwrap();
+    }
+    assert_eq!(set.pop_min(), Some(1));
+    set.insert(0).unwrap();
+    assert_eq!(set.pop_min(), Some(0));
+    assert_eq!(set.pop_min(), Some(2));
+    assert_eq!(set.pop_min(), Some(3));
+    assert_eq!(set.pop_min(), None);
+    assert_eq!(set.len(), 0);
+}
+
+#[test]
+fn pop_min_concurrent() {
+    const PRODUCERS: usize = if cfg!(miri) { 2 } else { 4 };
+    const KEYS: usize = if cfg!(miri) { 32 } else { 4096 };
+
+    let set = OrderedListSet::new();
+    let done = AtomicBool::new(false);
+    let mut popped = thread::scope(|s| {
+        let consumer = s.spawn(|_| {
+            let mut popped = Vec::new();
+            while !done.load(Acquire) {
+                popped.extend(set.pop_min());
             }
+            popped
+        });
+        let producers = (0..PRODUCERS)
+            .map(|t| {
+                let set = &set;
+                s.spawn(move |_| {
+                    // increasing keys with random gaps, tagged with the producer
+                    let mut rng = thread_rng();
+                    let mut key = 0;
+                    for _ in 0..KEYS {
+                        key += rng.gen_range(1..8);
+                        set.insert(key * PRODUCERS + t).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for producer in producers {
+            producer.join().unwrap();
         }
+        done.store(true, Release);
+        consumer.join().unwrap()
+    })
+    .unwrap();
+
+    // keys of a producer are inserted in increasing order, so a key is inserted before any
+    // larger key of the same producer, and the consumer pops them in that order
+    for t in 0..PRODUCERS {
+        let keys = popped.iter().filter(|k| *k % PRODUCERS == t);
+        assert!(keys.clone().zip(keys.skip(1)).all(|(a, b)| a < b));
+    }
+    while let Some(key) = set.pop_min() {
+        popped.push(key);
     }
+    assert_eq!(popped.len(), PRODUCERS * KEYS);
+    popped.sort_unstable();
+    popped.dedup();
+    assert_eq!(popped.len(), PRODUCERS * KEYS);
 }
 
-const THREADS: usize = 16;
-const STEPS: usize = 4096 * 8;
+#[test]
+fn pop_max() {
+    let set = OrderedListSet::new();
+    assert_eq!(set.pop_max(), None);
+
+    // a single element
+    set.insert(1).unwrap();
+    assert_eq!(set.pop_max(), Some(1));
+    assert!(set.is_empty());
+    assert_eq!(set.pop_max(), None);
 
-fn generate_random_string(rng: &mut ThreadRng) -> String {
-    rng.sample_iter(&Alphanumeric)
-        .take(1)
-        .map(|x| x as char)
-        .collect()
+    for &i in &[2, 4, 3] {
+        set.insert(i).unwrap();
+    }
+    assert_eq!(set.pop_max(), Some(4));
+    set.insert(5).unwrap();
+    assert_eq!(set.pop_max(), Some(5));
+    assert_eq!(set.pop_max(), Some(3));
+    assert_eq!(set.pop_max(), Some(2));
+    assert_eq!(set.pop_max(), None);
+    assert_eq!(set.len(), 0);
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Ops {
-    Contains,
-    Insert,
-    Remove,
+#[test]
+fn pop_max_concurrent_max() {
+    const KEYS: usize = if cfg!(miri) { 32 } else { 1024 };
+
+    let set = OrderedListSet::new();
+    set.insert(0).unwrap();
+    let mut popped = thread::scope(|s| {
+        // every insert is a new maximum, racing with the traversal of `pop_max`
+        s.spawn(|_| {
+            for i in 1..=KEYS {
+                set.insert(i).unwrap();
+            }
+        });
+        s.spawn(|_| {
+            (0..KEYS / 2)
+                .filter_map(|_| set.pop_max())
+                .collect::<Vec<_>>()
+        })
+        .join()
+        .unwrap()
+    })
+    .unwrap();
+
+    // nothing is lost, whether an insert landed before or after a removal
+    let rest = set.iter().map(|k| *k).collect::<Vec<_>>();
+    assert_eq!(set.len(), rest.len());
+    popped.extend(rest);
+    popped.sort_unstable();
+    assert_eq!(popped, (0..=KEYS).collect::<Vec<_>>());
 }
 
-#[derive(Debug, Clone)]
-enum Log {
-    Contains { key: String, result: bool },
-    Insert { key: String, result: bool },
-    Remove { key: String, result: bool },
-}
-
-impl Log {
-    fn key(&self) -> &String {
-        match self {
-            Self::Contains { key, .. } => key,
-            Self::Insert { key, .. } => key,
-            Self::Remove { key, .. } => key,
+#[test]
+fn pop_max_stress() {
+    const PRODUCERS: usize = if cfg!(miri) { 2 } else { 4 };
+    const KEYS: usize = if cfg!(miri) { 32 } else { 1024 };
+
+    let set = OrderedListSet::new();
+    let done = AtomicBool::new(false);
+    let mut popped = thread::scope(|s| {
+        let consumer = s.spawn(|_| {
+            let mut popped = Vec::new();
+            while !done.load(Acquire) {
+                popped.extend(set.pop_max());
+            }
+            popped
+        });
+        let producers = (0..PRODUCERS)
+            .map(|t| {
+                let set = &set;
+                s.spawn(move |_| {
+                    for i in 0..KEYS {
+                        set.insert(i * PRODUCERS + t).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for producer in producers {
+            producer.join().unwrap();
         }
+        done.store(true, Release);
+        consumer.join().unwrap()
+    })
+    .unwrap();
+
+    // without producers, the maxima come out in decreasing order
+    let mut rest = Vec::new();
+    while let Some(key) = set.pop_max() {
+        rest.push(key);
     }
+    assert!(rest.windows(2).all(|k| k[0] > k[1]));
+
+    popped.extend(rest);
+    popped.sort_unstable();
+    assert_eq!(popped, (0..PRODUCERS * KEYS).collect::<Vec<_>>());
 }
 
 #[test]
-fn stress_concurrent() {
-    let ops = [Ops::Contains, Ops::Insert, Ops::Remove, Ops::Remove];
-
+fn first_last() {
     let set = OrderedListSet::new();
+    assert_eq!(set.first(), None);
+    assert_eq!(set.last(), None);
+    assert_eq!(set.first_with(|_: &String| unreachable!()), None::<()>);
+    assert_eq!(set.last_with(|_: &String| unreachable!()), None::<()>);
+
+    // a single element
+    set.insert("b".to_string()).unwrap();
+    assert_eq!(set.first().as_deref(), Some("b"));
+    assert_eq!(set.last().as_deref(), Some("b"));
+
+    set.insert("c".to_string()).unwrap();
+    set.insert("a".to_string()).unwrap();
+    assert_eq!(set.first().as_deref(), Some("a"));
+    assert_eq!(set.last().as_deref(), Some("c"));
+    assert_eq!(set.first_with(String::len), Some(1));
+    assert_eq!(set.last_with(|s| s.clone() + "!").as_deref(), Some("c!"));
+    assert_eq!(set.len(), 3);
+}
+
+#[test]
+fn first_last_concurrent_pop_min() {
+    const KEYS: usize = if cfg!(miri) { 32 } else { 4096 };
+
+    const MAX: usize = KEYS - 1;
 
+    let set = (0..KEYS).collect::<OrderedListSet<_>>();
+    let done = AtomicBool::new(false);
     thread::scope(|s| {
-        for _ in 0..THREADS {
+        s.spawn(|_| {
+            while set.pop_min().is_some() {}
+            done.store(true, Release);
+        });
+        for _ in 0..2 {
             s.spawn(|_| {
-                let mut rng = thread_rng();
-                for _ in 0..STEPS {
-                    let op = ops.choose(&mut rng).unwrap();
-
-                    match op {
-                        Ops::Contains => {
-                            let value = generate_random_string(&mut rng);
-                            let _ = set.contains(&value);
-                        }
-                        Ops::Insert => {
-                            let value = generate_random_string(&mut rng);
-                            let _ = set.insert(value);
-                        }
-                        Ops::Remove => {
-                            let value = generate_random_string(&mut rng);
-                            let _ = set.remove(&value);
-                        }
+                // the minima only grow, and the maximum stays until it is the last one left
+                let mut min = 0;
+                while !done.load(Acquire) {
+                    if let Some(first) = set.first() {
+                        assert!(first >= min);
+                        min = first;
                     }
+                    assert!(matches!(set.last(), None | Some(MAX)));
+                    assert!(matches!(set.last_with(|k| *k), None | Some(MAX)));
                 }
             });
         }
     })
     .unwrap();
+    assert_eq!(set.first(), None);
+    assert_eq!(set.last(), None);
 }
 
-fn assert_logs_consistent(logs: &Vec<Vec<Log>>) {
-    let mut per_key_logs = HashMap::<String, Vec<Log>>::new();
-    for ls in logs {
-        for l in ls {
-            per_key_logs
-                .entry(l.key().clone())
-                .or_insert_with(|| Vec::new())
-                .push(l.clone());
+#[test]
+fn retain() {
+    fn retained(keys: &[usize], f: impl FnMut(&usize) -> bool) -> Vec<usize> {
+        let set = OrderedListSet::new();
+        for &k in keys {
+            set.insert(k).unwrap();
         }
+        set.retain(f);
+        assert_eq!(set.len(), set.len_exact());
+        set.iter().map(|k| *k).collect()
     }
 
-    for (k, logs) in &per_key_logs {
-        let mut inserts = HashMap::<String, usize>::new();
-        let mut deletes = HashMap::<String, usize>::new();
-
-        for l in logs {
-            match l {
-                Log::Insert { result: true, .. } => *inserts.entry(k.clone()).or_insert(0) += 1,
-                Log::Remove { result: true, .. } => *deletes.entry(k.clone()).or_insert(0) += 1,
-                _ => (),
-            }
-        }
-
-        for l in logs {
-            match l {
-                Log::Contains { key, result: true } => assert!(inserts.contains_key(key)),
-                _ => (),
-            }
-        }
+    let keys = (0..10).collect::<Vec<_>>();
+    // every other element
+    assert_eq!(retained(&keys, |k| k % 2 == 0), vec![0, 2, 4, 6, 8]);
+    // a run of consecutive elements, at the start, in the middle and at the end
+    assert_eq!(retained(&keys, |k| *k >= 3), vec![3, 4, 5, 6, 7, 8, 9]);
+    assert_eq!(
+        retained(&keys, |k| *k < 3 || *k > 6),
+        vec![0, 1, 2, 7, 8, 9]
+    );
+    assert_eq!(retained(&keys, |k| *k < 7), vec![0, 1, 2, 3, 4, 5, 6]);
+    // everything, and nothing
+    assert_eq!(retained(&keys, |_| false), Vec::<usize>::new());
+    assert_eq!(retained(&keys, |_| true), keys);
+    assert_eq!(retained(&[], |_| false), Vec::<usize>::new());
 
-        for (k, v) in &deletes {
-            assert!(inserts.get(k).unwrap() >= v);
-        }
+    // called once per element, in order
+    let set = OrderedListSet::new();
+    for &k in &keys {
+        set.insert(k).unwrap();
     }
+    let mut seen = Vec::new();
+    set.retain(|k| {
+        seen.push(*k);
+        k % 3 != 0
+    });
+    assert_eq!(seen, keys);
 }
 
 #[test]
-fn log_concurrent() {
-    let ops = [Ops::Contains, Ops::Insert, Ops::Remove];
-
-    const THREADS: usize = 16;
-    const STEPS: usize = 4096 * 12;
+fn retain_concurrent() {
+    const KEYS: usize = if cfg!(miri) { 64 } else { 4096 };
 
     let set = OrderedListSet::new();
-
-    let logs = thread::scope(|s| {
-        let mut handles = Vec::new();
-        for _ in 0..THREADS {
-            let handle = s.spawn(|_| {
-                let mut rng = thread_rng();
-                let mut logs = Vec::new();
-                for _ in 0..STEPS {
-                    let op = ops.choose(&mut rng).unwrap();
-
-                    match op {
-                        Ops::Contains => {
-                            let key = generate_random_string(&mut rng);
-                            let result = set.contains(&key);
-                            logs.push(Log::Contains {
-                                key: key.clone(),
-                                result,
-                            });
-                        }
-                        Ops::Insert => {
-                            let key = generate_random_string(&mut rng);
-                            let result = set.insert(key.clone());
-                            logs.push(Log::Insert {
-                                key,
-                                result: result.is_ok(),
-                            });
-                        }
-                        Ops::Remove => {
-                            let key = generate_random_string(&mut rng);
-                            let result = set.remove(&key);
-                            logs.push(Log::Remove {
-                                key: key.clone(),
-                                result: result.is_ok(),
-                            });
-                        }
-                    }
+    for i in 0..KEYS {
+        set.insert(3 * i + 1).unwrap();
+    }
+    thread::scope(|s| {
+        // inserted keys are never purged
+        for t in 0..2 {
+            let set = &set;
+            s.spawn(move |_| {
+                for i in 0..KEYS {
+                    set.insert(3 * i + t * 2).unwrap();
                 }
-                logs
             });
-            handles.push(handle);
         }
-        handles
-            .into_iter()
-            .map(|h| h.join().unwrap())
-            .collect::<Vec<_>>()
+        s.spawn(|_| set.retain(|k| k % 3 != 1));
     })
     .unwrap();
 
-    assert_logs_consistent(&logs);
+    let rest = set.iter().map(|k| *k).collect::<Vec<_>>();
+    let expected = (0..3 * KEYS).filter(|k| k % 3 != 1).collect::<Vec<_>>();
+    assert_eq!(rest, expected);
+    assert_eq!(set.len(), expected.len());
 }
 
 #[test]
-fn iter_consistent() {
-    const THREADS: usize = 15;
-    const STEPS: usize = 4096 * 12;
+fn extract_if() {
+    let mut rng = thread_rng();
+    for _ in 0..if cfg!(miri) { 4 } else { 64 } {
+        let mut oracle = (0..rng.gen_range(0..32))
+            .map(|_| rng.gen_range(0..64))
+            .collect::<BTreeSet<usize>>();
+        let set = OrderedListSet::from(oracle.clone());
+        // runs of matches, at the head and the tail among them
+        let (period, run) = (rng.gen_range(1..8), rng.gen_range(0..4));
+        let f = |k: &usize| k % period < run;
 
-    let set = OrderedListSet::new();
+        let expected = oracle.iter().copied().filter(f).collect::<Vec<_>>();
+        oracle.retain(|k| !f(k));
+        assert_eq!(set.extract_if(f), expected);
+        assert_eq!(set, oracle.iter().copied().collect::<Vec<_>>());
+        assert_eq!(set.len(), oracle.len());
+    }
+
+    let set = (0..4).collect::<OrderedListSet<_>>();
+    assert_eq!(set.extract_if(|_| false), Vec::<usize>::new());
+    assert_eq!(set.extract_if(|_| true), [0, 1, 2, 3]);
+    assert!(set.is_empty());
+}
+
+#[test]
+fn extract_if_concurrent_remove() {
+    const KEYS: usize = if cfg!(miri) { 64 } else { 4096 };
+
+    // every even key is taken by exactly one of the extraction and the remover
+    let set = (0..KEYS).collect::<OrderedListSet<_>>();
+    let (extracted, removed) = thread::scope(|s| {
+        let remover = s.spawn(|_| {
+            (0..KEYS)
+                .step_by(2)
+                .rev()
+                .filter(|k| set.remove(k).is_some())
+                .collect::<Vec<_>>()
+        });
+        let extracted = s.spawn(|_| set.extract_if(|k| k % 2 == 0));
+        (extracted.join().unwrap(), remover.join().unwrap())
+    })
+    .unwrap();
+
+    let mut all = extracted.into_iter().chain(removed).collect::<Vec<_>>();
+    all.sort_unstable();
+    assert_eq!(all, (0..KEYS).step_by(2).collect::<Vec<_>>());
+    assert_eq!(set, (1..KEYS).step_by(2).collect::<Vec<_>>());
+    assert_eq!(set.len(), KEYS / 2);
+}
+
+/// Ordered by the key, and counts its drops.
+#[derive(Debug, Clone)]
+struct DropCounter<'a>(usize, &'a AtomicUsize);
+
+impl Drop for DropCounter<'_> {
+    fn drop(&mut self) {
+        let _ = self.1.fetch_add(1, Relaxed);
+    }
+}
+
+impl PartialEq for DropCounter<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for DropCounter<'_> {}
+
+impl PartialOrd for DropCounter<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DropCounter<'_> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+#[test]
+fn clear() {
+    let dropped = AtomicUsize::new(0);
+    let mut set = OrderedListSet::new();
+    set.clear();
+    set.clear_mut();
+
+    for i in 0..10 {
+        set.insert(DropCounter(i, &dropped)).unwrap();
+    }
+    set.clear();
+    assert_eq!(dropped.load(Relaxed), 10);
+    assert!(set.is_empty());
+    assert_eq!(set.len(), 0);
 
-    // pre-fill with even numbers
-    for i in (0..100).step_by(2).rev() {
-        let _ = set.insert(i);
+    for i in 0..10 {
+        set.insert(DropCounter(i, &dropped)).unwrap();
     }
-    let evens = set.iter().copied().collect::<HashSet<_>>();
+    set.clear_mut();
+    assert_eq!(dropped.load(Relaxed), 20);
+    assert!(set.is_empty());
+    assert_eq!(set.len(), 0);
 
+    set.insert(DropCounter(0, &dropped)).unwrap();
+    drop(set);
+    assert_eq!(dropped.load(Relaxed), 21);
+}
+
+#[test]
+fn clear_concurrent() {
+    const KEYS: usize = if cfg!(miri) { 32 } else { 1024 };
+
+    let dropped = AtomicUsize::new(0);
+    let probes = AtomicUsize::new(0);
+    let set = OrderedListSet::new();
     let done = AtomicBool::new(false);
-    thread::scope(|s| {
-        // insert or remove odd numbers
-        for _ in 0..THREADS {
-            s.spawn(|_| {
-                let mut rng = thread_rng();
-                for _ in 0..STEPS {
-                    let key = 2 * rng.gen_range(0..50) + 1;
-                    if rng.gen() {
-                        let _ = set.insert(key);
-                    } else {
-                        let _ = set.remove(&key);
-                    }
-                }
-                done.store(true, Release);
-            });
-        }
-        // iterator consistency check
+    let inserted = thread::scope(|s| {
+        let inserters = (0..2)
+            .map(|t| {
+                let (set, dropped) = (&set, &dropped);
+                s.spawn(move |_| {
+                    (0..KEYS)
+                        .filter(|i| set.insert(DropCounter(2 * i + t, dropped)).is_ok())
+                        .count()
+                })
+            })
+            .collect::<Vec<_>>();
+        // traversals race with the freeing of the detached chains
+        s.spawn(|_| {
+            while !done.load(Acquire) {
+                let _ = set.contains(&DropCounter(2 * KEYS, &probes));
+            }
+        });
         s.spawn(|_| {
             while !done.load(Acquire) {
-                let snapshot = set.iter().copied().collect::<Vec<_>>();
-                // sorted
-                assert!(snapshot.windows(2).all(|k| k[0] <= k[1]));
-                // even numbers are not touched
-                let snapshot = snapshot.into_iter().collect::<HashSet<_>>();
-                assert!(evens.is_subset(&snapshot));
+                set.clear();
             }
         });
+        let inserted = inserters
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .sum::<usize>();
+        done.store(true, Release);
+        inserted
     })
     .unwrap();
+
+    // every inserted element is dropped exactly once, by `clear` or by the drop of the set
+    assert_eq!(inserted, 2 * KEYS);
+    let rest = set.len();
+    assert_eq!(rest, set.len_exact());
+    assert_eq!(dropped.load(Relaxed) + rest, 2 * KEYS);
+    drop(set);
+    assert_eq!(dropped.load(Relaxed), 2 * KEYS);
+}
+
+/// Panics when compared with `POISON`.
+#[derive(Debug, PartialEq, Eq)]
+struct Touchy(usize);
+
+const POISON: usize = 13;
+
+impl PartialOrd for Touchy {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Touchy {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        if self.0 == POISON || other.0 == POISON {
+            panic!("compared with the poison");
+        }
+        self.0.cmp(&other.0)
+    }
+}
+
+thread_local! {
+    /// Comparisons of `Countdown`s left before one panics.
+    static COMPARISONS: Cell<usize> = Cell::new(usize::MAX);
+}
+
+/// Counts the live instances, and panics in the comparison that exhausts `COMPARISONS`.
+#[derive(Debug)]
+struct Countdown<'a>(usize, &'a AtomicUsize);
+
+impl<'a> Countdown<'a> {
+    fn new(key: usize, live: &'a AtomicUsize) -> Self {
+        let _ = live.fetch_add(1, Relaxed);
+        Self(key, live)
+    }
+}
+
+impl Drop for Countdown<'_> {
+    fn drop(&mut self) {
+        let _ = self.1.fetch_sub(1, Relaxed);
+    }
+}
+
+impl PartialEq for Countdown<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == cmp::Ordering::Equal
+    }
+}
+
+impl Eq for Countdown<'_> {}
+
+impl PartialOrd for Countdown<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Countdown<'_> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        let left = COMPARISONS.with(|c| c.replace(c.get().saturating_sub(1)));
+        if left == 1 {
+            panic!("out of comparisons");
+        }
+        self.0.cmp(&other.0)
+    }
+}
+
+/// Checks that the elements are strictly increasing and counted, and returns their keys.
+fn validate(set: &OrderedListSet<Countdown<'_>>) -> Vec<usize> {
+    COMPARISONS.with(|c| c.set(usize::MAX));
+    let keys = set.iter().map(|k| k.0).collect::<Vec<_>>();
+    assert!(keys.windows(2).all(|k| k[0] < k[1]));
+    assert_eq!(set.len(), keys.len());
+    assert_eq!(set.len_exact(), keys.len());
+    keys
+}
+
+#[test]
+fn panicking_comparisons() {
+    const ROUNDS: usize = if cfg!(miri) { 16 } else { 1024 };
+
+    let live = AtomicUsize::new(0);
+    let set = OrderedListSet::new();
+    let mut rng = thread_rng();
+    let mut panics = 0;
+    for _ in 0..ROUNDS {
+        let before = validate(&set);
+        let key = rng.gen_range(0..32);
+        COMPARISONS.with(|c| c.set(rng.gen_range(1..16)));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| match rng.gen_range(0..4) {
+            0 => drop(set.insert(Countdown::new(key, &live))),
+            1 => drop(set.remove(&Countdown::new(key, &live))),
+            2 => set.extend((key..key + 4).map(|k| Countdown::new(k, &live))),
+            _ => drop(
+                set.lower_bound(&Countdown::new(key, &live))
+                    .insert_before(Countdown::new(key, &live)),
+            ),
+        }));
+        let after = validate(&set);
+        if result.is_err() {
+            panics += 1;
+            // at most the part of a batch before the panic is inserted
+            assert!(after
+                .iter()
+                .all(|k| before.contains(k) || (key..key + 4).contains(k)));
+            assert!(before.iter().all(|k| after.contains(k) || *k == key));
+        }
+        // only the elements in the set are alive
+        assert_eq!(live.load(Relaxed), after.len());
+    }
+    assert!(panics > 0);
+    drop(set);
+    assert_eq!(live.load(Relaxed), 0);
+}
+
+#[test]
+fn try_ops() {
+    let set = OrderedListSet::new();
+    assert_eq!(set.try_contains(&1), Ok(false));
+    assert_eq!(set.try_insert(1), Ok(Ok(())));
+    assert_eq!(set.try_insert(1), Ok(Err(1)));
+    assert_eq!(set.try_contains(&1), Ok(true));
+    assert_eq!(set.try_remove(&2), Ok(None));
+    assert_eq!(set.try_remove(&1), Ok(Some(1)));
+    assert!(set.is_empty());
+
+    // held by this thread itself
+    set.insert(2).unwrap();
+    let iter = set.iter();
+    assert_eq!(set.try_contains(&2), Err(()));
+    assert_eq!(set.try_insert(1), Err(1));
+    assert_eq!(set.try_remove(&2), Err(()));
+    drop(iter);
+    let elem = set.iter().next().unwrap();
+    assert_eq!(set.try_insert(3), Err(3));
+    drop(elem);
+    assert_eq!(set.try_insert(3), Ok(Ok(())));
+    assert_eq!(set.len(), 2);
+}
+
+#[test]
+fn try_ops_never_wait() {
+    const OPS: usize = if cfg!(miri) { 16 } else { 4096 };
+    const HELD: usize = 32;
+
+    let set = (0..64).step_by(2).collect::<OrderedListSet<_>>();
+    let (sender, receiver) = mpsc::channel();
+    let barrier = &Barrier::new(2);
+    thread::scope(|s| {
+        let set = &set;
+        // holds the lock of the link to `HELD` until the other thread is done, or gives up
+        s.spawn(move |_| {
+            let elem = set.iter().find(|k| **k == HELD).unwrap();
+            let _ = barrier.wait();
+            let _ = receiver.recv_timeout(Duration::from_secs(10));
+            drop(elem);
+        });
+        s.spawn(move |_| {
+            let _ = barrier.wait();
+            let start = Instant::now();
+            let mut rng = thread_rng();
+            for _ in 0..OPS {
+                // the link out of the node before `HELD` is held
+                let key = rng.gen_range(0..64);
+                match set.try_contains(&key) {
+                    Ok(found) => assert!(key < HELD - 1 && found == (key % 2 == 0)),
+                    Err(()) => assert!(key >= HELD - 1),
+                }
+                let key = rng.gen_range(0..HELD / 2 - 1) * 2 + 1;
+                assert_eq!(set.try_insert(key), Ok(Ok(())));
+                assert_eq!(set.try_remove(&key), Ok(Some(key)));
+                assert_eq!(set.try_insert(HELD + 1), Err(HELD + 1));
+                assert_eq!(set.try_remove(&HELD), Err(()));
+                assert_eq!(set.try_remove(&(HELD - 2)), Err(()));
+            }
+            // the other thread has not released the lock before this
+            assert!(start.elapsed() < Duration::from_secs(10));
+            sender.send(()).unwrap();
+        });
+    })
+    .unwrap();
+    assert_eq!(set, (0..64).step_by(2).collect::<Vec<_>>());
+}
+
+#[test]
+fn try_ops_concurrent() {
+    const STEPS: usize = if cfg!(miri) { 64 } else { 4096 };
+
+    // blocking operations on the even keys, and non-blocking ones on the odd keys
+    let set = OrderedListSet::new();
+    let mine = thread::scope(|s| {
+        s.spawn(|_| {
+            let mut rng = thread_rng();
+            for _ in 0..STEPS {
+                let key = rng.gen_range(0..32) * 2;
+                if set.insert(key).is_err() {
+                    assert_eq!(set.remove(&key), Some(key));
+                }
+            }
+        });
+        s.spawn(|_| {
+            let mut rng = thread_rng();
+            let mut mine = BTreeSet::new();
+            for _ in 0..STEPS {
+                let key = rng.gen_range(0..32) * 2 + 1;
+                match rng.gen_range(0..3) {
+                    0 => {
+                        if let Ok(found) = set.try_contains(&key) {
+                            assert_eq!(found, mine.contains(&key));
+                        }
+                    }
+                    1 => match set.try_insert(key) {
+                        Ok(result) => assert_eq!(result.is_ok(), mine.insert(key)),
+                        Err(k) => assert_eq!(k, key),
+                    },
+                    _ => {
+                        if let Ok(result) = set.try_remove(&key) {
+                            assert_eq!(result.is_some(), mine.remove(&key));
+                        }
+                    }
+                }
+            }
+            mine
+        })
+        .join()
+        .unwrap()
+    })
+    .unwrap();
+
+    let odd = set.iter().map(|k| *k).filter(|k| k % 2 == 1);
+    assert!(odd.eq(mine));
+}
+
+#[test]
+fn poisoned() {
+    let set = (0..POISON * 2)
+        .filter(|i| *i != POISON)
+        .map(Touchy)
+        .collect::<OrderedListSet<_>>();
+    let len = set.len();
+
+    // panics while holding the locks on the way to the poison
+    let set = &set;
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| set.insert(Touchy(POISON)))).is_err());
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| set.contains(&Touchy(POISON)))).is_err());
+    assert!(panic::catch_unwind(AssertUnwindSafe(
+        || set.retain(|k| k.0 < POISON + 1 || panic!())
+    ))
+    .is_err());
+
+    // every operation still works
+    assert_eq!(set.len(), len);
+    assert_eq!(set.len_exact(), len);
+    assert!((0..POISON).all(|i| set.contains(&Touchy(i))));
+    set.insert(Touchy(POISON * 2)).unwrap();
+    assert_eq!(set.remove(&Touchy(POISON + 1)), Some(Touchy(POISON + 1)));
+    assert_eq!(set.pop_max(), Some(Touchy(POISON * 2)));
+    assert_eq!(set.pop_min(), Some(Touchy(0)));
+    set.retain(|k| k.0 % 2 == 0);
+    assert_eq!(
+        set.iter().map(|k| k.0).collect::<Vec<_>>(),
+        (2..POISON * 2)
+            .step_by(2)
+            .filter(|i| *i != POISON + 1)
+            .collect::<Vec<_>>()
+    );
+    set.clear();
+    assert!(set.is_empty());
+}
+
+#[test]
+fn from_iter() {
+    let set = Vec::<usize>::new()
+        .into_iter()
+        .collect::<OrderedListSet<_>>();
+    assert!(set.is_empty());
+    assert_eq!(set.len(), 0);
+
+    let mut rng = thread_rng();
+    let input = (0..256)
+        .map(|_| rng.gen_range(0..64))
+        .collect::<Vec<usize>>();
+    let set = input.iter().copied().collect::<OrderedListSet<_>>();
+    let expected = input.iter().copied().collect::<BTreeSet<_>>();
+    let elems = set.iter().map(|k| *k).collect::<Vec<_>>();
+    assert!(elems.windows(2).all(|w| w[0] < w[1]));
+    assert_eq!(elems, expected.iter().copied().collect::<Vec<_>>());
+    assert_eq!(set.len(), expected.len());
+    assert_eq!(set.len_exact(), expected.len());
+
+    // the set is usable afterwards
+    assert_eq!(set.insert(64), Ok(()));
+    assert_eq!(set.remove(&0).is_some(), expected.contains(&0));
+}
+
+#[test]
+fn from_iter_keeps_first() {
+    let set = vec![(3, 0), (1, 0), (3, 1), (2, 0), (1, 1)]
+        .into_iter()
+        .map(|(key, i)| {
+            let mut entry = Entry::new(key);
+            entry.payload[0] = i;
+            entry
+        })
+        .collect::<OrderedListSet<_>>();
+    let entries = set
+        .iter()
+        .map(|e| (e.key, e.payload[0]))
+        .collect::<Vec<_>>();
+    assert_eq!(entries, vec![(1, 0), (2, 0), (3, 0)]);
+}
+
+#[test]
+fn from_iter_drop() {
+    let dropped = AtomicUsize::new(0);
+    let set = vec![2, 0, 2, 1, 0]
+        .into_iter()
+        .map(|i| DropCounter(i, &dropped))
+        .collect::<OrderedListSet<_>>();
+    // the duplicates are dropped when the set is built
+    assert_eq!(dropped.load(Relaxed), 2);
+    assert_eq!(set.len(), 3);
+    drop(set);
+    assert_eq!(dropped.load(Relaxed), 5);
+}
+
+#[test]
+fn extend() {
+    let set = OrderedListSet::new();
+    set.extend(vec![5, 1, 3]);
+    set.extend(Vec::new());
+    // overlapping, with duplicates inside the batch
+    set.extend(vec![4, 3, 0, 4, 5, 2]);
+    // entirely smaller, then entirely larger
+    set.extend(-3..0);
+    set.extend(vec![9, 7, 8]);
+    assert_eq!(
+        set.iter().map(|k| *k).collect::<Vec<_>>(),
+        (-3..10).filter(|k| *k != 6).collect::<Vec<_>>()
+    );
+    assert_eq!(set.len(), 12);
+    assert_eq!(set.len_exact(), 12);
+
+    let mut set = set;
+    Extend::extend(&mut set, vec![6]);
+    assert_eq!(set.len(), 13);
+    assert!(set.contains(&6));
+}
+
+#[test]
+fn extend_keeps_existing() {
+    let set = OrderedListSet::new();
+    set.insert(Entry::new(1)).unwrap();
+    set.extend(vec![Entry::key(2), Entry::key(1), Entry::new(2)]);
+    assert_eq!(set.read(&Entry::key(1), |e| e.payload.len()), Some(4));
+    assert_eq!(set.read(&Entry::key(2), |e| e.payload.len()), Some(0));
+}
+
+#[test]
+fn extend_drop() {
+    let dropped = AtomicUsize::new(0);
+    let set = OrderedListSet::new();
+    set.insert(DropCounter(1, &dropped)).unwrap();
+    set.extend(
+        vec![0, 1, 2, 0]
+            .into_iter()
+            .map(|i| DropCounter(i, &dropped)),
+    );
+    // the duplicate inside the batch and the one already in the set
+    assert_eq!(dropped.load(Relaxed), 2);
+    drop(set);
+    assert_eq!(dropped.load(Relaxed), 5);
+}
+
+#[test]
+fn extend_concurrent() {
+    const KEYS: usize = if cfg!(miri) { 64 } else { 4096 };
+
+    let set = OrderedListSet::new();
+    thread::scope(|s| {
+        for t in 0..2 {
+            let set = &set;
+            s.spawn(move |_| {
+                let mut rng = thread_rng();
+                let mut batch = (0..KEYS).filter(|k| k % 3 != t).collect::<Vec<_>>();
+                batch.shuffle(&mut rng);
+                for chunk in batch.chunks(KEYS / 8) {
+                    set.extend(chunk.iter().copied());
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    // every key is in one of the batches, and those in both are inserted once
+    assert_eq!(
+        set.iter().map(|k| *k).collect::<Vec<_>>(),
+        (0..KEYS).collect::<Vec<_>>()
+    );
+    assert_eq!(set.len(), KEYS);
+}
+
+#[test]
+fn replace() {
+    let set = OrderedListSet::new();
+    assert!(set.replace(Entry::key(1)).is_none());
+    assert!(set.replace(Entry::key(3)).is_none());
+    let replaced = set.replace(Entry::new(1)).unwrap();
+    assert_eq!((replaced.key, replaced.payload.len()), (1, 0));
+    assert_eq!(
+        set.read(&Entry::key(1), |e| e.payload.clone()),
+        Some(vec![1; 4])
+    );
+    assert_eq!(set.read(&Entry::key(3), |e| e.payload.len()), Some(0));
+    assert_eq!(set.len(), 2);
+    assert_eq!(set.len_exact(), 2);
+}
+
+#[test]
+fn replace_concurrent_remove() {
+    const STEPS: usize = if cfg!(miri) { 64 } else { 4096 };
+
+    // the payload tells which replace put the element there
+    let set = OrderedListSet::new();
+    let (inserted, removed) = thread::scope(|s| {
+        let replacer = s.spawn(|_| {
+            (0..STEPS)
+                .filter(|i| {
+                    let entry = Entry {
+                        key: 0,
+                        payload: vec![*i],
+                    };
+                    match set.replace(entry) {
+                        None => true,
+                        Some(old) => {
+                            assert!(old.payload[0] < *i);
+                            false
+                        }
+                    }
+                })
+                .count()
+        });
+        let remover = s.spawn(|_| {
+            (0..STEPS)
+                .filter(|_| set.remove(&Entry::key(0)).is_some())
+                .count()
+        });
+        (replacer.join().unwrap(), remover.join().unwrap())
+    })
+    .unwrap();
+
+    // every insert is removed, but maybe the last
+    assert_eq!(inserted, removed + set.len());
+    assert_eq!(set.len(), set.len_exact());
+    if let Some(payload) = set.read(&Entry::key(0), |e| e.payload[0]) {
+        assert!(payload < STEPS);
+    }
+}
+
+#[test]
+fn split_off() {
+    // at the head
+    let set = (0..8).collect::<OrderedListSet<_>>();
+    let suffix = set.split_off(&0);
+    assert!(set.is_empty());
+    assert_eq!(set.len(), 0);
+    assert_eq!(suffix, (0..8).collect::<Vec<_>>());
+    assert_eq!(suffix.len(), 8);
+
+    // past the end
+    let rest = suffix.split_off(&8);
+    assert!(rest.is_empty());
+    assert_eq!(suffix.len(), 8);
+
+    // in the middle, between elements and at one
+    let upper = suffix.split_off(&5);
+    assert_eq!(suffix, (0..5).collect::<Vec<_>>());
+    assert_eq!(upper, (5..8).collect::<Vec<_>>());
+    let set = [1, 3, 5].iter().copied().collect::<OrderedListSet<_>>();
+    let upper = set.split_off(&2);
+    assert_eq!(set, vec![1]);
+    assert_eq!(upper, vec![3, 5]);
+    assert_eq!((set.len(), upper.len()), (1, 2));
+
+    // both parts are independent sets
+    set.insert(4).unwrap();
+    upper.insert(4).unwrap();
+    assert_eq!(set, vec![1, 4]);
+    assert_eq!(upper, vec![3, 4, 5]);
+}
+
+#[test]
+fn split_off_concurrent() {
+    const KEYS: usize = if cfg!(miri) { 32 } else { 1024 };
+
+    // inserts on both sides of the split point while it splits
+    let set = (0..KEYS * 2).step_by(2).collect::<OrderedListSet<_>>();
+    let upper = thread::scope(|s| {
+        for t in 0..2 {
+            let set = &set;
+            s.spawn(move |_| {
+                for k in (t * KEYS + 1..(t + 1) * KEYS).step_by(2) {
+                    set.insert(k).unwrap();
+                }
+            });
+        }
+        s.spawn(|_| set.split_off(&KEYS)).join().unwrap()
+    })
+    .unwrap();
+
+    // the counters are exact, the upper part only has large keys, and the keys inserted after
+    // the split are in the lower part
+    assert_eq!(set.len(), set.len_exact());
+    assert_eq!(upper.len(), upper.len_exact());
+    let (lower, upper) = (set.snapshot(), upper.snapshot());
+    assert!(upper.iter().all(|k| *k >= KEYS));
+    assert!((0..KEYS).step_by(2).all(|k| lower.contains(&k)));
+    assert!((KEYS..KEYS * 2).step_by(2).all(|k| upper.contains(&k)));
+    let mut all = lower.iter().chain(&upper).copied().collect::<Vec<_>>();
+    all.sort_unstable();
+    assert_eq!(all, (0..KEYS * 2).collect::<Vec<_>>());
+}
+
+#[test]
+fn get_or_insert_with() {
+    let set = OrderedListSet::new();
+    assert!(set.get_or_insert_with("b", || "b".to_string()));
+    assert!(!set.get_or_insert_with("b", || unreachable!()));
+    assert!(set.get_or_insert_with("a", || "a".to_string()));
+    assert!(set.get_or_insert_with("c", || "c".to_string()));
+    assert_eq!(set, vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+    assert_eq!(set.len(), 3);
+}
+
+#[test]
+fn get_or_insert_with_concurrent() {
+    const THREADS: usize = if cfg!(miri) { 3 } else { 8 };
+    const KEYS: usize = if cfg!(miri) { 16 } else { 512 };
+
+    // every thread tries to insert every key, and exactly one inserts each
+    let set = OrderedListSet::new();
+    let calls = AtomicUsize::new(0);
+    let inserted = thread::scope(|s| {
+        let handles = (0..THREADS)
+            .map(|_| {
+                s.spawn(|_| {
+                    (0..KEYS)
+                        .filter(|k| {
+                            set.get_or_insert_with(k, || {
+                                let _ = calls.fetch_add(1, Relaxed);
+                                *k
+                            })
+                        })
+                        .count()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .sum::<usize>()
+    })
+    .unwrap();
+
+    assert_eq!(inserted, KEYS);
+    assert_eq!(calls.load(Relaxed), KEYS);
+    assert_eq!(set, (0..KEYS).collect::<Vec<_>>());
+}
+
+#[test]
+fn insert_sorted_batch() {
+    let set = (0..10).step_by(2).collect::<OrderedListSet<_>>();
+    assert_eq!(set.insert_sorted_batch(Vec::new()), Vec::<i32>::new());
+
+    // entirely interleaved
+    assert_eq!(
+        set.insert_sorted_batch((1..10).step_by(2).collect()),
+        Vec::<i32>::new()
+    );
+    assert_eq!(set, (0..10).collect::<Vec<_>>());
+
+    // internal duplicates, unsorted
+    assert_eq!(
+        set.insert_sorted_batch(vec![12, 10, 11, 10, 12, 12]),
+        [10, 12, 12]
+    );
+    assert_eq!(set, (0..13).collect::<Vec<_>>());
+
+    // colliding with existing keys
+    assert_eq!(set.insert_sorted_batch(vec![-1, 0, 5, 6, 13]), [0, 5, 6]);
+    assert_eq!(set, (-1..14).collect::<Vec<_>>());
+    assert_eq!(set.len(), 15);
+    assert_eq!(set.len_exact(), 15);
+}
+
+#[test]
+fn insert_sorted_batch_keeps_first() {
+    let set = OrderedListSet::new();
+    set.insert(Entry::new(1)).unwrap();
+    let rejected = set.insert_sorted_batch(vec![Entry::key(2), Entry::key(1), Entry::new(2)]);
+    assert_eq!(
+        rejected.iter().map(|e| e.payload.len()).collect::<Vec<_>>(),
+        [0, 4]
+    );
+    assert_eq!(set.read(&Entry::key(2), |e| e.payload.len()), Some(0));
+}
+
+#[test]
+fn insert_sorted_batch_concurrent() {
+    const KEYS: usize = if cfg!(miri) { 64 } else { 4096 };
+
+    // a batch inserter and a single-key inserter competing for every other key
+    let set = OrderedListSet::new();
+    let (rejected, inserted) = thread::scope(|s| {
+        let batches = s.spawn(|_| {
+            (0..KEYS)
+                .collect::<Vec<_>>()
+                .chunks(KEYS / 8)
+                .flat_map(|chunk| set.insert_sorted_batch(chunk.to_vec()))
+                .collect::<Vec<_>>()
+        });
+        let single = s.spawn(|_| {
+            (0..KEYS)
+                .step_by(2)
+                .filter(|k| set.insert(*k).is_ok())
+                .collect::<Vec<_>>()
+        });
+        (batches.join().unwrap(), single.join().unwrap())
+    })
+    .unwrap();
+
+    // every key is inserted by exactly one of them
+    assert_eq!(rejected, inserted);
+    assert_eq!(set, (0..KEYS).collect::<Vec<_>>());
+    assert_eq!(set.len(), KEYS);
+}
+
+#[test]
+#[allow(clippy::eq_op)]
+fn eq() {
+    let a = (0..10).collect::<OrderedListSet<_>>();
+    let b = (0..10).rev().collect::<OrderedListSet<_>>();
+    assert_eq!(a, a);
+    assert_eq!(a, b);
+    assert_eq!(b, a);
+    assert_eq!(a, (0..10).collect::<Vec<_>>());
+    assert_eq!(a, *(0..10).collect::<Vec<_>>().as_slice());
+
+    // prefixes
+    let c = (0..5).collect::<OrderedListSet<_>>();
+    assert_ne!(a, c);
+    assert_ne!(c, a);
+    assert_ne!(a, (0..9).collect::<Vec<_>>());
+    assert_ne!(c, (0..6).collect::<Vec<_>>());
+
+    assert!(b.remove(&3).is_some());
+    assert_ne!(a, b);
+    assert!(b.insert(3).is_ok());
+    assert_eq!(a, b);
+
+    let empty = OrderedListSet::<i32>::new();
+    assert_eq!(empty, OrderedListSet::new());
+    assert_eq!(empty, Vec::new());
+    assert_ne!(empty, a);
+}
+
+#[test]
+fn eq_concurrent() {
+    const KEYS: usize = if cfg!(miri) { 16 } else { 256 };
+    const STEPS: usize = if cfg!(miri) { 16 } else { 1024 };
+
+    // Equality is of snapshots, so it may fail while a key is missing from one side. Comparisons
+    // in both directions run at once, and must not deadlock.
+    let a = (0..KEYS).collect::<OrderedListSet<_>>();
+    let b = (0..KEYS).collect::<OrderedListSet<_>>();
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        s.spawn(|_| {
+            let mut rng = thread_rng();
+            for _ in 0..STEPS {
+                let key = rng.gen_range(0..KEYS);
+                let set = if rng.gen() { &a } else { &b };
+                let key = set.remove(&key).unwrap();
+                set.insert(key).unwrap();
+            }
+            done.store(true, Release);
+        });
+        for t in 0..2 {
+            let (lhs, rhs) = if t == 0 { (&a, &b) } else { (&b, &a) };
+            let done = &done;
+            s.spawn(move |_| {
+                while !done.load(Acquire) {
+                    let _ = lhs == rhs;
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    assert_eq!(a, b);
+}
+
+#[test]
+fn eq_releases_locks() {
+    let a = (0..10).collect::<OrderedListSet<_>>();
+    let b = (0..10).collect::<OrderedListSet<_>>();
+    let (first, second) = if (&a as *const _) < (&b as *const _) {
+        (&a, &b)
+    } else {
+        (&b, &a)
+    };
+    thread::scope(|s| {
+        // The comparison stops at 5 in the second list, which the cursor holds.
+        let cursor = second.lower_bound(&5);
+        let handle = s.spawn(|_| a == b);
+        // The first list is not locked behind the comparison.
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(first.remove(&0), Some(0));
+        drop(cursor);
+        // Equal or not, depending on whether the comparison passed 0 before the removal.
+        let _ = handle.join().unwrap();
+    })
+    .unwrap();
+}
+
+#[test]
+fn borrowed_keys() {
+    let set = OrderedListSet::new();
+    for key in &["b", "d", "a"] {
+        set.insert(key.to_string()).unwrap();
+    }
+    assert!(set.contains("a"));
+    assert!(!set.contains("c"));
+    assert_eq!(set.read("d", |s| s.len()), Some(1));
+    assert_eq!(set.remove("b"), Some("b".to_string()));
+    assert_eq!(set.remove("b"), None);
+    // `&T` still works
+    assert!(set.contains(&"d".to_string()));
+    assert_eq!(set, vec!["a".to_string(), "d".to_string()]);
+}
+
+/// A key that borrows to a slice, ordered like it.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Bytes(Vec<u8>);
+
+impl std::borrow::Borrow<[u8]> for Bytes {
+    fn borrow(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[test]
+fn borrowed_slice_keys() {
+    let set = OrderedListSet::new();
+    for key in &[&b"ab"[..], b"a", b"b", b""] {
+        set.insert(Bytes(key.to_vec())).unwrap();
+    }
+    assert!(set.contains(&b"ab"[..]));
+    assert!(set.contains(&b""[..]));
+    assert!(!set.contains(&b"abc"[..]));
+    assert_eq!(set.remove(&b"a"[..]), Some(Bytes(b"a".to_vec())));
+    assert!(!set.contains(&b"a"[..]));
+    assert_eq!(set.len(), 3);
+}
+
+#[test]
+fn snapshot() {
+    let set = (0..10).step_by(2).collect::<OrderedListSet<_>>();
+    let snapshot = set.snapshot();
+    assert_eq!(snapshot, vec![0, 2, 4, 6, 8]);
+
+    // With `iter`, the insert would wait for the lock that the iterator holds, and deadlock.
+    for k in snapshot {
+        set.insert(k + 1).unwrap();
+        assert!(set.remove(&k).is_some());
+    }
+    assert_eq!(set.snapshot(), vec![1, 3, 5, 7, 9]);
+    assert!(OrderedListSet::<i32>::new().snapshot().is_empty());
+}
+
+#[test]
+fn snapshot_concurrent() {
+    const STEPS: usize = if cfg!(miri) { 64 } else { 4096 };
+
+    // Only odd keys change, so every snapshot is sorted and has all even keys.
+    let set = (0..100).step_by(2).collect::<OrderedListSet<_>>();
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        s.spawn(|_| {
+            let mut rng = thread_rng();
+            for _ in 0..STEPS {
+                let key = 2 * rng.gen_range(0..50) + 1;
+                if set.insert(key).is_err() {
+                    assert!(set.remove(&key).is_some());
+                }
+            }
+            done.store(true, Release);
+        });
+        s.spawn(|_| {
+            while !done.load(Acquire) {
+                let snapshot = set.snapshot();
+                assert!(snapshot.windows(2).all(|w| w[0] < w[1]));
+                assert_eq!(snapshot.iter().filter(|k| *k % 2 == 0).count(), 50);
+                // the snapshot holds no lock, so the set can be used while going through it
+                for k in snapshot.iter().filter(|k| *k % 2 == 0).take(4) {
+                    assert!(set.contains(k));
+                }
+            }
+        });
+    })
+    .unwrap();
+}
+
+#[test]
+fn into_iter() {
+    let set = vec![3, 1, 2].into_iter().collect::<OrderedListSet<_>>();
+    let mut sum = 0;
+    for k in &set {
+        sum += *k;
+    }
+    assert_eq!(sum, 6);
+
+    let mut iter = set.into_iter();
+    assert_eq!(iter.len(), 3);
+    assert_eq!(iter.next(), Some(1));
+    assert_eq!(iter.len(), 2);
+    assert_eq!(iter.collect::<Vec<_>>(), vec![2, 3]);
+}
+
+#[test]
+fn into_iter_drop() {
+    let dropped = AtomicUsize::new(0);
+    let new_set = || {
+        (0..10)
+            .map(|i| DropCounter(i, &dropped))
+            .collect::<OrderedListSet<_>>()
+    };
+
+    // fully consumed
+    let keys = new_set().into_iter().map(|e| e.0).collect::<Vec<_>>();
+    assert_eq!(keys, (0..10).collect::<Vec<_>>());
+    assert_eq!(dropped.load(Relaxed), 10);
+
+    // dropped early, with and without taking any element
+    let mut iter = new_set().into_iter();
+    assert_eq!(iter.next().map(|e| e.0), Some(0));
+    assert_eq!(iter.next().map(|e| e.0), Some(1));
+    assert_eq!(dropped.load(Relaxed), 12);
+    drop(iter);
+    assert_eq!(dropped.load(Relaxed), 20);
+    drop(new_set().into_iter());
+    assert_eq!(dropped.load(Relaxed), 30);
+}
+
+#[test]
+fn debug() {
+    let set = vec![9, 1, 5].into_iter().collect::<OrderedListSet<_>>();
+    assert_eq!(format!("{:?}", set), "{1, 5, 9}");
+    assert_eq!(format!("{:?}", OrderedListSet::<i32>::new()), "{}");
+
+    // long sets are cut short
+    let set = (0..100).collect::<OrderedListSet<_>>();
+    let expected = (0..32)
+        .map(|k| k.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    assert_eq!(format!("{:?}", set), format!("{{{}, ...}}", expected));
+    // the traversal releases its locks
+    assert!(set.remove(&99).is_some());
+}
+
+#[test]
+fn clone() {
+    let set = (0..5).collect::<OrderedListSet<_>>();
+    let copy = set.clone();
+    assert_eq!(copy, set);
+    assert_eq!(copy.len(), 5);
+
+    // the copies are independent
+    assert!(set.remove(&0).is_some());
+    set.insert(5).unwrap();
+    assert!(copy.insert(5).is_ok());
+    assert_eq!(set, vec![1, 2, 3, 4, 5]);
+    assert_eq!(copy, vec![0, 1, 2, 3, 4, 5]);
+
+    let empty = OrderedListSet::new();
+    let copy = empty.clone();
+    copy.insert(0).unwrap();
+    assert!(empty.is_empty());
+    assert_eq!(copy.len(), 1);
+}
+
+#[test]
+fn clone_drop() {
+    let dropped = AtomicUsize::new(0);
+    let set = (0..10)
+        .map(|i| DropCounter(i, &dropped))
+        .collect::<OrderedListSet<_>>();
+    let copy = set.clone();
+    assert_eq!(dropped.load(Relaxed), 0);
+    drop(set);
+    assert_eq!(dropped.load(Relaxed), 10);
+    assert_eq!(copy.len_exact(), 10);
+    drop(copy);
+    assert_eq!(dropped.load(Relaxed), 20);
+}
+
+#[test]
+fn clone_concurrent() {
+    const STEPS: usize = if cfg!(miri) { 64 } else { 4096 };
+
+    // Only odd keys change, so every copy is sorted and has all even keys.
+    let set = (0..100).step_by(2).collect::<OrderedListSet<_>>();
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        s.spawn(|_| {
+            let mut rng = thread_rng();
+            for _ in 0..STEPS {
+                let key = 2 * rng.gen_range(0..50) + 1;
+                if set.insert(key).is_err() {
+                    assert!(set.remove(&key).is_some());
+                }
+            }
+            done.store(true, Release);
+        });
+        s.spawn(|_| {
+            while !done.load(Acquire) {
+                let copy = set.clone();
+                let elems = copy.snapshot();
+                assert!(elems.windows(2).all(|w| w[0] < w[1]));
+                assert_eq!(elems.iter().filter(|k| *k % 2 == 0).count(), 50);
+                assert_eq!(copy.len(), elems.len());
+            }
+        });
+    })
+    .unwrap();
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    const LARGE: usize = if cfg!(miri) { 64 } else { 10_000 };
+
+    for &n in &[0, 3, LARGE] {
+        let set = (0..n).rev().collect::<OrderedListSet<usize>>();
+
+        let json = serde_json::to_string(&set).unwrap();
+        let from_json = serde_json::from_str::<OrderedListSet<usize>>(&json).unwrap();
+        assert_eq!(from_json, set);
+        assert_eq!(from_json.len(), n);
+
+        let bytes = bincode::serialize(&set).unwrap();
+        let from_bincode = bincode::deserialize::<OrderedListSet<usize>>(&bytes).unwrap();
+        assert_eq!(from_bincode, set);
+        assert_eq!(from_bincode.len(), n);
+    }
+    assert_eq!(
+        serde_json::to_string(&vec![3, 1, 2].into_iter().collect::<OrderedListSet<_>>()).unwrap(),
+        "[1,2,3]"
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_malformed() {
+    for json in &["[1,1]", "[1,2,2,3]", "[2,1]"] {
+        assert!(serde_json::from_str::<OrderedListSet<i32>>(json).is_err());
+    }
+    let bytes = bincode::serialize(&vec![0u32, 5, 5]).unwrap();
+    assert!(bincode::deserialize::<OrderedListSet<u32>>(&bytes).is_err());
+}
+
+#[test]
+fn conversions() {
+    let set = OrderedListSet::from(vec![3, 1, 4, 1, 5, 9, 2, 6]);
+    assert_eq!(set.len(), 7);
+    assert_eq!(Vec::from(set), vec![1, 2, 3, 4, 5, 6, 9]);
+
+    let mut rng = thread_rng();
+    let oracle = (0..256)
+        .map(|_| rng.gen_range(0..512))
+        .collect::<BTreeSet<usize>>();
+    let set = OrderedListSet::from(oracle.clone());
+    assert_eq!(set.len(), oracle.len());
+    assert_eq!(set, oracle.iter().copied().collect::<Vec<_>>());
+    assert_eq!(BTreeSet::from(set), oracle);
+
+    assert!(Vec::from(OrderedListSet::<i32>::from(Vec::new())).is_empty());
+    assert!(BTreeSet::from(OrderedListSet::<i32>::from(BTreeSet::new())).is_empty());
+}
+
+#[test]
+fn conversions_keep_first() {
+    let set = OrderedListSet::from(vec![Entry::new(1), Entry::key(1), Entry::key(0)]);
+    let entries = Vec::from(set);
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1].payload.len(), 4);
+}
+
+#[test]
+fn set_operations() {
+    let mut rng = thread_rng();
+    for _ in 0..if cfg!(miri) { 4 } else { 64 } {
+        let a = (0..rng.gen_range(0..64))
+            .map(|_| rng.gen_range(0..64))
+            .collect::<BTreeSet<usize>>();
+        let b = (0..rng.gen_range(0..64))
+            .map(|_| rng.gen_range(0..64))
+            .collect::<BTreeSet<usize>>();
+        let (x, y) = (
+            OrderedListSet::from(a.clone()),
+            OrderedListSet::from(b.clone()),
+        );
+
+        let union = x.union_into(&y);
+        assert_eq!(union, a.union(&b).copied().collect::<Vec<_>>());
+        assert_eq!(union.len(), a.union(&b).count());
+        let intersection = x.intersection(&y);
+        assert_eq!(
+            intersection,
+            a.intersection(&b).copied().collect::<Vec<_>>()
+        );
+        assert_eq!(intersection.len(), a.intersection(&b).count());
+        let difference = x.difference(&y);
+        assert_eq!(difference, a.difference(&b).copied().collect::<Vec<_>>());
+        assert_eq!(difference.len(), a.difference(&b).count());
+    }
+
+    // with itself
+    let x = (0..8).collect::<OrderedListSet<_>>();
+    assert_eq!(x.union_into(&x), x);
+    assert_eq!(x.intersection(&x), x);
+    assert!(x.difference(&x).is_empty());
+}
+
+#[test]
+fn is_subset() {
+    let mut rng = thread_rng();
+    for _ in 0..if cfg!(miri) { 4 } else { 64 } {
+        // small ranges, so that subsets are common
+        let a = (0..rng.gen_range(0..8))
+            .map(|_| rng.gen_range(0..8))
+            .collect::<BTreeSet<usize>>();
+        let b = (0..rng.gen_range(0..16))
+            .map(|_| rng.gen_range(0..8))
+            .collect::<BTreeSet<usize>>();
+        let (x, y) = (
+            OrderedListSet::from(a.clone()),
+            OrderedListSet::from(b.clone()),
+        );
+        assert_eq!(x.is_subset(&y), a.is_subset(&b));
+        assert_eq!(y.is_subset(&x), b.is_subset(&a));
+        assert_eq!(x.contains_all(b.iter().copied()), b.is_subset(&a));
+        assert!(x.is_subset(&x));
+    }
+
+    // empty sets on either side
+    let empty = OrderedListSet::new();
+    let set = (0..4).collect::<OrderedListSet<_>>();
+    assert!(empty.is_subset(&empty));
+    assert!(empty.is_subset(&set));
+    assert!(!set.is_subset(&empty));
+    assert!(empty.contains_all(Vec::new()));
+    assert!(!empty.contains_all(vec![0]));
+    assert!(set.contains_all(Vec::new()));
+    assert!(set.contains_all(vec![3, 1, 3]));
+    assert!(!set.contains_all(vec![3, 4, 1]));
+}
+
+#[test]
+fn set_operations_concurrent() {
+    const STEPS: usize = if cfg!(miri) { 64 } else { 4096 };
+
+    // Operations in both directions run at once while both sets change, and must not deadlock.
+    let evens = (0..64).step_by(2).collect::<OrderedListSet<_>>();
+    let odds = (1..64).step_by(2).collect::<OrderedListSet<_>>();
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        s.spawn(|_| {
+            let mut rng = thread_rng();
+            for _ in 0..STEPS {
+                let set = if rng.gen() { &evens } else { &odds };
+                let key = 64 + rng.gen_range(0..64);
+                if set.insert(key).is_err() {
+                    assert!(set.remove(&key).is_some());
+                }
+            }
+            done.store(true, Release);
+        });
+        for t in 0..2 {
+            let (lhs, rhs) = if t == 0 {
+                (&evens, &odds)
+            } else {
+                (&odds, &evens)
+            };
+            let done = &done;
+            s.spawn(move |_| {
+                while !done.load(Acquire) {
+                    let union = lhs.union_into(rhs).snapshot();
+                    assert!(union.windows(2).all(|w| w[0] < w[1]));
+                    assert_eq!(union.iter().filter(|k| **k < 64).count(), 64);
+                    assert!(lhs.intersection(rhs).snapshot().iter().all(|k| *k >= 64));
+                    let difference = lhs.difference(rhs).snapshot();
+                    assert_eq!(difference.iter().filter(|k| **k < 64).count(), 32);
+                }
+            });
+        }
+    })
+    .unwrap();
+}
+
+#[test]
+fn parallel_iter_end() {
+    let set = OrderedListSet::new();
+    set.insert(1).unwrap();
+    set.insert(2).unwrap();
+    let mut iter = set.iter();
+    iter.next();
+    iter.next();
+    iter.next();
+    thread::scope(|s| {
+        s.spawn(|_| {
+            // this shouldn't block
+            let _ = set.iter().collect::<Vec<_>>();
+        });
+    })
+    .unwrap();
+    drop(iter);
+}
+
+#[test]
+fn len_sequential() {
+    const OPS: usize = if cfg!(miri) { 256 } else { 4096 };
+
+    let mut rng = thread_rng();
+    let set = OrderedListSet::new();
+    let mut oracle = HashSet::new();
+    for _ in 0..OPS {
+        let key = rng.gen_range(0..64);
+        if rng.gen() {
+            assert_eq!(set.insert(key).is_ok(), oracle.insert(key));
+        } else {
+            assert_eq!(set.remove(&key).is_some(), oracle.remove(&key));
+        }
+        assert_eq!(set.len(), oracle.len());
+        assert_eq!(set.len_exact(), oracle.len());
+    }
+}
+
+#[test]
+fn len_concurrent() {
+    const THREADS: usize = if cfg!(miri) { 4 } else { 16 };
+    const STEPS: usize = if cfg!(miri) { 64 } else { 4096 };
+
+    let set = OrderedListSet::new();
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                let mut rng = thread_rng();
+                for _ in 0..STEPS {
+                    let key = rng.gen_range(0..64);
+                    if rng.gen() {
+                        let _ = set.insert(key);
+                    } else {
+                        let _ = set.remove(&key);
+                    }
+                    // snapshots are never more than the keys
+                    assert!(set.len() <= 64);
+                    assert!(set.len_exact() <= 64);
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let len = set.iter().count();
+    assert_eq!(set.len(), len);
+    assert_eq!(set.len_exact(), len);
+}
+
+#[test]
+fn iter_while() {
+    let set = (0..8).collect::<OrderedListSet<_>>();
+    let mut visited = Vec::new();
+    assert_eq!(
+        set.iter_while(|k| {
+            visited.push(*k);
+            false
+        }),
+        0
+    );
+    assert_eq!(visited, [0]);
+    assert_eq!(set.iter_while(|k| *k < 5), 5);
+    assert_eq!(set.iter_while(|_| true), 8);
+    assert_eq!(OrderedListSet::<usize>::new().iter_while(|_| true), 0);
+
+    // every lock is released, so this thread can go past the stop
+    assert_eq!(set.iter_while(|k| *k < 3), 3);
+    set.insert(8).unwrap();
+    assert_eq!(set.remove(&4), Some(4));
+}
+
+#[test]
+fn iter_while_concurrent() {
+    const STEPS: usize = if cfg!(miri) { 16 } else { 1024 };
+
+    let set = (0..16).collect::<OrderedListSet<_>>();
+    let barrier = Barrier::new(2);
+    thread::scope(|s| {
+        s.spawn(|_| {
+            for _ in 0..STEPS {
+                assert_eq!(set.iter_while(|k| *k < 8), 8);
+                // the writer goes past the stop while this thread waits
+                let _ = barrier.wait();
+                let _ = barrier.wait();
+            }
+        });
+        s.spawn(|_| {
+            let mut rng = thread_rng();
+            for _ in 0..STEPS {
+                let _ = barrier.wait();
+                let key = rng.gen_range(8..16);
+                assert_eq!(set.remove(&key), Some(key));
+                set.insert(key).unwrap();
+                let _ = barrier.wait();
+            }
+        });
+    })
+    .unwrap();
+    assert_eq!(set, (0..16).collect::<Vec<_>>());
+}
+
+#[test]
+fn approx_len_quiescent() {
+    const THREADS: usize = if cfg!(miri) { 2 } else { 8 };
+    const ROUNDS: usize = if cfg!(miri) { 2 } else { 8 };
+    const STEPS: usize = if cfg!(miri) { 32 } else { 512 };
+
+    let set = OrderedListSet::new();
+    for _ in 0..ROUNDS {
+        thread::scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|_| {
+                    let mut rng = thread_rng();
+                    for _ in 0..STEPS {
+                        let key = rng.gen_range(0..64);
+                        match rng.gen_range(0..7) {
+                            0 | 1 => {
+                                let _ = set.insert(key);
+                            }
+                            2 => {
+                                let _ = set.remove(&key);
+                            }
+                            3 => {
+                                let _ = set.pop_min();
+                            }
+                            4 => {
+                                let _ = set.pop_max();
+                            }
+                            5 => {
+                                let _ = set.insert_sorted_batch(vec![key, key + 1, key + 2]);
+                            }
+                            _ => {
+                                let _ = set.lower_bound(&key).remove_current();
+                            }
+                        }
+                        // off by at most the removals in progress
+                        assert!(set.approx_len() <= 66 + THREADS);
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        // exact once every operation is done
+        assert_eq!(set.approx_len(), set.len_exact());
+    }
+}
+
+#[test]
+fn is_empty() {
+    let set = OrderedListSet::new();
+    assert!(set.is_empty());
+    set.insert(2).unwrap();
+    set.insert(1).unwrap();
+    assert!(!set.is_empty());
+    assert_eq!(set.remove(&1), Some(1));
+    assert!(!set.is_empty());
+    assert_eq!(set.remove(&2), Some(2));
+    assert!(set.is_empty());
+}
+
+#[test]
+fn is_empty_concurrent() {
+    const STEPS: usize = if cfg!(miri) { 64 } else { 4096 };
+
+    let set = OrderedListSet::new();
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        // a single key flips the set between empty and not
+        s.spawn(|_| {
+            for _ in 0..STEPS {
+                set.insert(0).unwrap();
+                assert!(!set.is_empty());
+                assert_eq!(set.remove(&0), Some(0));
+            }
+            done.store(true, Release);
+        });
+        for _ in 0..2 {
+            // checkers neither block the flips nor deadlock with them
+            s.spawn(|_| {
+                while !done.load(Acquire) {
+                    let _ = set.is_empty();
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert!(set.is_empty());
+}
+
+#[test]
+fn remove_head_concurrent() {
+    const KEYS: usize = if cfg!(miri) { 64 } else { 4096 };
+
+    let set = OrderedListSet::new();
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        // keys are inserted in increasing order, so the remover always removes the head
+        s.spawn(|_| {
+            for i in 0..KEYS {
+                set.insert(i).unwrap();
+            }
+        });
+        s.spawn(|_| {
+            for i in 0..KEYS {
+                while set.remove(&i).is_none() {}
+            }
+            done.store(true, Release);
+        });
+        // traversals hold the locks of the nodes being removed
+        s.spawn(|_| {
+            while !done.load(Acquire) {
+                assert!(!set.contains(&KEYS));
+            }
+        });
+    })
+    .unwrap();
+    assert!(set.iter().next().is_none());
+}
+
+#[test]
+fn iter_remove_race() {
+    const KEYS: usize = if cfg!(miri) { 16 } else { 1024 };
+    const ROUNDS: usize = if cfg!(miri) { 2 } else { 32 };
+
+    let set = OrderedListSet::new();
+    for _ in 0..ROUNDS {
+        for i in 0..KEYS {
+            set.insert(i).unwrap();
+        }
+        thread::scope(|s| {
+            // the yielded elements stay valid while the remover waits for them
+            s.spawn(|_| {
+                // the remover goes in increasing order, so a suffix of the keys is left
+                let elements = set.iter().collect::<Vec<_>>();
+                assert!(elements.windows(2).all(|k| *k[1] == *k[0] + 1));
+                if let Some(last) = elements.last() {
+                    assert_eq!(**last, KEYS - 1);
+                }
+            });
+            s.spawn(|_| {
+                for i in 0..KEYS {
+                    assert_eq!(set.remove(&i), Some(i));
+                }
+            });
+        })
+        .unwrap();
+        assert!(set.iter().next().is_none());
+    }
+}
+
+#[test]
+fn cursor() {
+    let set = [1, 3, 5].iter().copied().collect::<OrderedListSet<_>>();
+
+    let mut cursor = set.lower_bound(&2);
+    assert_eq!(cursor.current(), Some(&3));
+    // out of order, or equal to a neighbor
+    assert_eq!(cursor.insert_before(1), Err(1));
+    assert_eq!(cursor.insert_before(3), Err(3));
+    assert_eq!(cursor.insert_before(4), Err(4));
+    assert_eq!(cursor.insert_before(2), Ok(()));
+    assert_eq!(cursor.current(), Some(&3));
+    assert_eq!(cursor.remove_current(), Some(3));
+    assert_eq!(cursor.current(), Some(&5));
+    assert_eq!(cursor.insert_before(4), Ok(()));
+    cursor.move_next();
+    assert_eq!(cursor.current(), None);
+    assert_eq!(cursor.remove_current(), None);
+    cursor.move_next();
+    assert_eq!(cursor.insert_before(5), Err(5));
+    assert_eq!(cursor.insert_before(6), Ok(()));
+    drop(cursor);
+    assert_eq!(set, vec![1, 2, 4, 5, 6]);
+    assert_eq!(set.len(), 5);
+
+    // at the head
+    let mut cursor = set.lower_bound(&0);
+    assert_eq!(cursor.insert_before(1), Err(1));
+    assert_eq!(cursor.insert_before(0), Ok(()));
+    assert_eq!(cursor.remove_current(), Some(1));
+    drop(cursor);
+    assert_eq!(set, vec![0, 2, 4, 5, 6]);
+
+    let empty = OrderedListSet::new();
+    let mut cursor = empty.lower_bound(&0);
+    assert_eq!(cursor.current(), None);
+    assert_eq!(cursor.insert_before(0), Ok(()));
+    drop(cursor);
+    assert_eq!(empty, vec![0]);
+}
+
+/// A set whose modifications go through a cursor.
+struct CursorSet<T>(OrderedListSet<T>);
+
+impl<T> Default for CursorSet<T> {
+    fn default() -> Self {
+        Self(OrderedListSet::new())
+    }
+}
+
+impl<T: Ord + Clone + Send + Sync> ConcurrentSet<T> for CursorSet<T> {
+    fn contains(&self, key: &T) -> bool {
+        self.0.lower_bound(key).current() == Some(key)
+    }
+
+    fn insert(&self, key: T) -> bool {
+        self.0.lower_bound(&key).insert_before(key).is_ok()
+    }
+
+    fn remove(&self, key: &T) -> bool {
+        let mut cursor = self.0.lower_bound(key);
+        cursor.current() == Some(key) && cursor.remove_current().is_some()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn to_vec(&self) -> Vec<T> {
+        self.0.snapshot()
+    }
+}
+
+#[test]
+fn cursor_stress_sequential() {
+    common::stress_sequential::<CursorSet<_>>();
+}
+
+#[test]
+fn cursor_stress_concurrent() {
+    common::stress_concurrent::<CursorSet<_>>();
+}
+
+#[test]
+fn cursor_log_concurrent() {
+    common::log_concurrent::<CursorSet<_>>();
+}
+
+#[test]
+fn cursor_iter_consistent() {
+    common::iter_consistent::<CursorSet<_>>();
+}
+
+#[test]
+fn stress_sequential() {
+    common::stress_sequential::<OrderedListSet<_>>();
+}
+
+#[test]
+fn stress_concurrent() {
+    common::stress_concurrent::<OrderedListSet<_>>();
+}
+
+#[test]
+fn log_concurrent() {
+    common::log_concurrent::<OrderedListSet<_>>();
+}
+
+#[test]
+fn iter_consistent() {
+    common::iter_consistent::<OrderedListSet<_>>();
 }
@@ -0,0 +1,56 @@
+mod common;
+
+use common::ConcurrentSet;
+
+use cs431_homework::LockSet;
+
+impl<T: Ord + Clone + Send + Sync> ConcurrentSet<T> for LockSet<T> {
+    fn contains(&self, key: &T) -> bool {
+        LockSet::contains(self, key)
+    }
+
+    fn insert(&self, key: T) -> bool {
+        LockSet::insert(self, key).is_ok()
+    }
+
+    fn remove(&self, key: &T) -> bool {
+        LockSet::remove(self, key).is_some()
+    }
+
+    fn len(&self) -> usize {
+        LockSet::len(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        LockSet::is_empty(self)
+    }
+
+    fn to_vec(&self) -> Vec<T> {
+        self.snapshot()
+    }
+}
+
+#[test]
+fn smoke() {
+    common::smoke::<LockSet<_>>();
+}
+
+#[test]
+fn stress_sequential() {
+    common::stress_sequential::<LockSet<_>>();
+}
+
+#[test]
+fn stress_concurrent() {
+    common::stress_concurrent::<LockSet<_>>();
+}
+
+#[test]
+fn log_concurrent() {
+    common::log_concurrent::<LockSet<_>>();
+}
+
+#[test]
+fn iter_consistent() {
+    common::iter_consistent::<LockSet<_>>();
+}
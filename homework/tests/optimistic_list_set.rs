@@ -0,0 +1,82 @@
+mod common;
+
+use common::{BorrowSet, ConcurrentSet};
+use crossbeam_epoch::pin;
+
+use cs431_homework::OptimisticListSet;
+
+impl<T: Ord + Clone + Send + Sync> ConcurrentSet<T> for OptimisticListSet<T> {
+    fn contains(&self, key: &T) -> bool {
+        OptimisticListSet::contains(self, key)
+    }
+
+    fn insert(&self, key: T) -> bool {
+        OptimisticListSet::insert(self, key).is_ok()
+    }
+
+    fn remove(&self, key: &T) -> bool {
+        OptimisticListSet::remove(self, key).is_some()
+    }
+
+    fn len(&self) -> usize {
+        OptimisticListSet::len(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        OptimisticListSet::is_empty(self)
+    }
+
+    fn to_vec(&self) -> Vec<T> {
+        self.iter(&pin()).cloned().collect()
+    }
+}
+
+impl BorrowSet for OptimisticListSet<String> {
+    fn contains_str(&self, key: &str) -> bool {
+        OptimisticListSet::contains(self, key)
+    }
+
+    fn remove_str(&self, key: &str) -> bool {
+        OptimisticListSet::remove(self, key).is_some()
+    }
+}
+
+#[test]
+fn smoke() {
+    common::smoke::<OptimisticListSet<_>>();
+}
+
+#[test]
+fn borrowed_keys() {
+    common::borrowed_keys::<OptimisticListSet<_>>();
+}
+
+#[test]
+fn remove_head_concurrent() {
+    common::remove_head_concurrent::<OptimisticListSet<_>>();
+}
+
+#[test]
+fn panicking_clone() {
+    common::panicking_clone::<OptimisticListSet<_>>();
+}
+
+#[test]
+fn stress_sequential() {
+    common::stress_sequential::<OptimisticListSet<_>>();
+}
+
+#[test]
+fn stress_concurrent() {
+    common::stress_concurrent::<OptimisticListSet<_>>();
+}
+
+#[test]
+fn log_concurrent() {
+    common::log_concurrent::<OptimisticListSet<_>>();
+}
+
+#[test]
+fn iter_consistent() {
+    common::iter_consistent::<OptimisticListSet<_>>();
+}